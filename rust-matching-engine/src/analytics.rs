@@ -0,0 +1,816 @@
+//! Market analytics
+//!
+//! Three kinds of analytics live here: read-only walks over the live book,
+//! opt-in accumulators fed from the trade emission point, and a snapshot of
+//! the book's maintained counters for metrics scraping.
+//!
+//! Book walks never mutate the book. They use each level's live total, so
+//! cancelled-but-queued orders never contribute, and they ignore self-trade
+//! prevention since they don't know who the taker is.
+
+use std::collections::VecDeque;
+
+use crate::units::BASIS_POINTS_PER_DOLLAR;
+use crate::{OrderBook, Price, Quantity, Side, Timestamp};
+
+/// Ticks on each side of the touch counted as depth by `implied_probability`
+/// and `pressure`
+const NEAR_TOUCH_TICKS: u64 = 5;
+/// Spread, in bps, at which `implied_probability` and `pressure` are halved
+const HALVING_SPREAD_BPS: f64 = 100.0;
+/// Near-touch depth, in shares, at which `implied_probability` has half confidence
+const CONFIDENCE_DEPTH: f64 = 1_000.0;
+
+/// A range of trade sizes: `lower..upper`, unbounded above if `upper` is None
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QtyBucket {
+    /// Smallest quantity in the bucket (inclusive)
+    pub lower: Quantity,
+    /// Exclusive upper bound, None for the last bucket
+    pub upper: Option<Quantity>,
+}
+
+/// Histogram of executed trade quantities
+#[derive(Debug, Clone)]
+pub struct FillSizeHistogram {
+    /// Sorted, de-duplicated bucket boundaries
+    bounds: Vec<Quantity>,
+    /// One count per bucket (`bounds.len() + 1` buckets)
+    counts: Vec<u64>,
+}
+
+impl FillSizeHistogram {
+    /// Create a histogram splitting sizes at the given boundaries
+    ///
+    /// Boundaries `[10, 100]` give the buckets `0..10`, `10..100` and `100..`.
+    pub fn new(mut bounds: Vec<Quantity>) -> Self {
+        bounds.sort_unstable();
+        bounds.dedup();
+        let counts = vec![0; bounds.len() + 1];
+        Self { bounds, counts }
+    }
+
+    /// Count one trade of `quantity`
+    pub fn record(&mut self, quantity: Quantity) {
+        let bucket = self.bounds.partition_point(|&bound| bound <= quantity);
+        self.counts[bucket] += 1;
+    }
+
+    /// Every bucket with its count, smallest sizes first
+    pub fn buckets(&self) -> Vec<(QtyBucket, u64)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| {
+                let lower = if i == 0 { 0 } else { self.bounds[i - 1] };
+                let upper = self.bounds.get(i).copied();
+                (QtyBucket { lower, upper }, count)
+            })
+            .collect()
+    }
+}
+
+/// The most recent trade prices, oldest first
+#[derive(Debug, Clone)]
+pub(crate) struct PriceWindow {
+    prices: VecDeque<Price>,
+    capacity: usize,
+}
+
+impl PriceWindow {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { prices: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Retain one trade price, evicting the oldest once full
+    pub(crate) fn record(&mut self, price: Price) {
+        if self.prices.len() == self.capacity {
+            self.prices.pop_front();
+        }
+        self.prices.push_back(price);
+    }
+}
+
+/// Last trade price changes over time, for `twap`
+#[derive(Debug, Clone)]
+pub(crate) struct PriceHistory {
+    /// `(since, price)` for each change, oldest first
+    changes: VecDeque<(Timestamp, Price)>,
+    /// How far back changes are kept, in microseconds
+    horizon: Timestamp,
+}
+
+impl PriceHistory {
+    pub(crate) fn new(horizon: Timestamp) -> Self {
+        Self { changes: VecDeque::new(), horizon }
+    }
+
+    /// Note the price traded at `now`, forgetting changes beyond the horizon
+    ///
+    /// The newest change before the horizon is kept, as the price then in force.
+    pub(crate) fn record(&mut self, now: Timestamp, price: Price) {
+        if self.changes.back().is_none_or(|&(_, last)| last != price) {
+            self.changes.push_back((now, price));
+        }
+        let cutoff = now.saturating_sub(self.horizon);
+        while self.changes.get(1).is_some_and(|&(since, _)| since <= cutoff) {
+            self.changes.pop_front();
+        }
+    }
+}
+
+/// Two users in a fixed order, so a pair is the same whoever was the maker
+pub(crate) fn counterparty_pair<'a>(a: &'a str, b: &'a str) -> (&'a str, &'a str) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Observability counters for a book, read in one call
+///
+/// Every field comes from a maintained counter or the ends of the price maps,
+/// which only hold levels with live quantity, so building it is O(1) apart
+/// from the best-price lookups (O(log P)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BookMetrics {
+    /// Trades executed since the book was created
+    pub total_trades: u64,
+    /// Quantity traded since the book was created
+    pub total_volume: Quantity,
+    /// `price * quantity` traded since the book was created, in bps x shares
+    /// (saturating)
+    pub total_notional: u128,
+    /// Open or partially filled orders
+    pub active_orders: usize,
+    /// Price levels on the bid side with live orders
+    pub bid_levels: usize,
+    /// Price levels on the ask side with live orders
+    pub ask_levels: usize,
+    /// Cancelled orders still queued awaiting lazy cleanup
+    pub pending_cancelled: usize,
+    /// Best bid price with live orders
+    pub best_bid: Option<Price>,
+    /// Best ask price with live orders
+    pub best_ask: Option<Price>,
+    /// Best ask minus best bid (None if either side is empty or the book is locked)
+    pub spread: Option<Price>,
+    /// Price of the most recent trade
+    pub last_trade_price: Option<Price>,
+}
+
+/// Expected outcome of sweeping the book up to a protective limit price
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlippageReport {
+    /// Quantity that would fill at or inside the limit
+    pub filled_quantity: Quantity,
+    /// Quantity left unfilled because the limit (or the book) ran out first
+    pub unfilled_quantity: Quantity,
+    /// Size-weighted average fill price (None if nothing would fill)
+    pub average_price: Option<Price>,
+    /// Best opposite price at the start of the sweep
+    pub best_price: Option<Price>,
+    /// Distance between the average fill price and the best price, in bps
+    pub slippage_bps: Price,
+    /// True if the walk stopped at `limit_price` with quantity still unfilled
+    pub limit_reached: bool,
+}
+
+/// The book's price read as the probability of its outcome
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbabilityEstimate {
+    /// Midpoint over 10000: the market's probability the outcome happens
+    pub probability: f64,
+    /// How far to trust it, from 0 (no signal) towards 1: higher for a
+    /// tighter spread and more size near the touch
+    pub confidence: f64,
+}
+
+impl OrderBook {
+    /// All observability counters as one consistent snapshot
+    pub fn metrics(&self) -> BookMetrics {
+        BookMetrics {
+            total_trades: self.total_trades,
+            total_volume: self.total_volume,
+            total_notional: self.total_notional,
+            active_orders: self.live_orders,
+            bid_levels: self.bids.len(),
+            ask_levels: self.asks.len(),
+            pending_cancelled: self.queued_cancelled,
+            best_bid: self.best_bid(),
+            best_ask: self.best_ask(),
+            spread: self.spread(),
+            last_trade_price: self.last_trade_price,
+        }
+    }
+
+    /// Realized volatility over the last `window` trades, in basis points
+    ///
+    /// The population standard deviation of the simple returns between
+    /// consecutive trade prices, each in bps of the earlier price. Returns
+    /// None if fewer than `window` trades are retained (at most
+    /// `volatility_window`, and none unless it is configured), or if
+    /// `window` is below 2, which leaves no return to measure.
+    pub fn realized_volatility(&self, window: usize) -> Option<f64> {
+        let prices = &self.price_window.as_ref()?.prices;
+        if window < 2 || prices.len() < window {
+            return None;
+        }
+        let prices: Vec<f64> = prices.range(prices.len() - window..).map(|&p| p as f64).collect();
+        let returns: Vec<f64> = prices
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]) / pair[0] * BASIS_POINTS_PER_DOLLAR as f64)
+            .collect();
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n;
+        Some(variance.sqrt())
+    }
+
+    /// Quantity `user_a` and `user_b` have traded with each other, either way round
+    ///
+    /// Always 0 unless `track_counterparties` is configured. Volumes count
+    /// from book creation or the last `reset_statistics`.
+    pub fn counterparty_volume(&self, user_a: &str, user_b: &str) -> Quantity {
+        let Some(volumes) = &self.counterparty_volumes else {
+            return 0;
+        };
+        let (a, b) = counterparty_pair(user_a, user_b);
+        volumes.get(&(a.to_string(), b.to_string())).copied().unwrap_or(0)
+    }
+
+    /// Time-weighted average of the last trade price over the last `window` microseconds
+    ///
+    /// Each price counts for as long as it stood, up to the clock's `now`,
+    /// however much traded at it. The average is rounded with the book's
+    /// `RoundingMode`. Returns None until the history covers the whole window:
+    /// without `twap_horizon` configured, for a window longer than it, before
+    /// the first trade at least `window` ago, or for a zero window.
+    pub fn twap(&self, window: Timestamp) -> Option<Price> {
+        let changes = &self.price_history.as_ref()?.changes;
+        let now = self.clock.now();
+        let start = now.checked_sub(window).filter(|_| window > 0)?;
+        let first = changes.iter().rposition(|&(since, _)| since <= start)?;
+
+        let mut weighted = 0u128;
+        let mut held = changes.range(first..).peekable();
+        while let Some(&(since, price)) = held.next() {
+            let until = held.peek().map_or(now, |&&(next, _)| next);
+            weighted += price as u128 * (until - since.max(start)) as u128;
+        }
+        Some(self.config.rounding_mode.div(weighted, window as u128) as Price)
+    }
+
+    /// Estimate the fills for a `side` taker of `quantity` limited to `limit_price`
+    ///
+    /// Walks the opposite side best-first, stopping at the first level beyond
+    /// the limit. The average price is rounded with the book's `RoundingMode`.
+    pub fn slippage(&self, side: Side, quantity: Quantity, limit_price: Price) -> SlippageReport {
+        let mut filled: Quantity = 0;
+        let mut notional: u128 = 0;
+        let mut best_price = None;
+        let mut limit_reached = false;
+
+        for (price, available) in self.live_levels(side.opposite()) {
+            if filled == quantity {
+                break;
+            }
+            if !side.reaches(limit_price, price) {
+                limit_reached = true;
+                break;
+            }
+
+            best_price.get_or_insert(price);
+            let take = available.min(quantity - filled);
+            filled += take;
+            notional += price as u128 * take as u128;
+        }
+
+        let average_price = (filled > 0)
+            .then(|| self.config.rounding_mode.div(notional, filled as u128) as Price);
+        let slippage_bps = match (average_price, best_price) {
+            (Some(average), Some(best)) => average.abs_diff(best),
+            _ => 0,
+        };
+
+        SlippageReport {
+            filled_quantity: filled,
+            unfilled_quantity: quantity - filled,
+            average_price,
+            best_price,
+            slippage_bps,
+            limit_reached,
+        }
+    }
+
+    /// The price at which a `side` taker of `quantity` would have filled `fraction` of it
+    ///
+    /// Walks the opposite side best-first like `slippage`, returning the price
+    /// of the level where the cumulative fill first reaches
+    /// `ceil(quantity * fraction)`. E.g. a fraction of 0.9 gives the price the
+    /// 90th percentile share trades at.
+    ///
+    /// Returns None if `fraction` is outside `(0, 1]`, `quantity` is 0, or
+    /// the book is too thin to reach the fraction.
+    pub fn marginal_price(&self, side: Side, quantity: Quantity, fraction: f64) -> Option<Price> {
+        if quantity == 0 || !(fraction > 0.0 && fraction <= 1.0) {
+            return None;
+        }
+        let target = ((quantity as f64 * fraction).ceil() as Quantity).clamp(1, quantity);
+
+        let mut filled: Quantity = 0;
+        for (price, available) in self.live_levels(side.opposite()) {
+            filled += available;
+            if filled >= target {
+                return Some(price);
+            }
+        }
+        None
+    }
+
+    /// Quantity and notional a `side` taker must trade to move the opposite
+    /// best price to `target_price`
+    ///
+    /// Takes every live level strictly better than the target, so that the
+    /// best price left is at or beyond it: a buy moving the best ask from
+    /// 6500 to 7000 clears everything offered below 7000. Returns `(0, 0)` if
+    /// the best price is already there, and None if clearing that much would
+    /// leave the side empty, as the book isn't deep enough to hold the target.
+    pub fn cost_to_move(&self, side: Side, target_price: Price) -> Option<(Quantity, u128)> {
+        let mut quantity: Quantity = 0;
+        let mut notional: u128 = 0;
+        for (price, available) in self.live_levels(side.opposite()) {
+            if side.reaches(price, target_price) {
+                // This level is at or beyond the target
+                return Some((quantity, notional));
+            }
+            quantity += available;
+            notional += price as u128 * available as u128;
+        }
+        None
+    }
+
+    /// Size-weighted average price of the resting liquidity in `side`'s top `levels`
+    ///
+    /// The average is rounded with the book's `RoundingMode`. Returns None if
+    /// the side has no live quantity or `levels` is 0.
+    pub fn resting_vwap(&self, side: Side, levels: usize) -> Option<Price> {
+        let (quantity, notional) = self.live_levels(side).take(levels).fold(
+            (0u128, 0u128),
+            |(quantity, notional), (price, available)| {
+                (quantity + available as u128, notional + price as u128 * available as u128)
+            },
+        );
+        (quantity > 0).then(|| self.config.rounding_mode.div(notional, quantity) as Price)
+    }
+
+    /// The midpoint as a probability, with a confidence for it
+    ///
+    /// Confidence multiplies a spread factor, `1 / (1 + spread / 100)`, by a
+    /// depth factor, `d / (d + 1000)`, where `d` is the thinner side's live
+    /// quantity within 5 ticks of its best price. A locked book has no spread
+    /// to discount. Returns None if either side has no live orders, as a
+    /// one-sided book has no midpoint.
+    pub fn implied_probability(&self) -> Option<ProbabilityEstimate> {
+        let mid = self.midpoint()?;
+        let bid = self.best_live_price(Side::Buy)?;
+        let ask = self.best_live_price(Side::Sell)?;
+        let depth = self
+            .liquidity_near_touch(Side::Buy, NEAR_TOUCH_TICKS)
+            .min(self.liquidity_near_touch(Side::Sell, NEAR_TOUCH_TICKS)) as f64;
+
+        let spread_factor = 1.0 / (1.0 + ask.saturating_sub(bid) as f64 / HALVING_SPREAD_BPS);
+        let depth_factor = depth / (depth + CONFIDENCE_DEPTH);
+        Some(ProbabilityEstimate {
+            probability: mid as f64 / BASIS_POINTS_PER_DOLLAR as f64,
+            confidence: spread_factor * depth_factor,
+        })
+    }
+
+    /// Buying versus selling pressure, from -1 (all selling) to 1 (all buying)
+    ///
+    /// With `b` and `a` the live bid and ask quantity within 5 ticks of each
+    /// best price, the depth imbalance is `(b - a) / (b + a)`. With `v_buy`
+    /// and `v_sell` the volume of the retained `recent_trades` whose taker
+    /// bought or sold, the trade flow is `(v_buy - v_sell) / (v_buy + v_sell)`.
+    /// The pressure is their mean, or the imbalance alone if no trades are
+    /// retained, times `1 / (1 + spread / 100)`, so a wide spread mutes it
+    /// (a locked book has spread 0). Returns None if either side has no live
+    /// orders.
+    pub fn pressure(&self) -> Option<f64> {
+        let bid = self.best_live_price(Side::Buy)?;
+        let ask = self.best_live_price(Side::Sell)?;
+        let bids = self.liquidity_near_touch(Side::Buy, NEAR_TOUCH_TICKS) as f64;
+        let asks = self.liquidity_near_touch(Side::Sell, NEAR_TOUCH_TICKS) as f64;
+        let imbalance = (bids - asks) / (bids + asks);
+
+        let (mut bought, mut sold) = (0.0, 0.0);
+        for trade in &self.trade_history {
+            match trade.taker_side {
+                Side::Buy => bought += trade.quantity as f64,
+                Side::Sell => sold += trade.quantity as f64,
+            }
+        }
+        let direction = if bought + sold > 0.0 {
+            (imbalance + (bought - sold) / (bought + sold)) / 2.0
+        } else {
+            imbalance
+        };
+        Some(direction / (1.0 + ask.saturating_sub(bid) as f64 / HALVING_SPREAD_BPS))
+    }
+
+    /// Live quantity on `side` within `ticks` tick-sized levels of its best price
+    ///
+    /// Counts the best price and the `ticks - 1` ticks behind it, whether or
+    /// not orders rest there, so sparse books show less than the top `ticks`
+    /// levels would. Without a `tick_size` a tick is 1 bps and this is a
+    /// plain price range. Returns 0 if the side is empty or `ticks` is 0.
+    pub fn liquidity_near_touch(&self, side: Side, ticks: u64) -> Quantity {
+        let Some(best) = self.best_live_price(side) else {
+            return 0;
+        };
+        if ticks == 0 {
+            return 0;
+        }
+        let span = self.config.tick_size.unwrap_or(1).saturating_mul(ticks - 1);
+        let within = |price: Price| match side {
+            Side::Buy => price >= best.saturating_sub(span),
+            Side::Sell => price <= best.saturating_add(span),
+        };
+        self.live_levels(side)
+            .take_while(|&(price, _)| within(price))
+            .map(|(_, quantity)| quantity)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{create_test_order, order};
+    use crate::{MockClock, OrderBookConfig};
+    use std::sync::Arc;
+
+    fn book_with_asks(levels: &[(u64, u64)]) -> OrderBook {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        for (i, &(price, quantity)) in levels.iter().enumerate() {
+            let seller = format!("seller{}", i);
+            let id = i as u64 + 1;
+            let order = create_test_order(id, &seller, Side::Sell, price, quantity, i as u64);
+            book.process_limit_order(order).unwrap();
+        }
+        book
+    }
+
+    #[test]
+    fn test_slippage_stops_at_limit() {
+        let book = book_with_asks(&[(5000, 100), (5100, 100), (5300, 100)]);
+
+        // Limit 5200 stops before the 5300 level with 50 unfilled
+        let report = book.slippage(Side::Buy, 250, 5200);
+        assert_eq!(report.filled_quantity, 200);
+        assert_eq!(report.unfilled_quantity, 50);
+        assert_eq!(report.best_price, Some(5000));
+        assert_eq!(report.average_price, Some(5050));
+        assert_eq!(report.slippage_bps, 50);
+        assert!(report.limit_reached);
+    }
+
+    #[test]
+    fn test_slippage_fully_filled() {
+        let book = book_with_asks(&[(5000, 100), (5100, 100)]);
+
+        let report = book.slippage(Side::Buy, 150, 6000);
+        assert_eq!(report.filled_quantity, 150);
+        assert_eq!(report.unfilled_quantity, 0);
+        // (5000 * 100 + 5100 * 50) / 150 = 5033.33
+        assert_eq!(report.average_price, Some(5033));
+        assert!(!report.limit_reached);
+
+        let report = book.slippage(Side::Sell, 10, 1);
+        assert_eq!(report.average_price, None);
+        assert_eq!(report.unfilled_quantity, 10);
+    }
+
+    #[test]
+    fn test_resting_vwap() {
+        let mut book = book_with_asks(&[(5000, 100), (5100, 300), (5200, 50)]);
+
+        // (5000 * 100 + 5100 * 300) / 400 = 5075
+        assert_eq!(book.resting_vwap(Side::Sell, 2), Some(5075));
+        assert_eq!(book.resting_vwap(Side::Sell, 1), Some(5000));
+
+        // Cancelled size doesn't count, and an emptied level is skipped
+        book.cancel_order(1).unwrap();
+        // (5100 * 300 + 5200 * 50) / 350 = 5114.29
+        assert_eq!(book.resting_vwap(Side::Sell, 2), Some(5114));
+
+        assert_eq!(book.resting_vwap(Side::Buy, 2), None);
+        assert_eq!(book.resting_vwap(Side::Sell, 0), None);
+    }
+
+    #[test]
+    fn test_liquidity_near_touch() {
+        let config = OrderBookConfig { tick_size: Some(100), ..Default::default() };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        for (id, side, price) in [
+            (1, Side::Sell, 5000),
+            (2, Side::Sell, 5100),
+            (3, Side::Sell, 5400),
+            (4, Side::Buy, 4900),
+            (5, Side::Buy, 4600),
+        ] {
+            book.process_limit_order(order(id, side, price, 10 * id)).unwrap();
+        }
+
+        assert_eq!(book.liquidity_near_touch(Side::Sell, 1), 10);
+        assert_eq!(book.liquidity_near_touch(Side::Sell, 2), 30);
+        // The empty ticks at 5200 and 5300 add nothing, unlike the top 3 levels
+        assert_eq!(book.liquidity_near_touch(Side::Sell, 4), 30);
+        assert_eq!(book.liquidity_near_touch(Side::Sell, 5), 60);
+        assert_eq!(book.liquidity_near_touch(Side::Buy, 3), 40);
+        assert_eq!(book.liquidity_near_touch(Side::Buy, 4), 90);
+        assert_eq!(book.liquidity_near_touch(Side::Buy, 0), 0);
+
+        // Without a tick size it is a range of prices
+        let book = book_with_asks(&[(5000, 100), (5003, 50)]);
+        assert_eq!(book.liquidity_near_touch(Side::Sell, 3), 100);
+        assert_eq!(book.liquidity_near_touch(Side::Sell, 4), 150);
+        assert_eq!(book.liquidity_near_touch(Side::Buy, 4), 0);
+    }
+
+    #[test]
+    fn test_implied_probability() {
+        let quoted = |bid: Price, ask: Price, quantity: Quantity| {
+            let mut book = book_with_asks(&[(ask, quantity)]);
+            let bid = create_test_order(9, "buyer", Side::Buy, bid, quantity, 9);
+            book.process_limit_order(bid).unwrap();
+            book.implied_probability().unwrap()
+        };
+
+        let tight = quoted(6450, 6550, 1_000);
+        let wide = quoted(6000, 7000, 1_000);
+        let thin = quoted(6450, 6550, 10);
+        for estimate in [tight, wide, thin] {
+            assert!((estimate.probability - 0.65).abs() < 1e-9);
+        }
+        // Spread factor 1/2 and depth factor 1/2
+        assert!((tight.confidence - 0.25).abs() < 1e-9);
+        assert!(tight.confidence > wide.confidence);
+        assert!(tight.confidence > thin.confidence);
+
+        // One-sided and empty books have no midpoint
+        assert_eq!(book_with_asks(&[(6500, 100)]).implied_probability(), None);
+        assert_eq!(book_with_asks(&[]).implied_probability(), None);
+    }
+
+    #[test]
+    fn test_pressure() {
+        let config = OrderBookConfig {
+            trade_history_capacity: 10,
+            tick_size: Some(10),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        let submit = |book: &mut OrderBook, id, side, price, quantity| {
+            book.process_limit_order(order(id, side, price, quantity)).unwrap();
+        };
+        submit(&mut book, 1, Side::Sell, 5010, 100);
+        submit(&mut book, 2, Side::Buy, 4990, 900);
+        submit(&mut book, 3, Side::Buy, 4960, 100);
+        submit(&mut book, 4, Side::Buy, 4900, 500);
+
+        // Imbalance (1000 - 100) / 1100, muted by the 20 bps spread; 4900 is
+        // more than 5 ticks of 10 bps from the touch
+        let heavy = book.pressure().unwrap();
+        assert!((heavy - (900.0 / 1100.0) / 1.2).abs() < 1e-9);
+        assert!(heavy > 0.5);
+
+        // A seller hitting the bids drags it negative: imbalance
+        // (700 - 100) / 800, trade flow -1
+        submit(&mut book, 5, Side::Sell, 4990, 300);
+        let sold = book.pressure().unwrap();
+        assert!((sold - (600.0 / 800.0 - 1.0) / 2.0 / 1.2).abs() < 1e-9);
+        assert!(sold < 0.0);
+
+        assert_eq!(book_with_asks(&[(5000, 100)]).pressure(), None);
+        assert_eq!(book_with_asks(&[]).pressure(), None);
+    }
+
+    #[test]
+    fn test_cost_to_move() {
+        let book = book_with_asks(&[(6500, 100), (6600, 200), (6800, 50), (7000, 10)]);
+
+        // Moving YES from 0.65 to 0.70 clears everything offered below 7000
+        let notional = 6500 * 100 + 6600 * 200 + 6800 * 50;
+        assert_eq!(book.cost_to_move(Side::Buy, 7000), Some((350, notional)));
+        assert_eq!(book.cost_to_move(Side::Buy, 6700), Some((300, 6500 * 100 + 6600 * 200)));
+        assert_eq!(book.cost_to_move(Side::Buy, 6500), Some((0, 0)));
+
+        // Clearing the 7000 level too leaves no asks at all
+        assert_eq!(book.cost_to_move(Side::Buy, 7100), None);
+        assert_eq!(book.cost_to_move(Side::Sell, 6000), None);
+    }
+
+    #[test]
+    fn test_marginal_price() {
+        let book = book_with_asks(&[(5000, 100), (5100, 100)]);
+
+        // Half of 150 is 75, within the first level; 90% is 135, in the second
+        assert_eq!(book.marginal_price(Side::Buy, 150, 0.5), Some(5000));
+        assert_eq!(book.marginal_price(Side::Buy, 150, 0.9), Some(5100));
+        // Exactly the first level's depth
+        assert_eq!(book.marginal_price(Side::Buy, 200, 0.5), Some(5000));
+
+        // Too thin, or nothing on the bid side
+        assert_eq!(book.marginal_price(Side::Buy, 300, 0.9), None);
+        assert_eq!(book.marginal_price(Side::Sell, 10, 0.5), None);
+        assert_eq!(book.marginal_price(Side::Buy, 150, 0.0), None);
+        assert_eq!(book.marginal_price(Side::Buy, 150, 1.5), None);
+    }
+
+    #[test]
+    fn test_fill_size_histogram() {
+        let config = OrderBookConfig {
+            fill_size_buckets: Some(vec![10, 100]),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        let mut next_id = 1;
+        for quantity in [5, 10, 50, 99, 100, 500] {
+            for side in [Side::Sell, Side::Buy] {
+                book.process_limit_order(order(next_id, side, 5000, quantity)).unwrap();
+                next_id += 1;
+            }
+        }
+
+        let histogram = book.fill_size_histogram();
+        assert_eq!(
+            histogram,
+            vec![
+                (QtyBucket { lower: 0, upper: Some(10) }, 1),
+                (QtyBucket { lower: 10, upper: Some(100) }, 3),
+                (QtyBucket { lower: 100, upper: None }, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_realized_volatility() {
+        let config = OrderBookConfig { volatility_window: Some(4), ..Default::default() };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        let mut next_id = 1;
+        let mut trade_at = |book: &mut OrderBook, price: Price| {
+            for side in [Side::Sell, Side::Buy] {
+                book.process_limit_order(order(next_id, side, price, 10)).unwrap();
+                next_id += 1;
+            }
+        };
+
+        for _ in 0..3 {
+            trade_at(&mut book, 5000);
+        }
+        assert_eq!(book.realized_volatility(3), Some(0.0));
+        assert_eq!(book.realized_volatility(4), None);
+
+        // Returns of +100 bps up to 5050, then about -99 bps back down
+        trade_at(&mut book, 5050);
+        trade_at(&mut book, 5000);
+        let vol = book.realized_volatility(3).unwrap();
+        assert!((vol - 99.5).abs() < 0.1, "{}", vol);
+        assert!(book.realized_volatility(4).unwrap() > 0.0);
+        // Only four prices are retained
+        assert_eq!(book.realized_volatility(5), None);
+    }
+
+    #[test]
+    fn test_counterparty_volume_ignores_who_was_maker() {
+        let config = OrderBookConfig { track_counterparties: true, ..Default::default() };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        let mut next_id = 1;
+        let mut trade = |book: &mut OrderBook, maker: &str, taker: &str, quantity: Quantity| {
+            for (user, side) in [(maker, Side::Sell), (taker, Side::Buy)] {
+                let order = create_test_order(next_id, user, side, 5000, quantity, next_id);
+                book.process_limit_order(order).unwrap();
+                next_id += 1;
+            }
+        };
+
+        trade(&mut book, "alice", "bob", 10);
+        trade(&mut book, "bob", "alice", 20);
+        trade(&mut book, "alice", "bob", 5);
+        trade(&mut book, "alice", "carol", 7);
+        assert_eq!(book.counterparty_volume("alice", "bob"), 35);
+        assert_eq!(book.counterparty_volume("bob", "alice"), 35);
+        assert_eq!(book.counterparty_volume("alice", "carol"), 7);
+        assert_eq!(book.counterparty_volume("bob", "carol"), 0);
+
+        book.reset_statistics();
+        assert_eq!(book.counterparty_volume("alice", "bob"), 0);
+
+        // Not collected by default
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        trade(&mut book, "alice", "bob", 10);
+        assert_eq!(book.counterparty_volume("alice", "bob"), 0);
+    }
+
+    #[test]
+    fn test_twap_weights_by_time_held() {
+        let clock = Arc::new(MockClock::new(100));
+        let config = OrderBookConfig {
+            twap_horizon: Some(1_000),
+            clock: Some(clock.clone()),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        let mut next_id = 1;
+        let mut trade_at = |book: &mut OrderBook, price: Price| {
+            for side in [Side::Sell, Side::Buy] {
+                book.process_limit_order(order(next_id, side, price, 10)).unwrap();
+                next_id += 1;
+            }
+        };
+
+        assert_eq!(book.twap(100), None);
+        trade_at(&mut book, 5000);
+        clock.advance(200);
+        trade_at(&mut book, 6000);
+        clock.advance(100);
+
+        // 5000 for 200us, then 6000 for 100us
+        assert_eq!(book.twap(300), Some(5333));
+        assert_eq!(book.twap(100), Some(6000));
+        assert_eq!(book.twap(150), Some(5667));
+        // Cold start: no price known for the start of the window
+        assert_eq!(book.twap(301), None);
+        assert_eq!(book.twap(0), None);
+    }
+
+    #[test]
+    fn test_total_notional_beyond_u64() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let trade = |book: &mut OrderBook, id: u64, quantity: Quantity| {
+            for (id, side) in [(id, Side::Sell), (id + 1, Side::Buy)] {
+                book.process_limit_order(order(id, side, 9900, quantity)).unwrap();
+            }
+        };
+        trade(&mut book, 1, 1 << 62);
+        trade(&mut book, 3, 1 << 62);
+        assert_eq!(book.metrics().total_volume, 1 << 63);
+        assert_eq!(book.metrics().total_notional, 9900 * (1u128 << 63));
+        assert!(book.metrics().total_notional > u64::MAX as u128);
+
+        // Saturates instead of wrapping
+        book.total_notional = u128::MAX - 1;
+        trade(&mut book, 5, 10);
+        assert_eq!(book.total_notional, u128::MAX);
+    }
+
+    #[test]
+    fn test_fill_size_histogram_disabled_by_default() {
+        let book = book_with_asks(&[(5000, 100)]);
+        assert!(book.fill_size_histogram().is_empty());
+    }
+
+    #[test]
+    fn test_metrics_after_trade() {
+        let mut book = book_with_asks(&[(5000, 100), (5100, 100), (5200, 100)]);
+        let buy = create_test_order(10, "buyer", Side::Buy, 5100, 150, 10);
+        book.process_limit_order(buy).unwrap();
+        let bid = create_test_order(11, "buyer", Side::Buy, 4800, 30, 11);
+        book.process_limit_order(bid).unwrap();
+        book.cancel_order(3).unwrap();
+
+        assert_eq!(
+            book.metrics(),
+            BookMetrics {
+                total_trades: 2,
+                total_volume: 150,
+                total_notional: 100 * 5000 + 50 * 5100,
+                active_orders: 2,
+                bid_levels: 1,
+                ask_levels: 1,
+                pending_cancelled: 0,
+                best_bid: Some(4800),
+                best_ask: Some(5100),
+                spread: Some(300),
+                last_trade_price: Some(5100),
+            }
+        );
+    }
+
+    #[test]
+    fn test_metrics_ignore_cancelled_best_level() {
+        let mut book = book_with_asks(&[(5000, 100), (5100, 100)]);
+        let bid = create_test_order(3, "buyer", Side::Buy, 4900, 100, 3);
+        book.process_limit_order(bid).unwrap();
+        book.cancel_order(1).unwrap();
+
+        let metrics = book.metrics();
+        assert_eq!((metrics.best_bid, metrics.best_ask), (Some(4900), Some(5100)));
+        assert_eq!((metrics.spread, metrics.ask_levels), (Some(200), 1));
+        assert_eq!(book.midpoint(), Some(5000));
+    }
+}
@@ -0,0 +1,199 @@
+//! Archive of finished orders
+//!
+//! By default the order index keeps every order it has seen, filled and
+//! cancelled ones included. With `OrderBookConfig::order_archive_capacity`
+//! set, an order leaves the index once it is finished and off the book, and
+//! its final state moves to a bounded archive instead, so the index only
+//! holds what matching needs. A filled order leaves at its last fill. A
+//! cancelled order leaves when lazy deletion drops it from its level, since
+//! matching looks it up until then; a cancelled midpoint order leaves at once.
+//!
+//! The archive keeps the most recent `capacity` orders and evicts the oldest;
+//! a capacity of 0 discards finished orders outright. Archived ids still
+//! count as duplicates, but an evicted id may be reused.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{OrderBook, OrderBookError, OrderId, OrderStatus, Price, Side, Timestamp, UserId};
+
+/// The final state of an order that has left the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedOrder {
+    /// The order
+    pub order_id: OrderId,
+    /// User who placed the order
+    pub user_id: UserId,
+    /// Side the order rested on
+    pub side: Side,
+    /// Price the order rested at
+    pub price: Price,
+    /// Filled or Cancelled
+    pub status: OrderStatus,
+    /// The order's `client_order_id`
+    pub client_order_id: Option<String>,
+    /// When the order left the index
+    pub archived_at: Timestamp,
+}
+
+/// Recently finished orders, oldest evicted first
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OrderArchive {
+    /// Number of orders retained (None = archiving disabled)
+    capacity: Option<usize>,
+    orders: HashMap<OrderId, ArchivedOrder>,
+    /// Archived ids, oldest first
+    ids: VecDeque<OrderId>,
+}
+
+impl OrderArchive {
+    pub(crate) fn new(capacity: Option<usize>) -> Self {
+        Self { capacity, ..Default::default() }
+    }
+
+    /// Whether finished orders leave the index
+    pub(crate) fn enabled(&self) -> bool {
+        self.capacity.is_some()
+    }
+
+    pub(crate) fn get(&self, order_id: OrderId) -> Option<&ArchivedOrder> {
+        self.orders.get(&order_id)
+    }
+
+    /// Retain a finished order, evicting the oldest once full
+    fn insert(&mut self, order: ArchivedOrder) {
+        let capacity = self.capacity.unwrap_or(0);
+        if capacity == 0 {
+            return;
+        }
+        if self.ids.len() == capacity {
+            if let Some(evicted) = self.ids.pop_front() {
+                self.orders.remove(&evicted);
+            }
+        }
+        self.ids.push_back(order.order_id);
+        self.orders.insert(order.order_id, order);
+    }
+
+    /// The error for an order missing from the index: already finished if
+    /// it is archived, otherwise not found
+    pub(crate) fn missing(&self, order_id: OrderId) -> OrderBookError {
+        match self.get(order_id).map(|order| order.status) {
+            Some(OrderStatus::Filled) => OrderBookError::OrderAlreadyFilled(order_id),
+            Some(OrderStatus::Cancelled) => OrderBookError::OrderAlreadyCancelled(order_id),
+            _ => OrderBookError::OrderNotFound(order_id),
+        }
+    }
+
+    /// Forget every archived order
+    pub(crate) fn clear(&mut self) {
+        self.orders.clear();
+        self.ids.clear();
+    }
+}
+
+impl OrderBook {
+    /// The final state of a finished order that has left the index
+    ///
+    /// None unless `order_archive_capacity` is configured, and for orders
+    /// still indexed, evicted, or never seen.
+    pub fn archived_order(&self, order_id: OrderId) -> Option<ArchivedOrder> {
+        self.archive.get(order_id).cloned()
+    }
+
+    /// Move finished orders that are off the book from the index to the archive
+    ///
+    /// Does nothing unless archiving is enabled.
+    pub(crate) fn archive_orders(&mut self, order_ids: &[OrderId]) {
+        if !self.archive.enabled() {
+            return;
+        }
+        let now = self.clock.now();
+        for order_id in order_ids {
+            let Some(metadata) = self.order_index.remove(order_id) else {
+                continue;
+            };
+            debug_assert!(
+                matches!(metadata.status, OrderStatus::Filled | OrderStatus::Cancelled),
+                "archiving live order {}",
+                order_id
+            );
+            self.archive.insert(ArchivedOrder {
+                order_id: *order_id,
+                user_id: metadata.user_id,
+                side: metadata.side,
+                price: metadata.price,
+                status: metadata.status,
+                client_order_id: metadata.client_order_id,
+                archived_at: now,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::OrderBookConfig;
+
+    fn archiving_book(capacity: usize) -> OrderBook {
+        let config =
+            OrderBookConfig { order_archive_capacity: Some(capacity), ..Default::default() };
+        OrderBook::with_config("market1".to_string(), "YES".to_string(), config)
+    }
+
+    #[test]
+    fn test_filled_order_moves_to_archive() {
+        let mut book = archiving_book(10);
+        book.process_limit_order(order(1, Side::Sell, 5000, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5000, 100)).unwrap();
+        book.process_limit_order(order(3, Side::Buy, 5000, 150)).unwrap();
+
+        let archived = book.archived_order(1).unwrap();
+        assert_eq!(archived.status, OrderStatus::Filled);
+        assert_eq!((archived.side, archived.price), (Side::Sell, 5000));
+        assert!(book.get_order(1).is_none(), "no longer indexed");
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Filled));
+        assert_eq!(book.active_orders(), 1);
+        assert!(book.archived_order(2).is_none(), "still live");
+
+        assert_eq!(book.cancel_order(1), Err(OrderBookError::OrderAlreadyFilled(1)));
+        let duplicate = book.process_limit_order(order(1, Side::Sell, 5100, 10));
+        assert!(matches!(duplicate, Err(OrderBookError::DuplicateOrderId(1))));
+        let duplicate = book.match_against(order(1, Side::Buy, 5000, 10), 2);
+        assert!(matches!(duplicate, Err(OrderBookError::DuplicateOrderId(1))));
+        assert_eq!(book.get_order_remaining(2), Some(50));
+    }
+
+    #[test]
+    fn test_cancelled_order_archived_once_dropped() {
+        let mut book = archiving_book(1);
+        book.process_limit_order(order(1, Side::Sell, 5000, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5000, 100)).unwrap();
+        book.cancel_order(1).unwrap();
+
+        // Still queued under lazy deletion, so still indexed
+        assert!(book.archived_order(1).is_none());
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+
+        // Matching drops it from the level
+        book.process_limit_order(order(3, Side::Buy, 5000, 10)).unwrap();
+        assert_eq!(book.archived_order(1).unwrap().status, OrderStatus::Cancelled);
+        assert!(book.get_order(1).is_none());
+        assert_eq!(book.cancel_order(1), Err(OrderBookError::OrderAlreadyCancelled(1)));
+
+        // Cancelling the level's last live order drops the level with it. A
+        // full archive evicts the oldest, and capacity 0 keeps nothing
+        book.cancel_order(2).unwrap();
+        assert_eq!(book.best_ask(), None);
+        assert!(book.archived_order(1).is_none());
+        assert_eq!(book.get_order_status(1), None);
+        assert_eq!(book.archived_order(2).unwrap().status, OrderStatus::Cancelled);
+
+        let mut book = archiving_book(0);
+        book.process_limit_order(order(1, Side::Sell, 5000, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 5000, 100)).unwrap();
+        assert!(book.archived_order(1).is_none());
+        assert_eq!(book.get_order_status(1), None);
+    }
+}
@@ -0,0 +1,298 @@
+//! Call auction (uncrossing) for market opens
+//!
+//! In a call auction orders accumulate without matching. When the auction
+//! ends, [`AuctionBook::uncross`] picks a single clearing price and every
+//! eligible order trades at it. This is a different regime from the
+//! continuous matching in [`OrderBook`](crate::OrderBook), but it queues
+//! orders in the same price-level structures.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::{
+    current_timestamp, same_book, BookOrder, MarketId, Order, OrderBookError, OrderId,
+    OrderStatus, OutcomeId, Price, PriceLevelQueue, Quantity, Side, Trade, TradeId,
+};
+
+/// An order book collecting orders for a call auction
+#[derive(Debug)]
+pub struct AuctionBook {
+    /// Market this auction is for
+    pub market_id: MarketId,
+    /// Outcome this auction is for
+    pub outcome_id: OutcomeId,
+    /// Buy orders: price -> queue of orders
+    bids: BTreeMap<Price, PriceLevelQueue>,
+    /// Sell orders: price -> queue of orders
+    asks: BTreeMap<Price, PriceLevelQueue>,
+    /// Ids of every order accepted into the auction
+    order_ids: HashSet<OrderId>,
+    /// Next trade ID
+    next_trade_id: TradeId,
+}
+
+impl AuctionBook {
+    /// Create an empty auction for a specific market and outcome
+    pub fn new(market_id: MarketId, outcome_id: OutcomeId) -> Self {
+        Self {
+            market_id,
+            outcome_id: crate::canonical_outcome_id(&outcome_id),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            order_ids: HashSet::new(),
+            next_trade_id: 1,
+        }
+    }
+
+    /// Queue an order for the auction without matching it
+    pub fn submit(&mut self, order: Order) -> Result<(), OrderBookError> {
+        if order.price == 0 {
+            return Err(OrderBookError::InvalidPrice);
+        }
+        if order.remaining_quantity == 0 {
+            return Err(OrderBookError::InvalidQuantity);
+        }
+        if !same_book(&order.market_id, &order.outcome_id, &self.market_id, &self.outcome_id) {
+            return Err(OrderBookError::MarketMismatch);
+        }
+        if !self.order_ids.insert(order.id) {
+            return Err(OrderBookError::DuplicateOrderId(order.id));
+        }
+
+        let book = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        book.entry(order.price).or_default().push_back(BookOrder::from(&order));
+        Ok(())
+    }
+
+    /// Get the best bid price (highest buy price)
+    pub fn best_bid(&self) -> Option<Price> {
+        self.bids.keys().next_back().copied()
+    }
+
+    /// Get the best ask price (lowest sell price)
+    pub fn best_ask(&self) -> Option<Price> {
+        self.asks.keys().next().copied()
+    }
+
+    /// The price and volume the auction would clear at if it uncrossed now
+    ///
+    /// This is the indicative open published while the auction runs; it does
+    /// not modify the book.
+    ///
+    /// Every limit price in the book is a candidate. At each candidate, demand
+    /// is the quantity bid at or above it and supply the quantity offered at or
+    /// below it; the executable volume is the smaller of the two. The clearing
+    /// price is the candidate that:
+    ///
+    /// 1. maximizes executable volume, then
+    /// 2. minimizes the imbalance between demand and supply, then
+    /// 3. is the lowest such price.
+    ///
+    /// Returns None if bids and asks don't overlap.
+    ///
+    /// # Time Complexity
+    /// O(P) where P is the number of price levels
+    pub fn indicative_clearing(&self) -> Option<(Price, Quantity)> {
+        let prices: BTreeSet<Price> = self.bids.keys().chain(self.asks.keys()).copied().collect();
+
+        let mut demand: Quantity = self.bids.values().map(|l| l.total_quantity).sum();
+        let mut supply: Quantity = 0;
+        let mut bids = self.bids.iter().peekable();
+        let mut asks = self.asks.iter().peekable();
+
+        // (price, volume, imbalance) of the best candidate so far
+        let mut best: Option<(Price, Quantity, Quantity)> = None;
+        for price in prices {
+            while let Some((_, level)) = asks.next_if(|(&p, _)| p <= price) {
+                supply += level.total_quantity;
+            }
+            while let Some((_, level)) = bids.next_if(|(&p, _)| p < price) {
+                demand -= level.total_quantity;
+            }
+
+            let volume = demand.min(supply);
+            let imbalance = demand.abs_diff(supply);
+            let better = match best {
+                None => volume > 0,
+                Some((_, best_volume, best_imbalance)) => {
+                    volume > best_volume || (volume == best_volume && imbalance < best_imbalance)
+                }
+            };
+            if better {
+                best = Some((price, volume, imbalance));
+            }
+        }
+
+        best.map(|(price, volume, _)| (price, volume))
+    }
+
+    /// End the auction: execute all eligible orders at the clearing price
+    ///
+    /// Orders on each side are filled in price-time priority until the
+    /// clearing volume is exhausted, then paired off in that order. Since
+    /// there is no aggressor in an auction, the buy order is recorded as the
+    /// taker of each trade. Unfilled quantity stays queued. Self-trade
+    /// prevention does not apply to auctions.
+    ///
+    /// Returns None, executing nothing, if bids and asks don't overlap.
+    pub fn uncross(&mut self) -> Option<(Price, Vec<Trade>)> {
+        let (price, volume) = self.indicative_clearing()?;
+
+        let buys = Self::take_fills(&mut self.bids, Side::Buy, volume);
+        let sells = Self::take_fills(&mut self.asks, Side::Sell, volume);
+
+        let mut trades = Vec::new();
+        let mut buys = buys.into_iter();
+        let mut sells = sells.into_iter();
+        let mut buy = buys.next();
+        let mut sell = sells.next();
+        while let (Some((buy_order, buy_qty)), Some((sell_order, sell_qty))) =
+            (&mut buy, &mut sell)
+        {
+            let quantity = (*buy_qty).min(*sell_qty);
+            trades.push(Trade {
+                id: self.next_trade_id,
+                taker_order_id: buy_order.id,
+                maker_order_id: sell_order.id,
+                taker_user_id: buy_order.user_id.clone(),
+                maker_user_id: sell_order.user_id.clone(),
+                market_id: self.market_id.clone(),
+                outcome_id: self.outcome_id.clone(),
+                price,
+                quantity,
+                timestamp: current_timestamp(),
+                taker_side: Side::Buy,
+                taker_client_order_id: buy_order.client_order_id.clone(),
+                maker_client_order_id: sell_order.client_order_id.clone(),
+            });
+            self.next_trade_id += 1;
+
+            *buy_qty -= quantity;
+            *sell_qty -= quantity;
+            if *buy_qty == 0 {
+                buy = buys.next();
+            }
+            if *sell_qty == 0 {
+                sell = sells.next();
+            }
+        }
+
+        Some((price, trades))
+    }
+
+    /// Take `volume` from one side in price-time priority
+    ///
+    /// Returns each order touched, as it was before the auction, with the
+    /// quantity it fills, in priority order. Filled orders and emptied levels
+    /// are removed.
+    fn take_fills(
+        book: &mut BTreeMap<Price, PriceLevelQueue>,
+        side: Side,
+        mut volume: Quantity,
+    ) -> Vec<(BookOrder, Quantity)> {
+        let mut fills = Vec::new();
+        while volume > 0 {
+            let mut entry = match side {
+                Side::Buy => book.last_entry(),
+                Side::Sell => book.first_entry(),
+            }
+            .expect("clearing volume never exceeds one side's quantity");
+            let level = entry.get_mut();
+
+            while volume > 0 {
+                let Some(order) = level.orders.front_mut() else {
+                    break;
+                };
+                let quantity = order.remaining_quantity.min(volume);
+                fills.push((order.clone(), quantity));
+                order.remaining_quantity -= quantity;
+                order.status = OrderStatus::PartiallyFilled;
+                let filled = order.remaining_quantity == 0;
+
+                level.update_quantity(quantity);
+                volume -= quantity;
+                if filled {
+                    level.orders.pop_front();
+                }
+            }
+
+            if level.is_empty() {
+                entry.remove();
+            }
+        }
+        fills
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+
+    #[test]
+    fn test_uncross_at_volume_maximizing_price() {
+        let mut auction = AuctionBook::new("market1".to_string(), "YES".to_string());
+        auction.submit(order(1, Side::Buy, 5200, 100)).unwrap();
+        auction.submit(order(2, Side::Buy, 5000, 100)).unwrap();
+        auction.submit(order(3, Side::Sell, 4900, 50)).unwrap();
+        auction.submit(order(4, Side::Sell, 5000, 100)).unwrap();
+        auction.submit(order(5, Side::Sell, 5100, 100)).unwrap();
+
+        // Orders queue without matching
+        assert_eq!(auction.best_bid(), Some(5200));
+        assert_eq!(auction.best_ask(), Some(4900));
+
+        // At 5000: demand 200, supply 150 -> 150 trades (5100 and 5200 give 100)
+        let (price, trades) = auction.uncross().unwrap();
+        assert_eq!(price, 5000);
+        assert!(trades.iter().all(|t| t.price == 5000));
+        let pairs: Vec<(OrderId, OrderId, Quantity)> = trades
+            .iter()
+            .map(|t| (t.taker_order_id, t.maker_order_id, t.quantity))
+            .collect();
+        assert_eq!(pairs, vec![(1, 3, 50), (1, 4, 50), (2, 4, 50)]);
+
+        // The unfilled bid and the out-of-range ask remain, no longer crossed
+        assert_eq!(auction.best_bid(), Some(5000));
+        assert_eq!(auction.best_ask(), Some(5100));
+        assert!(auction.uncross().is_none());
+    }
+
+    #[test]
+    fn test_clearing_tie_break_prefers_balance_then_lowest_price() {
+        let mut auction = AuctionBook::new("market1".to_string(), "YES".to_string());
+        auction.submit(order(1, Side::Buy, 5100, 100)).unwrap();
+        auction.submit(order(2, Side::Sell, 5000, 100)).unwrap();
+
+        // 5000 and 5100 both clear 100 with no imbalance: the lower wins
+        let (price, trades) = auction.uncross().unwrap();
+        assert_eq!(price, 5000);
+        assert_eq!(trades.len(), 1);
+    }
+
+    #[test]
+    fn test_indicative_clearing_tracks_new_orders() {
+        let mut auction = AuctionBook::new("market1".to_string(), "YES".to_string());
+        auction.submit(order(1, Side::Buy, 4900, 100)).unwrap();
+        auction.submit(order(2, Side::Sell, 5000, 100)).unwrap();
+        assert_eq!(auction.indicative_clearing(), None);
+
+        // A bid crossing the ask: 5000..5100 all clear 100, lowest price wins
+        auction.submit(order(3, Side::Buy, 5100, 100)).unwrap();
+        assert_eq!(auction.indicative_clearing(), Some((5000, 100)));
+
+        // A cheaper offer ties 4900 with 5000 on volume and imbalance
+        auction.submit(order(4, Side::Sell, 4900, 100)).unwrap();
+        assert_eq!(auction.indicative_clearing(), Some((4900, 100)));
+
+        // Another aggressive bid lets both asks trade, which needs 5000
+        auction.submit(order(5, Side::Buy, 5100, 100)).unwrap();
+        assert_eq!(auction.indicative_clearing(), Some((5000, 200)));
+
+        // Querying doesn't change the book
+        assert_eq!(auction.indicative_clearing(), Some((5000, 200)));
+        assert_eq!(auction.best_ask(), Some(4900));
+    }
+}
@@ -0,0 +1,104 @@
+//! Price band around the last trade
+//!
+//! With `OrderBookConfig::price_band_bps` set, every order that trades pulls
+//! the resting orders left more than that many basis points from the new
+//! last trade price, on either side, so a sharp move doesn't leave stale
+//! quotes to be picked off. The band is applied once the order has finished
+//! matching, before its own remainder rests, and the pulled orders are
+//! cancelled as the book's own cancels are: listeners see `Cancelled` events
+//! and `min_resting_micros` doesn't hold them back. Their ids are returned
+//! in the trading order's ack.
+//!
+//! Midpoint orders follow the market already and are left alone. Trades
+//! from `match_against` and `cross_orders` don't apply the band.
+
+use crate::{OrderBook, OrderId, OrderStatus, Side};
+
+impl OrderBook {
+    /// Cancel the resting orders outside the band around the last trade price
+    ///
+    /// Returns their ids, bids best first then asks best first. Does nothing
+    /// unless `price_band_bps` is configured and a trade has happened.
+    pub(crate) fn enforce_price_band(&mut self) -> Vec<OrderId> {
+        let (Some(width), Some(last)) = (self.config.price_band_bps, self.last_trade_price) else {
+            return Vec::new();
+        };
+        let (low, high) = (last.saturating_sub(width), last.saturating_add(width));
+
+        let mut stale = Vec::new();
+        for side in [Side::Buy, Side::Sell] {
+            let levels: Box<dyn Iterator<Item = _>> = match side {
+                Side::Buy => Box::new(self.bids.iter().rev()),
+                Side::Sell => Box::new(self.asks.iter()),
+            };
+            for (&price, level) in levels {
+                if (low..=high).contains(&price) {
+                    continue;
+                }
+                stale.extend(level.orders.iter().map(|order| order.id).filter(|id| {
+                    self.order_index
+                        .get(id)
+                        .is_some_and(|m| m.status != OrderStatus::Cancelled)
+                }));
+            }
+        }
+
+        for &order_id in &stale {
+            // Only live orders were collected, so this cannot fail
+            let _ = self.cancel_resting(order_id);
+        }
+        stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::order;
+    use crate::{OrderBook, OrderBookConfig, OrderEvent, Price, Side};
+    use std::sync::{Arc, Mutex};
+
+    fn banded_book(width: Price) -> OrderBook {
+        let config = OrderBookConfig { price_band_bps: Some(width), ..Default::default() };
+        OrderBook::with_config("market1".to_string(), "YES".to_string(), config)
+    }
+
+    #[test]
+    fn test_big_trade_pulls_orders_outside_band() {
+        let mut book = banded_book(500);
+        book.process_limit_order(order(1, Side::Buy, 4900, 10)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 4400, 10)).unwrap();
+        book.process_limit_order(order(3, Side::Sell, 5100, 10)).unwrap();
+        book.process_limit_order(order(4, Side::Sell, 6000, 10)).unwrap();
+        book.process_limit_order(order(5, Side::Sell, 6400, 10)).unwrap();
+        let cancels = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&cancels);
+        book.add_listener(Box::new(move |event| {
+            if let OrderEvent::Cancelled { order_id, .. } = event {
+                sink.lock().unwrap().push(*order_id);
+            }
+        }));
+
+        // Resting orders alone trigger nothing
+        book.process_limit_order(order(6, Side::Buy, 4300, 10)).unwrap();
+        assert!(cancels.lock().unwrap().is_empty());
+
+        // Sweeping to 6000 leaves the band at 5500..=6500
+        let result = book.process_limit_order(order(7, Side::Buy, 6000, 30)).unwrap();
+        assert_eq!(result.ack.band_cancelled, vec![1, 2, 6]);
+        assert_eq!(*cancels.lock().unwrap(), vec![1, 2, 6]);
+        // The taker's own remainder rests, and the ask inside the band stays
+        assert!(result.ack.resting);
+        assert_eq!(book.live_levels(Side::Buy).collect::<Vec<_>>(), vec![(6000, 10)]);
+        assert_eq!(book.live_levels(Side::Sell).collect::<Vec<_>>(), vec![(6400, 10)]);
+    }
+
+    #[test]
+    fn test_no_band_by_default() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Buy, 1000, 10)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 9000, 10)).unwrap();
+        let result = book.process_limit_order(order(3, Side::Buy, 9000, 10)).unwrap();
+        assert!(result.ack.band_cancelled.is_empty());
+        assert_eq!(book.best_bid(), Some(1000));
+    }
+}
@@ -0,0 +1,119 @@
+//! All-or-none baskets of orders across books
+//!
+//! A trader may want several books at once, such as buying YES and NO of the
+//! same market together, and none of them if any is refused.
+//! [`submit_basket`] routes each leg to its book and submits every leg or
+//! none: fills reach listeners as they execute and can't be taken back, so
+//! every leg is validated against its book first, and only then are they
+//! submitted, in order.
+//!
+//! Each leg must go to a different book. A second leg for the same book
+//! would meet the book as the first leg left it, which can't be checked
+//! before the first is submitted.
+
+use crate::{same_book, Order, OrderBook, OrderBookError, ProcessOrderResult};
+
+/// Submit every leg to its book, or none of them
+///
+/// Each leg goes to the book in `books` with its market and outcome, and is
+/// processed as `process_limit_order` (or `process_market_order`, per its
+/// type) would. Returns the legs' results in order. If any leg would be
+/// rejected, returns its index and error with no book changed: a leg with no
+/// book fails with `MarketMismatch`, and one for a book an earlier leg uses
+/// with `DuplicateBasketBook`.
+pub fn submit_basket(
+    books: &mut [OrderBook],
+    legs: Vec<Order>,
+) -> Result<Vec<ProcessOrderResult>, (usize, OrderBookError)> {
+    let mut routes = Vec::with_capacity(legs.len());
+    for (leg, order) in legs.iter().enumerate() {
+        let book = books
+            .iter()
+            .position(|book| {
+                same_book(&book.market_id, &book.outcome_id, &order.market_id, &order.outcome_id)
+            })
+            .ok_or((leg, OrderBookError::MarketMismatch))?;
+        if routes.contains(&book) {
+            return Err((leg, OrderBookError::DuplicateBasketBook));
+        }
+        books[book].validate_order(order).map_err(|error| (leg, error))?;
+        routes.push(book);
+    }
+
+    legs.into_iter()
+        .zip(routes)
+        .enumerate()
+        .map(|(leg, (order, book))| {
+            books[book].process_limit_order(order).map_err(|error| (leg, error))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OrderId, OrderStatus, Price, Quantity, Side};
+
+    fn order(id: OrderId, outcome: &str, side: Side, price: Price, quantity: Quantity) -> Order {
+        Order::with_timestamp(
+            id,
+            format!("user{}", id),
+            "market1".to_string(),
+            outcome.to_string(),
+            side,
+            price,
+            quantity,
+            id,
+        )
+    }
+
+    /// YES and NO books, each with an ask of 100
+    fn paired_books() -> Vec<OrderBook> {
+        let mut yes = OrderBook::new("market1".to_string(), "YES".to_string());
+        yes.process_limit_order(order(1, "YES", Side::Sell, 6000, 100)).unwrap();
+        let mut no = OrderBook::new("market1".to_string(), "NO".to_string());
+        no.process_limit_order(order(2, "NO", Side::Sell, 4000, 100)).unwrap();
+        vec![yes, no]
+    }
+
+    #[test]
+    fn test_basket_submits_every_leg() {
+        let mut books = paired_books();
+        let legs = vec![
+            order(3, "NO", Side::Buy, 4000, 50),
+            order(4, "yes", Side::Buy, 6000, 150),
+        ];
+        let results = submit_basket(&mut books, legs).unwrap();
+
+        assert_eq!(results[0].trades[0].maker_order_id, 2);
+        assert_eq!(results[0].order.status, OrderStatus::Filled);
+        assert_eq!(results[1].trades[0].maker_order_id, 1);
+        assert!(results[1].ack.resting);
+        assert_eq!(books[0].best_bid(), Some(6000));
+        assert_eq!(books[1].get_order_remaining(2), Some(50));
+    }
+
+    #[test]
+    fn test_basket_with_invalid_leg_changes_nothing() {
+        let mut books = paired_books();
+        let before: Vec<_> = books.iter().map(|book| book.get_depth(10)).collect();
+
+        // The YES leg would trade, but the NO leg has no quantity
+        let legs = vec![order(3, "YES", Side::Buy, 6000, 50), order(4, "NO", Side::Buy, 4000, 0)];
+        let result = submit_basket(&mut books, legs);
+        assert!(matches!(result, Err((1, OrderBookError::InvalidQuantity))));
+
+        let legs = vec![order(3, "YES", Side::Buy, 6000, 50), order(4, "MAYBE", Side::Buy, 10, 1)];
+        let result = submit_basket(&mut books, legs);
+        assert!(matches!(result, Err((1, OrderBookError::MarketMismatch))));
+
+        let legs = vec![order(3, "YES", Side::Buy, 6000, 50), order(4, "YES", Side::Buy, 5000, 1)];
+        let result = submit_basket(&mut books, legs);
+        assert!(matches!(result, Err((1, OrderBookError::DuplicateBasketBook))));
+
+        let after: Vec<_> = books.iter().map(|book| book.get_depth(10)).collect();
+        assert_eq!(after, before);
+        assert!(books.iter().all(|book| book.total_trades == 0));
+        assert_eq!(books[0].get_order_status(3), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! Validated order construction
+//!
+//! `Order::new` and `Order::with_timestamp` accept any values and leave the
+//! book to reject bad ones on submission. [`OrderBuilder`] checks an order as
+//! it is built, so an invalid one never exists:
+//!
+//! ```
+//! use matching_engine::{OrderBuilder, Side};
+//!
+//! let order = OrderBuilder::new(1, "alice", "market1", "YES", Side::Buy)
+//!     .price(6500)
+//!     .quantity(100)
+//!     .client_order_id("a-1")
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(order.remaining_quantity, 100);
+//! ```
+
+use std::fmt;
+
+use crate::units::BASIS_POINTS_PER_DOLLAR;
+use crate::{
+    current_timestamp, IcebergSlicing, MarketId, Order, OrderId, OrderStatus, OrderType,
+    OutcomeId, Price, Quantity, Side, TimeInForce, Timestamp, UserId,
+};
+
+/// Why an `OrderBuilder` refused to build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderBuildError {
+    /// A limit or midpoint order's price is outside 1..=10_000 basis points
+    /// (a share pays at most $1)
+    PriceOutOfRange(Price),
+    /// The order has no quantity left to trade, or an iceberg shows none
+    InvalidQuantity,
+    /// More quantity remains than was ordered
+    RemainingExceedsOriginal {
+        original: Quantity,
+        remaining: Quantity,
+    },
+}
+
+impl fmt::Display for OrderBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PriceOutOfRange(price) => write!(f, "Price out of range: {}", price),
+            Self::InvalidQuantity => write!(f, "Invalid quantity (must be > 0)"),
+            Self::RemainingExceedsOriginal { original, remaining } => write!(
+                f,
+                "Remaining quantity {} exceeds original quantity {}",
+                remaining, original
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OrderBuildError {}
+
+/// Builder for an `Order` that is validated on `build`
+#[derive(Debug, Clone)]
+pub struct OrderBuilder {
+    order: Order,
+    /// None = stamp with the current time on `build`
+    timestamp: Option<Timestamp>,
+}
+
+impl OrderBuilder {
+    /// Start an order; price and quantity must be set before building
+    pub fn new(
+        id: OrderId,
+        user_id: impl Into<UserId>,
+        market_id: impl Into<MarketId>,
+        outcome_id: impl Into<OutcomeId>,
+        side: Side,
+    ) -> Self {
+        let order = Order::with_timestamp(
+            id,
+            user_id.into(),
+            market_id.into(),
+            outcome_id.into(),
+            side,
+            0,
+            0,
+            0,
+        );
+        Self { order, timestamp: None }
+    }
+
+    /// Limit price in basis points (ignored for market orders)
+    pub fn price(mut self, price: Price) -> Self {
+        self.order.price = price;
+        self
+    }
+
+    /// Ordered quantity; the remaining quantity is set to match
+    pub fn quantity(mut self, quantity: Quantity) -> Self {
+        self.order.original_quantity = quantity;
+        self.order.remaining_quantity = quantity;
+        self
+    }
+
+    /// Remaining quantity, for an order that has already partially filled
+    ///
+    /// Set after `quantity`. A remainder below the original marks the order
+    /// partially filled.
+    pub fn remaining_quantity(mut self, remaining: Quantity) -> Self {
+        self.order.remaining_quantity = remaining;
+        self
+    }
+
+    /// Placement time (default: the current time when built)
+    pub fn timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Make this a market order
+    pub fn market(mut self) -> Self {
+        self.order.order_type = OrderType::Market;
+        self
+    }
+
+    /// Make this a midpoint-pegged order, with `price` as its limit
+    pub fn midpoint_peg(mut self) -> Self {
+        self.order.order_type = OrderType::MidpointPeg;
+        self
+    }
+
+    /// See `Order::last_look_bps`
+    pub fn last_look_bps(mut self, bps: Price) -> Self {
+        self.order.last_look_bps = Some(bps);
+        self
+    }
+
+    /// See `Order::max_levels`
+    pub fn max_levels(mut self, levels: usize) -> Self {
+        self.order.max_levels = Some(levels);
+        self
+    }
+
+    /// See `Order::max_slippage_bps`
+    pub fn max_slippage_bps(mut self, bps: Price) -> Self {
+        self.order.max_slippage_bps = Some(bps);
+        self
+    }
+
+    /// See `Order::max_rest_quantity`
+    pub fn max_rest_quantity(mut self, quantity: Quantity) -> Self {
+        self.order.max_rest_quantity = Some(quantity);
+        self
+    }
+
+    /// See `Order::time_in_force`
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.order.time_in_force = time_in_force;
+        self
+    }
+
+    /// Make this an iceberg showing at most `display` at a time; see
+    /// `Order::display_quantity` and `Order::iceberg_slicing`
+    pub fn iceberg(mut self, display: Quantity, slicing: IcebergSlicing) -> Self {
+        self.order.display_quantity = Some(display);
+        self.order.iceberg_slicing = slicing;
+        self
+    }
+
+    /// See `Order::client_order_id`
+    pub fn client_order_id(mut self, client_order_id: impl Into<String>) -> Self {
+        self.order.client_order_id = Some(client_order_id.into());
+        self
+    }
+
+    /// Validate and return the order
+    pub fn build(self) -> Result<Order, OrderBuildError> {
+        let mut order = self.order;
+        if order.order_type != OrderType::Market
+            && !(1..=BASIS_POINTS_PER_DOLLAR).contains(&order.price)
+        {
+            return Err(OrderBuildError::PriceOutOfRange(order.price));
+        }
+        if order.remaining_quantity > order.original_quantity {
+            return Err(OrderBuildError::RemainingExceedsOriginal {
+                original: order.original_quantity,
+                remaining: order.remaining_quantity,
+            });
+        }
+        if order.remaining_quantity == 0 || order.display_quantity == Some(0) {
+            return Err(OrderBuildError::InvalidQuantity);
+        }
+
+        if order.remaining_quantity < order.original_quantity {
+            order.status = OrderStatus::PartiallyFilled;
+        }
+        order.timestamp = self.timestamp.unwrap_or_else(current_timestamp);
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> OrderBuilder {
+        OrderBuilder::new(1, "user1", "market1", "yes", Side::Sell).timestamp(7)
+    }
+
+    #[test]
+    fn test_build_validates_order() {
+        let order = builder().price(5000).quantity(100).remaining_quantity(60).build().unwrap();
+        assert_eq!(order.outcome_id, "YES");
+        assert_eq!((order.original_quantity, order.remaining_quantity), (100, 60));
+        assert_eq!(order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.timestamp, 7);
+
+        assert_eq!(
+            builder().price(5000).quantity(100).remaining_quantity(150).build(),
+            Err(OrderBuildError::RemainingExceedsOriginal { original: 100, remaining: 150 })
+        );
+        assert_eq!(
+            builder().quantity(100).build(),
+            Err(OrderBuildError::PriceOutOfRange(0))
+        );
+        assert_eq!(
+            builder().price(10_001).quantity(100).build(),
+            Err(OrderBuildError::PriceOutOfRange(10_001))
+        );
+        assert_eq!(builder().price(5000).build(), Err(OrderBuildError::InvalidQuantity));
+
+        // Market orders need no price
+        let order = builder().market().max_slippage_bps(50).quantity(10).build().unwrap();
+        assert_eq!(order.order_type, OrderType::Market);
+    }
+}
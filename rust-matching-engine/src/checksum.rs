@@ -0,0 +1,110 @@
+//! Depth checksums for mirror validation
+//!
+//! A client maintaining its own copy of the book from the event feed can
+//! compare [`OrderBook::depth_checksum`] against the same checksum computed
+//! over its mirror. The formula is:
+//!
+//! 1. Take the top `levels` live bid levels, best (highest) price first, then
+//!    the top `levels` live ask levels, best (lowest) price first. The
+//!    quantity is the live quantity. Midpoint orders are hidden and not
+//!    included.
+//! 2. Write each level as `price:quantity` in decimal basis points and shares,
+//!    join each side's levels with `:`, and join the bids to the asks with
+//!    `|`, e.g. `5000:100:4900:50|5100:30` for two bids and one ask. The `|`
+//!    is always there, so an empty book gives `|` and levels can't move from
+//!    one side to the other without changing the string.
+//! 3. The checksum is the CRC-32 (IEEE 802.3, as used by zlib and gzip) of
+//!    that string's ASCII bytes.
+
+use crate::{OrderBook, Side};
+
+/// CRC-32 (IEEE) of `bytes`, bit by bit
+///
+/// Checksums cover a few dozen levels, so a lookup table isn't worth it.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+impl OrderBook {
+    /// The canonical string the depth checksum is computed over
+    ///
+    /// See the module docs for the format. `levels` is clamped to
+    /// `max_query_levels`, if configured.
+    pub fn depth_checksum_input(&self, levels: usize) -> String {
+        let levels = self.query_levels(levels);
+        let side = |side: Side| {
+            self.live_levels(side)
+                .take(levels)
+                .map(|(price, quantity)| format!("{}:{}", price, quantity))
+                .collect::<Vec<_>>()
+                .join(":")
+        };
+        format!("{}|{}", side(Side::Buy), side(Side::Sell))
+    }
+
+    /// CRC-32 of the top `levels` live levels per side, for clients to check
+    /// their mirror of the book against
+    pub fn depth_checksum(&self, levels: usize) -> u32 {
+        crc32(self.depth_checksum_input(levels).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_identical_depth_gives_identical_checksum() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Buy, 5000, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 4900, 50)).unwrap();
+        book.process_limit_order(order(3, Side::Sell, 5100, 30)).unwrap();
+        book.process_limit_order(order(4, Side::Buy, 4800, 70)).unwrap();
+        book.cancel_order(4).unwrap();
+        assert_eq!(book.depth_checksum_input(5), "5000:100:4900:50|5100:30");
+
+        // Same live depth, reached through different orders
+        let mut mirror = OrderBook::new("market1".to_string(), "YES".to_string());
+        mirror.process_limit_order(order(1, Side::Buy, 5000, 60)).unwrap();
+        mirror.process_limit_order(order(2, Side::Buy, 5000, 40)).unwrap();
+        mirror.process_limit_order(order(3, Side::Buy, 4900, 50)).unwrap();
+        mirror.process_limit_order(order(4, Side::Sell, 5100, 30)).unwrap();
+        assert_eq!(mirror.depth_checksum(5), book.depth_checksum(5));
+
+        // One order's difference shows
+        mirror.process_limit_order(order(5, Side::Sell, 5200, 1)).unwrap();
+        assert_ne!(mirror.depth_checksum(5), book.depth_checksum(5));
+        // ...unless it is below the levels covered
+        assert_eq!(mirror.depth_checksum(1), book.depth_checksum(1));
+    }
+    #[test]
+    fn test_sides_are_told_apart() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        assert_eq!(book.depth_checksum_input(5), "|");
+        book.process_limit_order(order(1, Side::Buy, 5000, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5100, 50)).unwrap();
+
+        // The same levels, all on the ask side
+        let mut asks = OrderBook::new("market1".to_string(), "YES".to_string());
+        asks.process_limit_order(order(1, Side::Sell, 5000, 100)).unwrap();
+        asks.process_limit_order(order(2, Side::Sell, 5100, 50)).unwrap();
+        assert_eq!(book.depth_checksum_input(5), "5000:100|5100:50");
+        assert_eq!(asks.depth_checksum_input(5), "|5000:100:5100:50");
+        assert_ne!(asks.depth_checksum(5), book.depth_checksum(5));
+    }
+}
@@ -0,0 +1,124 @@
+//! Time sources for the order book
+//!
+//! The book stamps trades and amended orders through a [`Clock`] so tests can
+//! control time and production can guard against system clock regressions.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::{current_timestamp, Timestamp};
+
+/// A source of timestamps (microseconds since the Unix epoch)
+pub trait Clock: Debug + Send + Sync {
+    /// The current time
+    fn now(&self) -> Timestamp;
+}
+
+/// Shared clocks, e.g. a `MockClock` also held by a test
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+/// The system wall clock
+///
+/// Wall-clock time can jump backwards (e.g. NTP adjustments); wrap it in a
+/// [`MonotonicClock`] where ordering matters.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        current_timestamp()
+    }
+}
+
+/// A manually driven clock for tests and replays
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+impl MockClock {
+    /// Create a clock reading `now`
+    pub fn new(now: Timestamp) -> Self {
+        Self { now: AtomicU64::new(now) }
+    }
+
+    /// Set the current reading
+    pub fn set(&self, now: Timestamp) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    /// Move the current reading forward by `delta`
+    pub fn advance(&self, delta: Timestamp) {
+        self.now.fetch_add(delta, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Timestamp {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// A clock that never goes backwards
+///
+/// Readings are passed through from the inner clock unless they are earlier
+/// than the previous reading, in which case `previous + 1` is returned
+/// instead. Timestamps are therefore non-decreasing, and strictly increasing
+/// across a regression, until the inner clock catches up.
+#[derive(Debug, Default)]
+pub struct MonotonicClock<C> {
+    inner: C,
+    last: AtomicU64,
+}
+
+impl<C: Clock> MonotonicClock<C> {
+    /// Wrap `inner`
+    pub fn new(inner: C) -> Self {
+        Self { inner, last: AtomicU64::new(0) }
+    }
+}
+
+impl<C: Clock> Clock for MonotonicClock<C> {
+    fn now(&self) -> Timestamp {
+        let reading = self.inner.now();
+        let previous = self
+            .last
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+                Some(if reading < last { last + 1 } else { reading })
+            })
+            .unwrap_or_else(|last| last);
+        if reading < previous {
+            previous + 1
+        } else {
+            reading
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic_clock_clamps_backwards_reading() {
+        let inner = Arc::new(MockClock::new(1_000));
+        let clock = MonotonicClock::new(Arc::clone(&inner));
+
+        assert_eq!(clock.now(), 1_000);
+        assert_eq!(clock.now(), 1_000);
+
+        // The system clock steps back: readings continue from previous + 1
+        inner.set(400);
+        assert_eq!(clock.now(), 1_001);
+        assert_eq!(clock.now(), 1_002);
+
+        // Once the inner clock is ahead again it is passed through
+        inner.set(2_000);
+        assert_eq!(clock.now(), 2_000);
+    }
+}
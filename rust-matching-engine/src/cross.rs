@@ -0,0 +1,202 @@
+//! Broker crosses between two resting orders
+//!
+//! A broker holding two customer orders on opposite sides can print a trade
+//! between them at an agreed price with [`OrderBook::cross_orders`], without
+//! either order going through the match loop. Both orders must be resting
+//! and live, and the price must be within both limits; the cross then fills
+//! them exactly as matching would, keeping the level totals and the index in
+//! step, and is reported and counted like any other trade.
+//!
+//! The trade's taker is the order that came second in time priority, as if it
+//! had been submitted against the other. Both orders were resting, so each
+//! gets a `MakerFill` event if those are configured.
+
+use crate::{
+    OrderBook, OrderBookError, OrderId, OrderMetadata, OrderStatus, Price, Quantity, Side, Trade,
+};
+
+impl OrderBook {
+    /// Trade `quantity` between two resting orders at `price`, off the book
+    ///
+    /// `buy_id` must be a bid and `sell_id` an ask, with `price` no higher
+    /// than the bid's limit and no lower than the ask's, and `quantity` no more
+    /// than either has left (any iceberg reserve included). Resting limits
+    /// only overlap on a locked book, so that is the lock price. Pegged
+    /// orders, orders of the same user and halted markets are rejected as by
+    /// `match_against`. Nothing changes on rejection.
+    pub fn cross_orders(
+        &mut self,
+        buy_id: OrderId,
+        sell_id: OrderId,
+        price: Price,
+        quantity: Quantity,
+    ) -> Result<Trade, OrderBookError> {
+        self.check_open()?;
+        if price == 0 {
+            return Err(OrderBookError::InvalidPrice);
+        }
+        if quantity == 0 {
+            return Err(OrderBookError::InvalidQuantity);
+        }
+        let buy = self.crossable(buy_id)?;
+        let sell = self.crossable(sell_id)?;
+        if buy.side != Side::Buy {
+            return Err(OrderBookError::WrongSide(buy_id));
+        }
+        if sell.side != Side::Sell {
+            return Err(OrderBookError::WrongSide(sell_id));
+        }
+        if buy.user_id == sell.user_id {
+            return Err(OrderBookError::SelfTrade);
+        }
+        for (order_id, metadata) in [(buy_id, buy), (sell_id, sell)] {
+            if !metadata.side.reaches(metadata.price, price) {
+                return Err(OrderBookError::OutsideLimit(order_id));
+            }
+            if quantity > metadata.total_remaining() {
+                return Err(OrderBookError::ExceedsRemaining(order_id));
+            }
+        }
+
+        let ((taker_id, taker), (maker_id, maker)) =
+            if (buy.timestamp, buy_id) > (sell.timestamp, sell_id) {
+                ((buy_id, buy), (sell_id, sell))
+            } else {
+                ((sell_id, sell), (buy_id, buy))
+            };
+        let trade = Trade {
+            id: self.next_trade_id,
+            taker_order_id: taker_id,
+            maker_order_id: maker_id,
+            taker_user_id: taker.user_id.clone(),
+            maker_user_id: maker.user_id.clone(),
+            market_id: self.market_id.clone(),
+            outcome_id: self.outcome_id.clone(),
+            price,
+            quantity,
+            timestamp: self.clock.now(),
+            taker_side: taker.side,
+            taker_client_order_id: taker.client_order_id.clone(),
+            maker_client_order_id: maker.client_order_id.clone(),
+        };
+        self.next_trade_id += 1;
+
+        for order_id in [maker_id, taker_id] {
+            self.fill_crossed_order(order_id, quantity);
+        }
+        self.record_trades(std::slice::from_ref(&trade));
+        for order_id in [maker_id, taker_id] {
+            let left = self.order_index.get(&order_id).map_or(0, |m| m.total_remaining());
+            self.record_maker_fill(order_id, quantity, left);
+        }
+        for order_id in [maker_id, taker_id] {
+            if self.get_order_status(order_id) == Some(OrderStatus::Filled) {
+                self.archive_orders(&[order_id]);
+            }
+        }
+        self.poll_depth_updates();
+
+        Ok(trade)
+    }
+
+    /// A live order resting at its price, which a cross may fill
+    fn crossable(&self, order_id: OrderId) -> Result<&OrderMetadata, OrderBookError> {
+        let metadata =
+            self.order_index.get(&order_id).ok_or_else(|| self.archive.missing(order_id))?;
+        match metadata.status {
+            OrderStatus::Cancelled => Err(OrderBookError::OrderAlreadyCancelled(order_id)),
+            OrderStatus::Filled => Err(OrderBookError::OrderAlreadyFilled(order_id)),
+            _ if metadata.pegged => Err(OrderBookError::PeggedOrder(order_id)),
+            _ => Ok(metadata),
+        }
+    }
+
+    /// Fill `quantity` of a resting order, slice by slice for an iceberg
+    fn fill_crossed_order(&mut self, order_id: OrderId, mut quantity: Quantity) {
+        while quantity > 0 {
+            let Some(metadata) = self.order_index.get(&order_id) else {
+                return;
+            };
+            let (side, price) = (metadata.side, metadata.price);
+            let fill = quantity.min(metadata.remaining_quantity);
+            if fill == 0 {
+                return;
+            }
+            self.fill_resting_order(side, price, order_id, fill);
+            quantity -= fill;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+
+    /// A bid at 5000 (order 1) and an iceberg ask of 250 showing 100 at 5200
+    /// (order 2), which can only meet by a cross
+    fn crossable_book() -> OrderBook {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Buy, 5000, 100)).unwrap();
+        let mut iceberg = order(2, Side::Sell, 5200, 250);
+        iceberg.display_quantity = Some(100);
+        book.process_limit_order(iceberg).unwrap();
+        book
+    }
+
+    #[test]
+    fn test_cross_fills_both_orders() {
+        let mut book = crossable_book();
+        book.process_limit_order(order(3, Side::Buy, 5000, 10)).unwrap();
+        // Amends never match, so this locks the book
+        book.amend_order(1, 5200, 100).unwrap();
+
+        let trade = book.cross_orders(1, 2, 5200, 100).unwrap();
+        assert_eq!((trade.price, trade.quantity), (5200, 100));
+        // The amend cost order 1 its priority, so it is the taker
+        assert_eq!((trade.taker_order_id, trade.maker_order_id), (1, 2));
+        assert_eq!(trade.taker_side, Side::Buy);
+        assert_eq!(book.total_trades, 1);
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Filled));
+        assert_eq!(book.get_order_remaining(2), Some(150));
+        assert_eq!(book.get_depth(5), (vec![(5000, 10)], vec![(5200, 100)]));
+        assert!(book.user_orders("user1").is_empty());
+
+        // An iceberg crosses past its shown slice
+        book.process_limit_order(order(4, Side::Buy, 5000, 150)).unwrap();
+        book.amend_order(4, 5200, 150).unwrap();
+        book.cross_orders(4, 2, 5200, 150).unwrap();
+        assert_eq!(book.get_order_status(2), Some(OrderStatus::Filled));
+        assert_eq!(book.get_depth(5), (vec![(5000, 10)], vec![]));
+        assert!(book.health().is_healthy());
+    }
+
+    #[test]
+    fn test_cross_rejects_limits_and_quantities() {
+        let mut book = crossable_book();
+        // The bid won't pay above 5000
+        assert_eq!(book.cross_orders(1, 2, 5100, 10).unwrap_err(), OrderBookError::OutsideLimit(1));
+        book.amend_order(1, 5200, 100).unwrap();
+        // The ask won't go below 5200
+        assert_eq!(book.cross_orders(1, 2, 5100, 10).unwrap_err(), OrderBookError::OutsideLimit(2));
+        assert_eq!(book.cross_orders(1, 2, 5300, 10).unwrap_err(), OrderBookError::OutsideLimit(1));
+
+        assert_eq!(
+            book.cross_orders(1, 2, 5200, 101).unwrap_err(),
+            OrderBookError::ExceedsRemaining(1)
+        );
+        assert_eq!(book.cross_orders(1, 2, 5200, 0).unwrap_err(), OrderBookError::InvalidQuantity);
+        assert_eq!(book.cross_orders(2, 1, 5200, 10).unwrap_err(), OrderBookError::WrongSide(2));
+        assert_eq!(
+            book.cross_orders(1, 9, 5200, 10).unwrap_err(),
+            OrderBookError::OrderNotFound(9)
+        );
+        book.process_limit_order(order(3, Side::Buy, 5000, 10)).unwrap();
+        assert_eq!(book.cross_orders(1, 3, 5200, 10).unwrap_err(), OrderBookError::WrongSide(3));
+
+        // Rejections change nothing
+        assert_eq!(book.get_order_remaining(1), Some(100));
+        assert_eq!(book.get_order_remaining(2), Some(250));
+        assert_eq!(book.total_trades, 0);
+    }
+}
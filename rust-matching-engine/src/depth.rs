@@ -0,0 +1,221 @@
+//! Throttled depth snapshots
+//!
+//! Publishing every change to the book overwhelms slow consumers of a busy
+//! market. With a [`DepthThrottle`] configured, the book instead coalesces
+//! changes and publishes one [`DepthSnapshot`] of its top levels per flush,
+//! as an [`OrderEvent::Depth`] to listeners. A flush publishes nothing if the
+//! top levels are unchanged since the previous one, so changes that cancel
+//! out between flushes are never seen.
+//!
+//! Flushes happen when [`OrderBook::flush_depth_updates`] is called, and, if
+//! the throttle has an `interval`, after any order entry, cancel, amend or
+//! direct match that comes at least `interval` after the previous flush.
+
+use crate::{OrderBook, OrderBookError, OrderEvent, OrderId, Price, Quantity, Side, Timestamp};
+
+/// Throttled depth publishing settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthThrottle {
+    /// Number of levels per side in each snapshot
+    pub levels: usize,
+    /// Minimum time between automatic flushes (None = only explicit flushes)
+    pub interval: Option<Timestamp>,
+}
+
+/// The top live levels of the book at a flush
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthSnapshot {
+    /// (price, live quantity), best first
+    pub bids: Vec<(Price, Quantity)>,
+    /// (price, live quantity), best first
+    pub asks: Vec<(Price, Quantity)>,
+    /// Time of the flush
+    pub timestamp: Timestamp,
+}
+
+/// The change to one price level's live total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthDelta {
+    /// Side of the level
+    pub side: Side,
+    /// Price of the level
+    pub price: Price,
+    /// Live quantity at the level before the change
+    pub before: Quantity,
+    /// Live quantity at the level after it
+    pub after: Quantity,
+}
+
+/// What the book last published
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DepthFeed {
+    bids: Vec<(Price, Quantity)>,
+    asks: Vec<(Price, Quantity)>,
+    last_flush: Timestamp,
+}
+
+impl OrderBook {
+    /// Publish a depth snapshot if the top levels changed since the last flush
+    ///
+    /// Returns the snapshot sent to listeners, or None if nothing changed or
+    /// no `DepthThrottle` is configured.
+    pub fn flush_depth_updates(&mut self, now: Timestamp) -> Option<DepthSnapshot> {
+        let levels = self.config.depth_throttle?.levels;
+        let bids: Vec<_> = self.live_levels(Side::Buy).take(levels).collect();
+        let asks: Vec<_> = self.live_levels(Side::Sell).take(levels).collect();
+
+        self.depth_feed.last_flush = now;
+        if bids == self.depth_feed.bids && asks == self.depth_feed.asks {
+            return None;
+        }
+        self.depth_feed.bids.clone_from(&bids);
+        self.depth_feed.asks.clone_from(&asks);
+
+        let snapshot = DepthSnapshot { bids, asks, timestamp: now };
+        self.listeners.emit(OrderEvent::Depth(snapshot.clone()));
+        Some(snapshot)
+    }
+
+    /// The change to its level that cancelling `order_id` would make, without cancelling it
+    ///
+    /// Returns the error `cancel_order` would return if the order can't be
+    /// cancelled now. Only an iceberg's shown slice counts, and a midpoint
+    /// order, being hidden, leaves its level (at its limit price) unchanged.
+    pub fn simulate_cancel(&self, order_id: OrderId) -> Result<DepthDelta, OrderBookError> {
+        let metadata = self.cancellable(order_id)?;
+        let before = self
+            .book(metadata.side)
+            .get(&metadata.price)
+            .map_or(0, |level| level.total_quantity);
+        let shown = if metadata.pegged { 0 } else { metadata.remaining_quantity };
+        Ok(DepthDelta {
+            side: metadata.side,
+            price: metadata.price,
+            before,
+            after: before - shown,
+        })
+    }
+
+    /// Flush if the throttle interval has elapsed since the last flush
+    pub(crate) fn poll_depth_updates(&mut self) {
+        let Some(interval) = self.config.depth_throttle.and_then(|t| t.interval) else {
+            return;
+        };
+        let now = self.clock.now();
+        if now.saturating_sub(self.depth_feed.last_flush) >= interval {
+            self.flush_depth_updates(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::{MockClock, OrderBookConfig};
+    use std::sync::{Arc, Mutex};
+
+    fn throttled(interval: Option<Timestamp>, clock: Arc<MockClock>) -> OrderBook {
+        let config = OrderBookConfig {
+            depth_throttle: Some(DepthThrottle { levels: 2, interval }),
+            clock: Some(clock),
+            ..Default::default()
+        };
+        OrderBook::with_config("market1".to_string(), "YES".to_string(), config)
+    }
+
+    fn depth_events(book: &mut OrderBook) -> Arc<Mutex<Vec<DepthSnapshot>>> {
+        let snapshots = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&snapshots);
+        book.add_listener(Box::new(move |event| {
+            if let OrderEvent::Depth(snapshot) = event {
+                sink.lock().unwrap().push(snapshot.clone());
+            }
+        }));
+        snapshots
+    }
+
+    #[test]
+    fn test_rapid_changes_coalesce_into_one_snapshot() {
+        let mut book = throttled(None, Arc::new(MockClock::new(1)));
+        let snapshots = depth_events(&mut book);
+
+        // Ten changes: rests, a trade, a cancel and an amend
+        book.process_limit_order(order(1, Side::Buy, 4900, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 4800, 100)).unwrap();
+        book.process_limit_order(order(3, Side::Buy, 4700, 100)).unwrap();
+        book.process_limit_order(order(4, Side::Sell, 5100, 100)).unwrap();
+        book.process_limit_order(order(5, Side::Sell, 5200, 100)).unwrap();
+        book.process_limit_order(order(6, Side::Sell, 4900, 30)).unwrap();
+        book.cancel_order(2).unwrap();
+        book.amend_order(4, 5000, 40).unwrap();
+        book.process_limit_order(order(7, Side::Sell, 5300, 10)).unwrap();
+        book.process_limit_order(order(8, Side::Buy, 4600, 10)).unwrap();
+        assert!(snapshots.lock().unwrap().is_empty());
+
+        let snapshot = book.flush_depth_updates(1_000).unwrap();
+        assert_eq!(snapshot.bids, vec![(4900, 70), (4700, 100)]);
+        assert_eq!(snapshot.asks, vec![(5000, 40), (5200, 100)]);
+        assert_eq!(snapshot.timestamp, 1_000);
+        assert_eq!(*snapshots.lock().unwrap(), vec![snapshot]);
+
+        // A change that is undone before the next flush publishes nothing
+        book.process_limit_order(order(9, Side::Buy, 4950, 10)).unwrap();
+        book.cancel_order(9).unwrap();
+        assert_eq!(book.flush_depth_updates(2_000), None);
+        assert_eq!(snapshots.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_interval_flushes_automatically() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let mut book = throttled(Some(500), Arc::clone(&clock));
+        let snapshots = depth_events(&mut book);
+
+        // The first change comes a full interval after the (empty) start
+        book.process_limit_order(order(1, Side::Buy, 4900, 100)).unwrap();
+        assert_eq!(snapshots.lock().unwrap().len(), 1);
+
+        clock.advance(100);
+        book.process_limit_order(order(2, Side::Sell, 5100, 100)).unwrap();
+        book.modify_quantity(1, 50).unwrap();
+        assert_eq!(snapshots.lock().unwrap().len(), 1);
+
+        clock.advance(400);
+        book.cancel_order(2).unwrap();
+        let snapshots = snapshots.lock().unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].bids, vec![(4900, 50)]);
+        assert!(snapshots[1].asks.is_empty());
+        assert_eq!(snapshots[1].timestamp, 1_500);
+    }
+
+    #[test]
+    fn test_simulate_cancel_previews_depth_change() {
+        // Orders are stamped with their id, so order 2 arrives at the clock's start
+        let clock = Arc::new(MockClock::new(2));
+        let config = OrderBookConfig {
+            min_resting_micros: 50,
+            clock: Some(clock.clone()),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(order(1, Side::Sell, 5100, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5100, 30)).unwrap();
+        assert_eq!(
+            book.simulate_cancel(2),
+            Err(OrderBookError::TooSoonToCancel { remaining_micros: 50 })
+        );
+
+        clock.advance(50);
+        let delta = book.simulate_cancel(2).unwrap();
+        let expected = DepthDelta { side: Side::Sell, price: 5100, before: 130, after: 100 };
+        assert_eq!(delta, expected);
+        assert_eq!(book.get_depth(1).1, vec![(5100, 130)], "nothing was cancelled");
+
+        book.cancel_order(2).unwrap();
+        assert_eq!(book.get_depth(1).1, vec![(delta.price, delta.after)]);
+        assert_eq!(book.simulate_cancel(2), Err(OrderBookError::OrderAlreadyCancelled(2)));
+        assert_eq!(book.simulate_cancel(9), Err(OrderBookError::OrderNotFound(9)));
+    }
+}
@@ -0,0 +1,152 @@
+//! Live-state comparison of two books
+//!
+//! Used to reconcile a book against a mirror or a replay: [`OrderBook::diff`]
+//! reports every price level and order on which the two disagree. Only live
+//! state is compared, so cancelled orders still queued under lazy deletion,
+//! filled orders and statistics never show up as differences.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{OrderBook, OrderId, OrderStatus, Price, Quantity, Side};
+
+/// A price level whose live quantity differs between two books
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelDiff {
+    /// Side of the level
+    pub side: Side,
+    /// Price of the level
+    pub price: Price,
+    /// Live quantity in the book `diff` was called on (0 if the level is absent)
+    pub ours: Quantity,
+    /// Live quantity in the other book
+    pub theirs: Quantity,
+}
+
+/// Every live difference between two books
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BookDiff {
+    /// Differing levels, bids then asks, each best price first
+    pub levels: Vec<LevelDiff>,
+    /// Live orders only in the book `diff` was called on, by id
+    pub only_ours: Vec<OrderId>,
+    /// Live orders only in the other book, by id
+    pub only_theirs: Vec<OrderId>,
+    /// Live in both books, but with a different user, side, price or remaining quantity
+    pub mismatched: Vec<OrderId>,
+}
+
+impl BookDiff {
+    /// Whether the two books agree on all live state
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+            && self.only_ours.is_empty()
+            && self.only_theirs.is_empty()
+            && self.mismatched.is_empty()
+    }
+}
+
+/// What identifies a live order's state for comparison
+type LiveOrder<'a> = (&'a str, Side, Price, Quantity);
+
+impl OrderBook {
+    /// Compare this book's live state with another's
+    ///
+    /// # Time Complexity
+    /// O(N log N) where N is the number of orders in both books
+    pub fn diff(&self, other: &OrderBook) -> BookDiff {
+        let mut diff = BookDiff::default();
+
+        for side in [Side::Buy, Side::Sell] {
+            let ours: BTreeMap<Price, Quantity> = self.live_levels(side).collect();
+            let theirs: BTreeMap<Price, Quantity> = other.live_levels(side).collect();
+            let mut prices: Vec<Price> = ours.keys().chain(theirs.keys()).copied().collect();
+            prices.sort_unstable();
+            prices.dedup();
+            if side == Side::Buy {
+                prices.reverse();
+            }
+
+            for price in prices {
+                let ours = ours.get(&price).copied().unwrap_or(0);
+                let theirs = theirs.get(&price).copied().unwrap_or(0);
+                if ours != theirs {
+                    diff.levels.push(LevelDiff { side, price, ours, theirs });
+                }
+            }
+        }
+
+        let ours = self.live_orders_by_id();
+        let theirs = other.live_orders_by_id();
+        for (&order_id, order) in &ours {
+            match theirs.get(&order_id) {
+                None => diff.only_ours.push(order_id),
+                Some(their_order) if their_order != order => diff.mismatched.push(order_id),
+                Some(_) => {}
+            }
+        }
+        diff.only_theirs = theirs.keys().filter(|id| !ours.contains_key(id)).copied().collect();
+
+        diff.only_ours.sort_unstable();
+        diff.only_theirs.sort_unstable();
+        diff.mismatched.sort_unstable();
+        diff
+    }
+
+    fn live_orders_by_id(&self) -> HashMap<OrderId, LiveOrder<'_>> {
+        self.order_index
+            .iter()
+            .filter(|(_, m)| matches!(m.status, OrderStatus::Open | OrderStatus::PartiallyFilled))
+            .map(|(&id, m)| (id, (m.user_id.as_str(), m.side, m.price, m.total_remaining())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::Order;
+
+    fn book(orders: &[Order]) -> OrderBook {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        for order in orders {
+            book.process_limit_order(order.clone()).unwrap();
+        }
+        book
+    }
+
+    #[test]
+    fn test_diff_compares_live_state_only() {
+        let common = [order(1, Side::Buy, 4900, 100), order(2, Side::Sell, 5100, 100)];
+        let mirror = book(&common);
+
+        // The primary also saw an order that was cancelled but is still queued
+        let mut primary = book(&common);
+        primary.process_limit_order(order(3, Side::Buy, 4900, 50)).unwrap();
+        primary.cancel_order(3).unwrap();
+        assert!(primary.diff(&mirror).is_empty());
+        assert!(mirror.diff(&primary).is_empty());
+
+        // A resting order the mirror missed, and one it has at the wrong size
+        primary.process_limit_order(order(4, Side::Sell, 5200, 30)).unwrap();
+        primary.modify_quantity(1, 80).unwrap();
+        let diff = primary.diff(&mirror);
+        assert_eq!(
+            diff.levels,
+            vec![
+                LevelDiff { side: Side::Buy, price: 4900, ours: 80, theirs: 100 },
+                LevelDiff { side: Side::Sell, price: 5200, ours: 30, theirs: 0 },
+            ]
+        );
+        assert_eq!(diff.only_ours, vec![4]);
+        assert!(diff.only_theirs.is_empty());
+        assert_eq!(diff.mismatched, vec![1]);
+
+        let reverse = mirror.diff(&primary);
+        assert_eq!(reverse.only_theirs, vec![4]);
+        assert_eq!(
+            reverse.levels[1],
+            LevelDiff { side: Side::Sell, price: 5200, ours: 0, theirs: 30 }
+        );
+    }
+}
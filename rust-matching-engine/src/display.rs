@@ -0,0 +1,118 @@
+//! Displayed prices
+//!
+//! A binary market's outcomes are complements: buying NO at 3500 is selling
+//! YES at 6500. With `OrderBookConfig::invert_display` set, the `_displayed`
+//! views report the book in the complementary outcome's terms, so a NO book
+//! reads like the YES book traders think in. Each price becomes
+//! `10000 - price` and the sides swap: NO asks are YES bids, best first, and
+//! NO bids are YES asks.
+//!
+//! Only these views change. Orders, trades, events and every other query
+//! stay in the book's own prices. Without the flag the views are the plain
+//! ones.
+
+use crate::units::BASIS_POINTS_PER_DOLLAR;
+use crate::{Depth, OrderBook, Price, Quantity, Side};
+
+impl OrderBook {
+    /// A price of this book as it is displayed
+    pub fn display_price(&self, price: Price) -> Price {
+        if self.config.invert_display {
+            BASIS_POINTS_PER_DOLLAR.saturating_sub(price)
+        } else {
+            price
+        }
+    }
+
+    /// `get_depth` as displayed: `(bids, asks)`, each best first
+    pub fn get_depth_displayed(&self, levels: usize) -> Depth {
+        let (bids, asks) = self.get_depth(levels);
+        if !self.config.invert_display {
+            return (bids, asks);
+        }
+        let invert = |side: Vec<(Price, Quantity)>| {
+            side.into_iter()
+                .map(|(price, quantity)| (self.display_price(price), quantity))
+                .collect()
+        };
+        (invert(asks), invert(bids))
+    }
+
+    /// Best bid as displayed (the best ask, inverted, on an inverted book)
+    ///
+    /// Like `get_depth_displayed`, this is the best live level.
+    pub fn best_bid_displayed(&self) -> Option<Price> {
+        let side = if self.config.invert_display { Side::Sell } else { Side::Buy };
+        self.best_live_price(side).map(|price| self.display_price(price))
+    }
+
+    /// Best ask as displayed (the best bid, inverted, on an inverted book)
+    pub fn best_ask_displayed(&self) -> Option<Price> {
+        let side = if self.config.invert_display { Side::Buy } else { Side::Sell };
+        self.best_live_price(side).map(|price| self.display_price(price))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Order, OrderBook, OrderBookConfig, OrderId, Price, Quantity, Side};
+
+    fn order(id: OrderId, outcome: &str, side: Side, price: Price, quantity: Quantity) -> Order {
+        Order::with_timestamp(
+            id,
+            format!("user{}", id),
+            "market1".to_string(),
+            outcome.to_string(),
+            side,
+            price,
+            quantity,
+            id,
+        )
+    }
+
+    #[test]
+    fn test_inverted_no_book_displays_yes_prices() {
+        let config = OrderBookConfig { invert_display: true, ..Default::default() };
+        let mut no = OrderBook::with_config("market1".to_string(), "NO".to_string(), config);
+        let mut yes = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // The same interest, expressed on each outcome
+        for (id, side, price, quantity) in [
+            (1, Side::Buy, 3500, 100),
+            (2, Side::Buy, 3400, 50),
+            (3, Side::Sell, 3700, 80),
+            (4, Side::Sell, 3900, 20),
+        ] {
+            no.process_limit_order(order(id, "NO", side, price, quantity)).unwrap();
+            let complement = order(id, "YES", side.opposite(), 10_000 - price, quantity);
+            yes.process_limit_order(complement).unwrap();
+        }
+
+        assert_eq!(no.get_depth_displayed(5), yes.get_depth(5));
+        assert_eq!(no.get_depth_displayed(5).0, vec![(6300, 80), (6100, 20)]);
+        assert_eq!(no.best_bid_displayed(), yes.best_bid());
+        assert_eq!(no.best_ask_displayed(), Some(6500));
+        assert_eq!(no.display_price(3500), 6500);
+
+        // The book itself is untouched, and uninverted books display as-is
+        assert_eq!(no.best_bid(), Some(3500));
+        assert_eq!(yes.get_depth_displayed(5), yes.get_depth(5));
+        assert_eq!(yes.best_ask_displayed(), yes.best_ask());
+    }
+
+    #[test]
+    fn test_displayed_bbo_agrees_with_displayed_depth() {
+        let config = OrderBookConfig { invert_display: true, ..Default::default() };
+        let mut no = OrderBook::with_config("market1".to_string(), "NO".to_string(), config);
+        no.process_limit_order(order(1, "NO", Side::Buy, 3500, 100)).unwrap();
+        no.process_limit_order(order(2, "NO", Side::Buy, 3400, 50)).unwrap();
+        no.process_limit_order(order(3, "NO", Side::Sell, 3700, 80)).unwrap();
+
+        // Cancelling the best NO bid moves the displayed YES ask
+        no.cancel_order(1).unwrap();
+        let (bids, asks) = no.get_depth_displayed(1);
+        assert_eq!(no.best_ask_displayed(), asks.first().map(|&(price, _)| price));
+        assert_eq!(no.best_ask_displayed(), Some(6600));
+        assert_eq!(no.best_bid_displayed(), bids.first().map(|&(price, _)| price));
+    }
+}
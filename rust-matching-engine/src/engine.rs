@@ -0,0 +1,170 @@
+//! A book behind a channel, for async and multi-threaded services
+//!
+//! [`run_engine`] owns an [`OrderBook`] on one thread and serves
+//! [`Request`]s from a channel, sending one [`Response`] per request, in
+//! order. A request is either a [`Command`], the same one the replay log
+//! records, or a read-only [`Query`]. Tasks on other threads (or an async runtime, via a blocking
+//! bridge) talk to the book through the channels without any locking, and
+//! the book keeps its strictly sequential processing.
+//!
+//! ```
+//! use std::sync::mpsc;
+//! use std::thread;
+//! use matching_engine::engine::{run_engine, Query, Response};
+//! use matching_engine::OrderBook;
+//!
+//! let (requests, rx) = mpsc::channel();
+//! let (tx, responses) = mpsc::channel();
+//! let book = OrderBook::new("market1".to_string(), "YES".to_string());
+//! let engine = thread::spawn(move || run_engine(book, rx, tx));
+//!
+//! requests.send(Query::Depth(5).into()).unwrap();
+//! assert!(matches!(responses.recv().unwrap(), Response::Depth { .. }));
+//!
+//! // Dropping the sender stops the loop and hands the book back
+//! drop(requests);
+//! let book = engine.join().unwrap();
+//! assert_eq!(book.active_orders(), 0);
+//! ```
+
+use std::sync::mpsc::{Receiver, Sender};
+
+pub use crate::replay::Command;
+use crate::{
+    BookMetrics, OrderBook, OrderBookError, OrderId, OrderRecord, Price, ProcessOrderResult,
+    Quantity,
+};
+
+/// A request to the engine thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// A change to the book, as the replay log records it (boxed, as it
+    /// dwarfs a query)
+    Command(Box<Command>),
+    /// A read that doesn't change the book
+    Query(Query),
+}
+
+impl From<Command> for Request {
+    fn from(command: Command) -> Self {
+        Request::Command(Box::new(command))
+    }
+}
+
+impl From<Query> for Request {
+    fn from(query: Query) -> Self {
+        Request::Query(query)
+    }
+}
+
+/// A read-only question about the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    /// `get_depth` to this many levels
+    Depth(usize),
+    /// `get_order`
+    Order(OrderId),
+    /// `metrics`
+    Metrics,
+}
+
+/// The engine's answer to one `Request`
+#[derive(Debug)]
+pub enum Response {
+    /// Outcome of a `Submit`, including any trades (boxed, as it dwarfs the others)
+    Submitted(Box<Result<ProcessOrderResult, OrderBookError>>),
+    /// Outcome of a `Cancel`
+    Cancelled(Result<(), OrderBookError>),
+    /// Outcome of an `Amend`
+    Amended(Result<(), OrderBookError>),
+    /// Answer to `Query::Depth`
+    Depth {
+        bids: Vec<(Price, Quantity)>,
+        asks: Vec<(Price, Quantity)>,
+    },
+    /// Answer to `Query::Order`
+    Order(Option<OrderRecord>),
+    /// Answer to `Query::Metrics`
+    Metrics(BookMetrics),
+}
+
+/// Serve requests against `book` until either channel closes
+///
+/// Commands are applied as `Command::apply` applies them, so a log of the
+/// commands served replays to the same book. Returns the book once
+/// `requests` is disconnected and drained, or as soon as `responses` has no
+/// receiver left.
+pub fn run_engine(
+    mut book: OrderBook,
+    requests: Receiver<Request>,
+    responses: Sender<Response>,
+) -> OrderBook {
+    for request in requests {
+        let response = match request {
+            Request::Command(command) => match *command {
+                Command::Submit(order) => {
+                    Response::Submitted(Box::new(book.process_limit_order(order)))
+                }
+                Command::Cancel(order_id) => Response::Cancelled(book.cancel_order(order_id)),
+                Command::Amend { order_id, price, quantity } => {
+                    Response::Amended(book.amend_order(order_id, price, quantity))
+                }
+            },
+            Request::Query(Query::Depth(levels)) => {
+                let (bids, asks) = book.get_depth(levels);
+                Response::Depth { bids, asks }
+            }
+            Request::Query(Query::Order(order_id)) => Response::Order(book.get_order(order_id)),
+            Request::Query(Query::Metrics) => Response::Metrics(book.metrics()),
+        };
+        if responses.send(response).is_err() {
+            break;
+        }
+    }
+    book
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::{OrderStatus, Side};
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn test_engine_loop_returns_trades() {
+        let (requests, rx) = mpsc::channel();
+        let (tx, responses) = mpsc::channel();
+        let book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let engine = thread::spawn(move || run_engine(book, rx, tx));
+
+        requests.send(Command::Submit(order(1, Side::Sell, 5000, 100)).into()).unwrap();
+        requests.send(Command::Submit(order(2, Side::Buy, 5000, 40)).into()).unwrap();
+        requests.send(Command::Cancel(9).into()).unwrap();
+        requests.send(Request::Query(Query::Order(1))).unwrap();
+
+        let Response::Submitted(result) = responses.recv().unwrap() else {
+            panic!("expected the maker's result");
+        };
+        assert!(result.unwrap().trades.is_empty());
+        let Response::Submitted(result) = responses.recv().unwrap() else {
+            panic!("expected the taker's result");
+        };
+        let result = result.unwrap();
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!((result.trades[0].maker_order_id, result.trades[0].quantity), (1, 40));
+        assert!(matches!(
+            responses.recv().unwrap(),
+            Response::Cancelled(Err(OrderBookError::OrderNotFound(9)))
+        ));
+        let Response::Order(Some(record)) = responses.recv().unwrap() else {
+            panic!("expected the maker's record");
+        };
+        assert_eq!(record.status, OrderStatus::PartiallyFilled);
+
+        drop(requests);
+        let book = engine.join().unwrap();
+        assert_eq!(book.get_order_remaining(1), Some(60));
+    }
+}
@@ -0,0 +1,342 @@
+//! Event stream for order book listeners
+//!
+//! Listeners registered with [`OrderBook::add_listener`] see every trade and
+//! cancellation as it happens, for audit trails and downstream feeds, plus
+//! throttled depth snapshots and per-maker fill progress if configured.
+//! Rejections are reported for orders submitted through
+//! `process_limit_order` (and its variants), `submit_quote` and
+//! `match_against`; other calls only return their errors. Listeners only
+//! observe: they receive events by reference and have no access to the book.
+
+use std::fmt;
+
+use crate::{DepthSnapshot, OrderBook, OrderBookError, OrderId, Quantity, Trade};
+
+/// Something that happened on the book
+#[derive(Debug, Clone)]
+pub enum OrderEvent {
+    /// A trade executed
+    Trade(Trade),
+    /// A resting order was cancelled, by request or by the book
+    Cancelled {
+        order_id: OrderId,
+        client_order_id: Option<String>,
+    },
+    /// A submitted order was rejected; `code` is `OrderBookError::code()`
+    Rejected {
+        order_id: OrderId,
+        client_order_id: Option<String>,
+        code: u16,
+    },
+    /// A coalesced snapshot of the top levels, if `depth_throttle` is configured
+    Depth(DepthSnapshot),
+    /// A resting order was filled, right after the `Trade` event, if
+    /// `maker_fill_events` is configured
+    ///
+    /// `cumulative_filled` counts every fill since the order was placed;
+    /// `remaining` is what it has left to trade, any iceberg reserve included,
+    /// and is 0 once it is filled.
+    MakerFill {
+        order_id: OrderId,
+        fill_quantity: Quantity,
+        cumulative_filled: Quantity,
+        remaining: Quantity,
+    },
+}
+
+/// A callback receiving every event, in order
+///
+/// Listeners run while the book is mid-operation, and the borrow checker
+/// rules out reentrant calls: the book is mutably borrowed for the whole
+/// call, so a listener cannot reach it. A listener holding the book's own
+/// `Arc<Mutex<OrderBook>>` finds it locked by the caller (`try_lock` fails
+/// with `WouldBlock`; `lock` would deadlock). To act on an event, queue the
+/// follow-up and submit it once the call that emitted the event returns.
+pub type Listener = Box<dyn FnMut(&OrderEvent) + Send>;
+
+/// The book's registered listeners
+#[derive(Default)]
+pub(crate) struct Listeners(Vec<Listener>);
+
+impl Listeners {
+    pub(crate) fn emit(&mut self, event: OrderEvent) {
+        for listener in &mut self.0 {
+            listener(&event);
+        }
+    }
+}
+
+/// Listeners can't be copied, so a cloned book starts with none
+impl Clone for Listeners {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Listeners({})", self.0.len())
+    }
+}
+
+impl OrderBook {
+    /// Register a listener for all subsequent events
+    pub fn add_listener(&mut self, listener: Listener) {
+        self.listeners.0.push(listener);
+    }
+
+    /// Count a fill against a resting order, reporting its progress if configured
+    ///
+    /// `remaining` is what the maker has left after the fill.
+    pub(crate) fn record_maker_fill(
+        &mut self,
+        order_id: OrderId,
+        fill_quantity: Quantity,
+        remaining: Quantity,
+    ) {
+        let Some(metadata) = self.order_index.get_mut(&order_id) else {
+            return;
+        };
+        metadata.filled_quantity += fill_quantity;
+        if self.config.maker_fill_events {
+            let cumulative_filled = metadata.filled_quantity;
+            self.listeners.emit(OrderEvent::MakerFill {
+                order_id,
+                fill_quantity,
+                cumulative_filled,
+                remaining,
+            });
+        }
+    }
+}
+
+impl OrderBookError {
+    /// Stable numeric reason code for this error
+    ///
+    /// Codes are part of the public contract: they are never reused or
+    /// renumbered, and new variants get new codes.
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::DuplicateOrderId(_) => 1,
+            Self::OrderNotFound(_) => 2,
+            Self::OrderAlreadyCancelled(_) => 3,
+            Self::OrderAlreadyFilled(_) => 4,
+            Self::InvalidPrice => 5,
+            Self::InvalidQuantity => 6,
+            Self::MarketMismatch => 7,
+            Self::WouldLock => 8,
+            Self::WouldCross => 9,
+            Self::SameSide => 10,
+            Self::IncompatiblePrice => 11,
+            Self::SelfTrade => 12,
+            Self::OrderIdOutOfRange(_) => 13,
+            Self::WouldSelfCross => 14,
+            Self::NotionalExceeded => 15,
+            Self::DuplicateClientOrderId(_) => 16,
+            Self::NoLiquidity => 17,
+            Self::InvalidTick => 18,
+            Self::TooSoonToCancel { .. } => 19,
+            Self::PeggedOrder(_) => 20,
+            Self::TimestampOutOfWindow { .. } => 21,
+            Self::MarketHalted => 22,
+            Self::InconsistentSnapshot => 23,
+            Self::OutsideLimit(_) => 24,
+            Self::ExceedsRemaining(_) => 25,
+            Self::WrongSide(_) => 26,
+            Self::RateLimited { .. } => 27,
+            Self::MarketOrderIncomplete => 28,
+            Self::DuplicateBasketBook => 29,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::{Order, OrderBookConfig, Side};
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex, TryLockError};
+
+    #[test]
+    fn test_error_codes_are_distinct_and_stable() {
+        let errors = [
+            OrderBookError::DuplicateOrderId(1),
+            OrderBookError::OrderNotFound(1),
+            OrderBookError::OrderAlreadyCancelled(1),
+            OrderBookError::OrderAlreadyFilled(1),
+            OrderBookError::InvalidPrice,
+            OrderBookError::InvalidQuantity,
+            OrderBookError::MarketMismatch,
+            OrderBookError::WouldLock,
+            OrderBookError::WouldCross,
+            OrderBookError::SameSide,
+            OrderBookError::IncompatiblePrice,
+            OrderBookError::SelfTrade,
+            OrderBookError::OrderIdOutOfRange(1),
+            OrderBookError::WouldSelfCross,
+            OrderBookError::NotionalExceeded,
+            OrderBookError::DuplicateClientOrderId("a".to_string()),
+            OrderBookError::NoLiquidity,
+            OrderBookError::InvalidTick,
+            OrderBookError::TooSoonToCancel { remaining_micros: 1 },
+            OrderBookError::PeggedOrder(1),
+            OrderBookError::TimestampOutOfWindow { timestamp: 1, now: 2 },
+            OrderBookError::MarketHalted,
+            OrderBookError::InconsistentSnapshot,
+            OrderBookError::OutsideLimit(1),
+            OrderBookError::ExceedsRemaining(1),
+            OrderBookError::WrongSide(1),
+            OrderBookError::RateLimited { retry_after_micros: 1 },
+            OrderBookError::MarketOrderIncomplete,
+            OrderBookError::DuplicateBasketBook,
+        ];
+        let codes: Vec<u16> = errors.iter().map(OrderBookError::code).collect();
+
+        let distinct: HashSet<u16> = codes.iter().copied().collect();
+        assert_eq!(distinct.len(), errors.len());
+        // Payloads don't affect the code
+        assert_eq!(OrderBookError::DuplicateOrderId(7).code(), codes[0]);
+        assert_eq!(codes, (1..=29).collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn test_listener_sees_trades_cancels_and_rejections() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let sink = Arc::clone(&events);
+        book.add_listener(Box::new(move |event| sink.lock().unwrap().push(event.clone())));
+
+        let order = |id, side, price| {
+            Order::with_timestamp(
+                id,
+                format!("user{}", id),
+                "market1".to_string(),
+                "YES".to_string(),
+                side,
+                price,
+                100,
+                id,
+            )
+        };
+        book.process_limit_order(order(1, Side::Sell, 5000)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5100)).unwrap();
+        book.process_limit_order(order(3, Side::Buy, 5000)).unwrap();
+        book.cancel_order(2).unwrap();
+        assert!(book.process_limit_order(order(4, Side::Buy, 0)).is_err());
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], OrderEvent::Trade(t) if t.maker_order_id == 1));
+        assert!(matches!(events[1], OrderEvent::Cancelled { order_id: 2, .. }));
+        assert!(matches!(
+            events[2],
+            OrderEvent::Rejected { order_id: 4, code, .. }
+                if code == OrderBookError::InvalidPrice.code()
+        ));
+    }
+
+    #[test]
+    fn test_listener_sees_refused_match_against() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Sell, 5000, 100)).unwrap();
+        let sink = Arc::clone(&events);
+        book.add_listener(Box::new(move |event| sink.lock().unwrap().push(event.clone())));
+
+        let mut taker = order(2, Side::Buy, 4900, 50);
+        taker.client_order_id = Some("cross-1".to_string());
+        assert_eq!(book.match_against(taker, 1).unwrap_err(), OrderBookError::IncompatiblePrice);
+
+        let events = events.lock().unwrap();
+        assert!(matches!(
+            &events[..],
+            [OrderEvent::Rejected { order_id: 2, client_order_id: Some(id), code }]
+                if id == "cross-1" && *code == OrderBookError::IncompatiblePrice.code()
+        ));
+    }
+
+    #[test]
+    fn test_listener_cannot_reenter_book() {
+        let book = Arc::new(Mutex::new(OrderBook::new("market1".to_string(), "YES".to_string())));
+        let nested = Arc::new(Mutex::new(Vec::new()));
+        let (handle, sink) = (Arc::downgrade(&book), Arc::clone(&nested));
+        book.lock().unwrap().add_listener(Box::new(move |event| {
+            if let OrderEvent::Trade(trade) = event {
+                // Try to cancel the maker mid-match through the shared handle
+                let book = handle.upgrade().unwrap();
+                let denied = match book.try_lock() {
+                    Err(TryLockError::WouldBlock) => true,
+                    Ok(mut book) => book.cancel_order(trade.maker_order_id).is_ok(),
+                    Err(TryLockError::Poisoned(_)) => false,
+                };
+                sink.lock().unwrap().push(denied);
+            }
+        }));
+
+        let order = |id, side| {
+            Order::with_timestamp(
+                id,
+                format!("user{}", id),
+                "market1".to_string(),
+                "YES".to_string(),
+                side,
+                5000,
+                100,
+                id,
+            )
+        };
+        let mut guard = book.lock().unwrap();
+        guard.process_limit_order(order(1, Side::Sell)).unwrap();
+        guard.process_limit_order(order(2, Side::Sell)).unwrap();
+        let result = guard.process_limit_order(order(3, Side::Buy)).unwrap();
+        assert_eq!(result.trades.len(), 1);
+        drop(guard);
+
+        assert_eq!(*nested.lock().unwrap(), vec![true]);
+        let book = book.lock().unwrap();
+        assert_eq!(book.get_order_remaining(2), Some(100));
+        assert_eq!(book.active_orders(), 1);
+    }
+
+    #[test]
+    fn test_maker_fill_reports_cumulative_progress() {
+        let config = OrderBookConfig { maker_fill_events: true, ..Default::default() };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        book.add_listener(Box::new(move |event| sink.lock().unwrap().push(event.clone())));
+
+        let order = |id, side, quantity| {
+            Order::with_timestamp(
+                id,
+                format!("user{}", id),
+                "market1".to_string(),
+                "YES".to_string(),
+                side,
+                5000,
+                quantity,
+                id,
+            )
+        };
+        book.process_limit_order(order(1, Side::Sell, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 60)).unwrap();
+        book.process_limit_order(order(3, Side::Buy, 50)).unwrap();
+
+        let fills: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match *event {
+                OrderEvent::MakerFill { order_id, fill_quantity, cumulative_filled, remaining } => {
+                    Some((order_id, fill_quantity, cumulative_filled, remaining))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fills, vec![(1, 60, 60, 40), (1, 40, 100, 0)]);
+        // Taker 3 rests with its last 10 and gets no fill event of its own
+        assert_eq!(book.get_order_remaining(3), Some(10));
+        assert!(matches!(events.lock().unwrap()[0], OrderEvent::Trade(_)));
+    }
+}
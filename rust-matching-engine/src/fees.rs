@@ -0,0 +1,89 @@
+//! Fees and maker rebates
+//!
+//! The book itself charges nothing; settlement applies a [`FeeSchedule`] to
+//! the trades it reports. Amounts are signed per-share rates in basis points,
+//! so a trade's fee or rebate is its quantity times the rate, in the same
+//! basis-point units as prices. A negative maker rebate is a maker fee.
+
+use std::collections::HashMap;
+
+use crate::{Trade, UserId};
+
+/// Per-share fee and rebate rates, in basis points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FeeSchedule {
+    /// Paid to the maker per filled share; negative if makers pay instead
+    pub maker_rebate_per_share: i64,
+    /// Charged to the taker per filled share
+    pub taker_fee_per_share: i64,
+}
+
+impl FeeSchedule {
+    /// Rebate owed to the maker of a trade
+    pub fn maker_rebate(&self, trade: &Trade) -> i64 {
+        (trade.quantity as i64).saturating_mul(self.maker_rebate_per_share)
+    }
+
+    /// Fee owed by the taker of a trade
+    pub fn taker_fee(&self, trade: &Trade) -> i64 {
+        (trade.quantity as i64).saturating_mul(self.taker_fee_per_share)
+    }
+}
+
+/// Net rebate owed to each maker across `trades`
+///
+/// Positive amounts are paid to the maker, negative ones charged to them.
+/// Users who made none of the trades are absent, whatever they took.
+pub fn maker_rebates(trades: &[Trade], schedule: &FeeSchedule) -> HashMap<UserId, i64> {
+    let mut rebates = HashMap::new();
+    for trade in trades {
+        *rebates.entry(trade.maker_user_id.clone()).or_insert(0) += schedule.maker_rebate(trade);
+    }
+    rebates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Order, OrderBook, OrderId, Price, Quantity, Side};
+
+    fn order(id: OrderId, user: &str, side: Side, price: Price, quantity: Quantity) -> Order {
+        Order::with_timestamp(
+            id,
+            user.to_string(),
+            "market1".to_string(),
+            "YES".to_string(),
+            side,
+            price,
+            quantity,
+            id,
+        )
+    }
+
+    #[test]
+    fn test_maker_rebates_follow_the_maker_side() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, "alice", Side::Sell, 5000, 100)).unwrap();
+        book.process_limit_order(order(2, "bob", Side::Buy, 4900, 100)).unwrap();
+
+        let mut trades = Vec::new();
+        let mut submit = |order| trades.extend(book.process_limit_order(order).unwrap().trades);
+        // carol takes 60 from alice, then alice takes 30 from bob
+        submit(order(3, "carol", Side::Buy, 5000, 60));
+        submit(order(4, "alice", Side::Sell, 4900, 30));
+        // dave takes the rest of alice's 100 and 50 more of bob's
+        submit(order(5, "dave", Side::Buy, 5000, 40));
+        submit(order(6, "dave", Side::Sell, 4900, 50));
+
+        let schedule = FeeSchedule { maker_rebate_per_share: 2, taker_fee_per_share: 5 };
+        let rebates = maker_rebates(&trades, &schedule);
+        let expected: HashMap<UserId, i64> =
+            [("alice".to_string(), 200), ("bob".to_string(), 160)].into();
+        assert_eq!(rebates, expected);
+
+        // A maker fee is a negative rebate
+        let schedule = FeeSchedule { maker_rebate_per_share: -1, ..schedule };
+        assert_eq!(maker_rebates(&trades[..2], &schedule)["alice"], -60);
+        assert_eq!(maker_rebates(&trades[..2], &schedule)["bob"], -30);
+    }
+}
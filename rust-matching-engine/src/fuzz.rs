@@ -0,0 +1,304 @@
+//! Deterministic fuzzing of the match loop.
+//!
+//! Each run drives a fresh book with a long pseudo-random sequence of valid
+//! operations (limit, market, midpoint and iceberg submits with last look and level limits,
+//! cancels, amends, quotes, direct matches, mass cancels) drawn from a seeded
+//! generator, and checks the book after every step:
+//!
+//! - Nothing panics, including the debug-build fill checks
+//! - No order trades more than the quantity it was given
+//! - The book's health check passes (live prices uncrossed, level totals and
+//!   the index agree, no filled order left queued)
+//! - The live order counter and the per-user index agree with the index
+//! - A market order accepted under `MarketOrderPolicy::RejectIfIncomplete`
+//!   fills completely
+//!
+//! Failures report the seed and step, and `FUZZ_SEED=<seed>` reruns just that
+//! seed. `FUZZ_RUNS` raises the number of seeds tried (default 32).
+
+use super::*;
+use std::collections::HashMap;
+
+/// xorshift64*, so runs are reproducible from the seed alone
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift state must be non-zero
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn chance(&mut self, one_in: u64) -> bool {
+        self.below(one_in) == 0
+    }
+}
+
+const USERS: u64 = 4;
+
+fn user(rng: &mut Rng) -> String {
+    format!("user{}", rng.below(USERS))
+}
+
+fn price(rng: &mut Rng) -> Price {
+    4900 + 10 * rng.below(20)
+}
+
+fn quantity(rng: &mut Rng) -> Quantity {
+    1 + rng.below(150)
+}
+
+/// The configurations each seed is run under
+fn configs(rng: &mut Rng) -> OrderBookConfig {
+    let matching_mode = match rng.below(3) {
+        0 => MatchingMode::PriceTime,
+        1 => MatchingMode::ProRata,
+        _ => MatchingMode::Hybrid { fifo_portion: rng.below(100) },
+    };
+    let self_trade_policy = match rng.below(3) {
+        0 => SelfTradePolicy::CancelResting,
+        1 => SelfTradePolicy::RejectTaker,
+        _ => SelfTradePolicy::DecrementAndCancel,
+    };
+    let execution_price_policy = if rng.chance(2) {
+        ExecutionPricePolicy::TakerImprovement
+    } else {
+        ExecutionPricePolicy::TakerPrice
+    };
+    OrderBookConfig {
+        matching_mode,
+        self_trade_policy,
+        execution_price_policy,
+        trade_history_capacity: 16,
+        order_archive_capacity: rng.chance(3).then(|| rng.below(8) as usize),
+        price_band_bps: rng.chance(4).then(|| 20 * (1 + rng.below(10))),
+        market_order_policy: if rng.chance(3) {
+            MarketOrderPolicy::RejectIfIncomplete
+        } else {
+            MarketOrderPolicy::CancelRemainder
+        },
+        ..Default::default()
+    }
+}
+
+/// Trading capacity of every order the run has created
+///
+/// An order may trade up to its submitted size; a successful amend resets
+/// what is left to the new size.
+#[derive(Default)]
+struct Ledger {
+    capacity: HashMap<OrderId, Quantity>,
+    filled: HashMap<OrderId, Quantity>,
+}
+
+impl Ledger {
+    fn record(&mut self, trades: &[Trade]) {
+        for trade in trades {
+            assert!(trade.quantity > 0, "empty trade {:?}", trade);
+            assert_ne!(trade.taker_user_id, trade.maker_user_id, "self trade {:?}", trade);
+            for id in [trade.taker_order_id, trade.maker_order_id] {
+                *self.filled.entry(id).or_default() += trade.quantity;
+            }
+        }
+    }
+
+    fn filled(&self, id: OrderId) -> Quantity {
+        self.filled.get(&id).copied().unwrap_or(0)
+    }
+}
+
+fn is_live(book: &OrderBook, id: OrderId) -> bool {
+    matches!(
+        book.get_order_status(id),
+        Some(OrderStatus::Open | OrderStatus::PartiallyFilled)
+    )
+}
+
+fn check(book: &OrderBook, ledger: &Ledger) {
+    for (&id, &capacity) in &ledger.capacity {
+        let filled = ledger.filled(id);
+        let live = if is_live(book, id) { book.get_order_remaining(id).unwrap_or(0) } else { 0 };
+        assert!(
+            filled + live <= capacity,
+            "order {} overfilled: {} filled + {} live > {}",
+            id,
+            filled,
+            live,
+            capacity
+        );
+    }
+
+    let health = book.health();
+    assert!(health.is_healthy(), "unhealthy book: {:?}", health);
+
+    let live = book.order_index.keys().filter(|&&id| is_live(book, id)).count();
+    assert_eq!(book.active_orders(), live, "live order counter drifted");
+
+    let indexed: usize = (0..USERS)
+        .map(|u| {
+            let ids = book.user_orders(&format!("user{}", u));
+            assert!(ids.iter().all(|&id| is_live(book, id)), "user index lists a dead order");
+            ids.len()
+        })
+        .sum();
+    assert_eq!(indexed, live, "user index misses live orders");
+}
+
+/// Apply one random operation
+fn step(book: &mut OrderBook, rng: &mut Rng, ledger: &mut Ledger, next_id: &mut OrderId) {
+    let created: Vec<OrderId> = ledger.capacity.keys().copied().collect();
+    let existing = |rng: &mut Rng| -> Option<OrderId> {
+        // Sorted so the pick doesn't depend on hash map order
+        let mut ids = created.clone();
+        ids.sort_unstable();
+        (!ids.is_empty()).then(|| ids[rng.below(ids.len() as u64) as usize])
+    };
+
+    match rng.below(12) {
+        0..=5 => {
+            let id = *next_id;
+            *next_id += 1;
+            let side = if rng.chance(2) { Side::Buy } else { Side::Sell };
+            let size = quantity(rng);
+            let mut order = Order::with_timestamp(
+                id,
+                user(rng),
+                "market1".to_string(),
+                "YES".to_string(),
+                side,
+                price(rng),
+                size,
+                id,
+            );
+            if rng.chance(5) {
+                order.last_look_bps = Some(10 * rng.below(5));
+            }
+            if rng.chance(5) {
+                order.max_levels = Some(1 + rng.below(3) as usize);
+            }
+            if rng.chance(6) {
+                order.max_rest_quantity = Some(rng.below(100));
+            }
+            if rng.chance(8) {
+                order.time_in_force = TimeInForce::FillAndKill;
+            }
+            if rng.chance(6) {
+                order.display_quantity = Some(1 + rng.below(40));
+                if rng.chance(2) {
+                    order.iceberg_slicing = IcebergSlicing::EqualSlices;
+                }
+            }
+            if rng.chance(8) {
+                order.order_type = OrderType::Market;
+                order.max_slippage_bps = rng.chance(2).then(|| 10 * rng.below(10));
+            } else if rng.chance(10) {
+                order.order_type = OrderType::MidpointPeg;
+            }
+            ledger.capacity.insert(id, size);
+            let all_or_nothing = order.order_type == OrderType::Market
+                && book.config.market_order_policy == MarketOrderPolicy::RejectIfIncomplete;
+            if let Ok(result) = book.process_limit_order(order) {
+                ledger.record(&result.trades);
+                if all_or_nothing {
+                    assert_eq!(result.order.status, OrderStatus::Filled, "{:?}", result.order);
+                }
+            }
+        }
+        6 | 7 => {
+            if let Some(id) = existing(rng) {
+                let _ = book.cancel_order(id);
+            }
+        }
+        8 => {
+            if let Some(id) = existing(rng) {
+                let size = quantity(rng);
+                let amended = if rng.chance(2) {
+                    book.amend_order(id, price(rng), size)
+                } else {
+                    book.modify_quantity(id, size)
+                };
+                if amended.is_ok() {
+                    ledger.capacity.insert(id, ledger.filled(id) + size);
+                }
+            }
+        }
+        9 => {
+            let bid = price(rng);
+            let ask = bid + 10 * (1 + rng.below(3));
+            let (bid_qty, ask_qty) = (quantity(rng), quantity(rng));
+            if let Ok(quote) = book.submit_quote(&user(rng), bid, bid_qty, ask, ask_qty) {
+                ledger.capacity.insert(quote.bid.order.id, bid_qty);
+                ledger.capacity.insert(quote.ask.order.id, ask_qty);
+                ledger.record(&quote.bid.trades);
+                ledger.record(&quote.ask.trades);
+                *next_id = (*next_id).max(quote.ask.order.id + 1);
+            }
+        }
+        10 => {
+            if let Some(maker) = existing(rng).filter(|&id| is_live(book, id)) {
+                let id = *next_id;
+                *next_id += 1;
+                let side = book.order_index[&maker].side.opposite();
+                let size = quantity(rng);
+                let taker = Order::with_timestamp(
+                    id,
+                    user(rng),
+                    "market1".to_string(),
+                    "YES".to_string(),
+                    side,
+                    price(rng),
+                    size,
+                    id,
+                );
+                ledger.capacity.insert(id, size);
+                if let Ok(trade) = book.match_against(taker, maker) {
+                    ledger.record(std::slice::from_ref(&trade));
+                }
+            }
+        }
+        _ => {
+            book.cancel_user_orders(&user(rng));
+        }
+    }
+}
+
+fn run(seed: u64, steps: usize) {
+    let mut rng = Rng::new(seed);
+    let config = configs(&mut rng);
+    let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+    let mut ledger = Ledger::default();
+    let mut next_id: OrderId = 1;
+
+    for n in 0..steps {
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            step(&mut book, &mut rng, &mut ledger, &mut next_id);
+            check(&book, &ledger);
+        }));
+        if outcome.is_err() {
+            panic!("fuzz failure: seed {} step {} (rerun with FUZZ_SEED={})", seed, n, seed);
+        }
+    }
+}
+
+#[test]
+fn fuzz_match_loop() {
+    if let Some(seed) = std::env::var("FUZZ_SEED").ok().and_then(|s| s.parse().ok()) {
+        run(seed, 2_000);
+        return;
+    }
+
+    let runs = std::env::var("FUZZ_RUNS").ok().and_then(|s| s.parse().ok()).unwrap_or(32);
+    for seed in 0..runs {
+        run(seed, 500);
+    }
+}
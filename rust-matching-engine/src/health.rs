@@ -0,0 +1,148 @@
+//! Book consistency health check
+//!
+//! Lazy deletion leaves cancelled orders queued among live ones until
+//! matching or cleanup drops them. The health check looks past them, the same
+//! way matching does, and reports the conditions that indicate real
+//! corruption. A locked book only counts as unhealthy when
+//! `LockedBookPolicy::Reject` is configured.
+
+use crate::{
+    LockedBookPolicy, OrderBook, OrderId, OrderStatus, Price, PriceLevelQueue, Quantity, Side,
+};
+
+/// Result of a book consistency check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookHealth {
+    /// Best bid with live quantity
+    pub best_live_bid: Option<Price>,
+    /// Best ask with live quantity
+    pub best_live_ask: Option<Price>,
+    /// Whether the book's `LockedBookPolicy` lets best bid equal best ask
+    pub lock_allowed: bool,
+    /// Levels whose total differs from the sum of their live orders' remainders
+    pub mismatched_levels: Vec<(Side, Price)>,
+    /// Filled orders found at the front of a level, after skipping cancelled ones
+    pub filled_fronts: Vec<OrderId>,
+}
+
+impl BookHealth {
+    /// Whether the live book is uncrossed, and unlocked unless locking is allowed
+    pub fn is_uncrossed(&self) -> bool {
+        match (self.best_live_bid, self.best_live_ask) {
+            (Some(bid), Some(ask)) => bid < ask || (bid == ask && self.lock_allowed),
+            _ => true,
+        }
+    }
+
+    /// Whether every check passed
+    pub fn is_healthy(&self) -> bool {
+        self.is_uncrossed() && self.mismatched_levels.is_empty() && self.filled_fronts.is_empty()
+    }
+}
+
+impl OrderBook {
+    /// Check the book's consistency
+    ///
+    /// # Time Complexity
+    /// O(N) where N is the number of queued orders
+    pub fn health(&self) -> BookHealth {
+        let mut health = BookHealth {
+            best_live_bid: None,
+            best_live_ask: None,
+            lock_allowed: self.config.locked_book_policy == LockedBookPolicy::Allow,
+            mismatched_levels: Vec::new(),
+            filled_fronts: Vec::new(),
+        };
+
+        for side in [Side::Buy, Side::Sell] {
+            let levels: Box<dyn Iterator<Item = (&Price, &PriceLevelQueue)>> = match side {
+                Side::Buy => Box::new(self.bids.iter().rev()),
+                Side::Sell => Box::new(self.asks.iter()),
+            };
+
+            let mut best_live = None;
+            for (&price, level) in levels {
+                let live = self.live_quantity_at(level);
+                if live != level.total_quantity {
+                    health.mismatched_levels.push((side, price));
+                }
+                if live > 0 && best_live.is_none() {
+                    best_live = Some(price);
+                }
+
+                let front = level.orders.iter().find(|o| {
+                    self.order_index
+                        .get(&o.id)
+                        .is_some_and(|m| m.status != OrderStatus::Cancelled)
+                });
+                if let Some(front) = front {
+                    if self.order_index[&front.id].status == OrderStatus::Filled {
+                        health.filled_fronts.push(front.id);
+                    }
+                }
+            }
+
+            match side {
+                Side::Buy => health.best_live_bid = best_live,
+                Side::Sell => health.best_live_ask = best_live,
+            }
+        }
+
+        health
+    }
+
+    /// Whether the book passes every `health` check
+    pub fn is_healthy(&self) -> bool {
+        self.health().is_healthy()
+    }
+
+    /// Sum of the indexed remainders of a level's open and partially filled orders
+    fn live_quantity_at(&self, level: &PriceLevelQueue) -> Quantity {
+        level
+            .orders
+            .iter()
+            .filter_map(|o| self.order_index.get(&o.id))
+            .filter(|m| matches!(m.status, OrderStatus::Open | OrderStatus::PartiallyFilled))
+            .map(|m| m.remaining_quantity)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+
+    #[test]
+    fn test_stale_cancelled_front_is_healthy() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Buy, 5100, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 5000, 100)).unwrap();
+        book.process_limit_order(order(3, Side::Buy, 5000, 50)).unwrap();
+        book.process_limit_order(order(4, Side::Sell, 5200, 100)).unwrap();
+
+        // Order 2 stays queued at the front of its level, ahead of order 3;
+        // the 5100 level had nothing else live, so it is gone
+        book.cancel_order(1).unwrap();
+        book.cancel_order(2).unwrap();
+        book.amend_order(4, 5050, 100).unwrap();
+        assert_eq!(book.level_cancel_ratio(Side::Buy, 5000), Some(0.5));
+
+        let health = book.health();
+        assert_eq!(health.best_live_bid, book.best_bid());
+        assert_eq!((health.best_live_bid, health.best_live_ask), (Some(5000), Some(5050)));
+        assert!(health.is_healthy());
+        assert!(book.is_healthy());
+    }
+
+    #[test]
+    fn test_level_mismatch_is_unhealthy() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Sell, 5100, 100)).unwrap();
+        book.asks.get_mut(&5100).unwrap().total_quantity = 90;
+
+        let health = book.health();
+        assert_eq!(health.mismatched_levels, vec![(Side::Sell, 5100)]);
+        assert!(!health.is_healthy());
+    }
+}
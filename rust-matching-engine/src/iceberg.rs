@@ -0,0 +1,160 @@
+//! Iceberg orders
+//!
+//! An order with a `display_quantity` rests one slice at a time: the book
+//! shows at most that much, and holds the rest in reserve. When a slice is
+//! filled, the next one is cut from the reserve and queued at the back of
+//! the level with a fresh timestamp, so each slice earns its own time
+//! priority like a new order would. A taker still trades a refreshed slice
+//! before moving on to a worse price.
+//!
+//! Depth, level totals and `level_snapshot`'s `total_quantity` count only the
+//! shown slice. `get_order_remaining`, `get_order` and orders leaving the book
+//! report the whole remainder, reserve included; an order resubmitted or
+//! imported from them is sliced afresh.
+//!
+//! How the reserve is cut when it isn't a multiple of the display size is set
+//! per order by [`IcebergSlicing`]. Midpoint orders are hidden already and
+//! are never sliced.
+
+use crate::{BookOrder, OrderType, Quantity, Timestamp};
+
+/// How an iceberg cuts its remainder into slices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IcebergSlicing {
+    /// Every slice shows the full display size, and the last one whatever is
+    /// left: 250 shares showing 100 rest as 100, 100, then 50
+    #[default]
+    Remainder,
+    /// Slices are as even as possible, so none is conspicuously small: the
+    /// remainder is split into as many slices as `Remainder` would need, each
+    /// at most one share apart, larger ones first. 250 shares showing 100 rest
+    /// as 84, 83, then 83
+    EqualSlices,
+}
+
+impl IcebergSlicing {
+    /// Size of the next slice to show out of `remaining`
+    ///
+    /// `display` must be non-zero.
+    pub fn next_slice(self, remaining: Quantity, display: Quantity) -> Quantity {
+        match self {
+            IcebergSlicing::Remainder => remaining.min(display),
+            IcebergSlicing::EqualSlices => {
+                let slices = remaining.div_ceil(display);
+                remaining.div_ceil(slices.max(1))
+            }
+        }
+    }
+}
+
+impl BookOrder {
+    /// Show the first slice of an order about to rest, holding back the rest
+    ///
+    /// An order already showing a slice is cut afresh from its whole remainder.
+    pub(crate) fn slice(&mut self) {
+        if self.order_type == OrderType::MidpointPeg {
+            return;
+        }
+        let Some(display) = self.display_quantity else {
+            return;
+        };
+        let remaining = self.remaining_quantity + self.hidden_quantity;
+        self.remaining_quantity = self.iceberg_slicing.next_slice(remaining, display);
+        self.hidden_quantity = remaining - self.remaining_quantity;
+    }
+
+    /// Show the next slice once the current one is filled
+    ///
+    /// Returns whether there was a reserve to refresh from; the caller moves
+    /// the order to the back of its level.
+    pub(crate) fn refresh_slice(&mut self, now: Timestamp) -> bool {
+        if self.remaining_quantity > 0 || self.hidden_quantity == 0 {
+            return false;
+        }
+        self.slice();
+        self.timestamp = now;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::{Order, OrderBook, OrderId, OrderStatus, Side};
+
+    fn iceberg(id: OrderId, slicing: IcebergSlicing) -> Order {
+        let mut order = order(id, Side::Sell, 5000, 250);
+        order.display_quantity = Some(100);
+        order.iceberg_slicing = slicing;
+        order
+    }
+
+    /// The slices a 250-share iceberg showing 100 displays, taking each in turn
+    fn slices_shown(slicing: IcebergSlicing) -> Vec<Quantity> {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(iceberg(1, slicing)).unwrap();
+
+        let mut shown = Vec::new();
+        while let Some(&(_, quantity)) = book.get_depth(1).1.first() {
+            shown.push(quantity);
+            let taker = order(10 + shown.len() as OrderId, Side::Buy, 5000, quantity);
+            book.process_limit_order(taker).unwrap();
+        }
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Filled));
+        shown
+    }
+
+    #[test]
+    fn test_iceberg_slicing_rules() {
+        assert_eq!(slices_shown(IcebergSlicing::Remainder), vec![100, 100, 50]);
+        assert_eq!(slices_shown(IcebergSlicing::EqualSlices), vec![84, 83, 83]);
+    }
+
+    #[test]
+    fn test_iceberg_refresh_loses_priority_but_not_price() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(iceberg(1, IcebergSlicing::Remainder)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5000, 40)).unwrap();
+        book.process_limit_order(order(3, Side::Sell, 5100, 500)).unwrap();
+        assert_eq!(book.get_depth(1).1, vec![(5000, 140)]);
+        assert_eq!(book.get_order_remaining(1), Some(250));
+
+        // The refreshed slice goes behind order 2, but still trades before 5100
+        let result = book.process_limit_order(order(4, Side::Buy, 5100, 160)).unwrap();
+        let fills: Vec<_> =
+            result.trades.iter().map(|t| (t.maker_order_id, t.price, t.quantity)).collect();
+        assert_eq!(fills, vec![(1, 5000, 100), (2, 5000, 40), (1, 5000, 20)]);
+        assert_eq!(book.get_depth(1).1, vec![(5000, 80)]);
+        assert_eq!(book.get_order_remaining(1), Some(130));
+
+        // Cancelling takes the reserve too
+        book.cancel_order(1).unwrap();
+        assert_eq!(book.total_cancelled_volume, 130);
+        assert_eq!(book.get_depth(1).1, vec![(5100, 500)]);
+    }
+
+    #[test]
+    fn test_iceberg_amend_gives_up_reserve_first() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(iceberg(1, IcebergSlicing::Remainder)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5000, 40)).unwrap();
+
+        // Down to 120: the reserve shrinks to 20, the slice keeps its place
+        book.modify_quantity(1, 120).unwrap();
+        assert_eq!(book.get_order_remaining(1), Some(120));
+        assert_eq!(book.get_depth(1).1, vec![(5000, 140)]);
+        let result = book.process_limit_order(order(3, Side::Buy, 5000, 10)).unwrap();
+        assert_eq!(result.trades[0].maker_order_id, 1);
+
+        // Down to 50: the slice itself shrinks
+        book.modify_quantity(1, 50).unwrap();
+        assert_eq!(book.get_depth(1).1, vec![(5000, 90)]);
+
+        // Up to 300: re-queued and sliced afresh
+        book.modify_quantity(1, 300).unwrap();
+        assert_eq!(book.get_depth(1).1, vec![(5000, 140)]);
+        let result = book.process_limit_order(order(4, Side::Buy, 5000, 10)).unwrap();
+        assert_eq!(result.trades[0].maker_order_id, 2);
+    }
+}
@@ -23,7 +23,9 @@
 //!
 //! - Orders are marked as cancelled in the HashMap (O(1))
 //! - Cancelled orders are skipped during matching iteration
-//! - Empty price levels are cleaned up after matching
+//! - A price level left without live quantity is removed at once, along with
+//!   the cancelled orders still queued there, so every level in the price
+//!   maps is live and the best prices, level counts and depth agree
 //!
 //! **Rationale**: Lazy deletion provides better average-case performance because:
 //! 1. Cancellation is O(1) instead of O(N) for VecDeque removal
@@ -37,9 +39,65 @@
 //! - Prices are in basis points (e.g., $0.65 = 6500 basis points)
 //! - Quantities are whole units (shares)
 
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+pub mod analytics;
+pub mod archive;
+pub mod auction;
+pub mod band;
+pub mod basket;
+pub mod builder;
+pub mod checksum;
+pub mod clock;
+pub mod cross;
+pub mod depth;
+pub mod diff;
+pub mod display;
+pub mod engine;
+pub mod events;
+pub mod fees;
+pub mod health;
+pub mod iceberg;
+pub mod market;
+pub mod matching;
+pub mod midpoint;
+pub mod quote;
+pub mod ratelimit;
+pub mod replay;
+pub mod rounding;
+pub mod snapshot;
+pub mod units;
+pub mod view;
+
+pub use archive::ArchivedOrder;
+pub use auction::AuctionBook;
+pub use basket::submit_basket;
+pub use builder::{OrderBuildError, OrderBuilder};
+pub use clock::{Clock, MockClock, MonotonicClock, SystemClock};
+pub use depth::{DepthDelta, DepthSnapshot, DepthThrottle};
+pub use diff::{BookDiff, LevelDiff};
+pub use events::{Listener, OrderEvent};
+pub use fees::{maker_rebates, FeeSchedule};
+pub use health::BookHealth;
+pub use iceberg::IcebergSlicing;
+pub use market::MarketOrderPolicy;
+pub use analytics::{
+    BookMetrics, FillSizeHistogram, ProbabilityEstimate, QtyBucket, SlippageReport,
+};
+pub use matching::{
+    HybridMatching, LevelAction, MatchingAlgorithm, MatchingMode, PriceTimeMatching,
+    ProRataMatching,
+};
+pub use quote::QuoteResult;
+pub use ratelimit::RateLimit;
+pub use replay::{state_at, Command, LogEntry};
+pub use rounding::RoundingMode;
+pub use snapshot::{BookSnapshot, LevelSnapshot, OrderRecord, Repair, RepairReport};
+pub use view::BookView;
+
 /// Price represented in basis points (1 basis point = 0.0001)
 /// Example: $0.65 = 6500 basis points
 pub type Price = u64;
@@ -56,6 +114,9 @@ pub type TradeId = u64;
 /// Timestamp in microseconds since UNIX epoch
 pub type Timestamp = u64;
 
+/// Depth by side, `(bids, asks)`, each a list of `(price, quantity)` levels best first
+pub type Depth = (Vec<(Price, Quantity)>, Vec<(Price, Quantity)>);
+
 /// Market identifier
 pub type MarketId = String;
 
@@ -65,6 +126,34 @@ pub type OutcomeId = String;
 /// User identifier
 pub type UserId = String;
 
+/// Canonical form of an outcome identifier (ASCII uppercase, e.g. "yes" -> "YES")
+///
+/// Orders and books store outcomes in this form so that clients sending
+/// "YES", "Yes" or "yes" all trade in the same book.
+pub fn canonical_outcome_id(outcome_id: &str) -> OutcomeId {
+    outcome_id.to_ascii_uppercase()
+}
+
+/// Check whether two market/outcome pairs refer to the same book
+///
+/// Market ids are stored as given but compared case-insensitively (e.g. UUIDs
+/// in either case); outcome ids are compared in canonical form.
+///
+/// Every entry path checks this, which is what keeps a YES buy from meeting
+/// a NO buy at complementary prices: that match would mint a share pair, and
+/// the engine has no paired matching or collateral accounting to record it.
+fn same_book(market_a: &str, outcome_a: &str, market_b: &str, outcome_b: &str) -> bool {
+    market_a.eq_ignore_ascii_case(market_b) && outcome_a.eq_ignore_ascii_case(outcome_b)
+}
+
+/// Current time in microseconds since UNIX epoch
+fn current_timestamp() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
 /// Side of the order (Buy or Sell)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Side {
@@ -72,6 +161,27 @@ pub enum Side {
     Sell,
 }
 
+impl Side {
+    /// The other side of the book
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+
+    /// Whether a limit of `limit` on this side reaches an opposite order resting at `resting_price`
+    ///
+    /// Equal prices trade: a buy at 5000 matches an ask at 5000. Every path
+    /// that decides whether two prices meet uses this rule.
+    pub fn reaches(self, limit: Price, resting_price: Price) -> bool {
+        match self {
+            Side::Buy => limit >= resting_price,
+            Side::Sell => limit <= resting_price,
+        }
+    }
+}
+
 impl std::fmt::Display for Side {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -94,8 +204,41 @@ pub enum OrderStatus {
     Cancelled,
 }
 
+/// How an order's trading prices are bounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrderType {
+    /// Trades at its limit price or better, and rests any remainder
+    #[default]
+    Limit,
+    /// Trades at whatever prices the book offers and never rests
+    ///
+    /// Any remainder is cancelled. The submitted `price` is ignored; see
+    /// `OrderBook::process_market_order`.
+    Market,
+    /// Rests hidden at the midpoint of the live best bid and ask, repricing
+    /// as they move
+    ///
+    /// `price` is a limit: the order only trades while the midpoint is at or
+    /// better than it. See the `midpoint` module.
+    MidpointPeg,
+}
+
+/// How long an order's unfilled quantity stays on the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeInForce {
+    /// Rests any remainder until it is filled or cancelled
+    #[default]
+    GoodTilCancelled,
+    /// Fill and kill: trades whatever is available on entry and cancels the
+    /// rest instead of resting it
+    ///
+    /// Unlike fill-or-kill, a partial fill stands. The ack's
+    /// `cancelled_quantity` is the killed shortfall.
+    FillAndKill,
+}
+
 /// A limit order in the order book
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Order {
     /// Unique order identifier
     pub id: OrderId,
@@ -103,7 +246,7 @@ pub struct Order {
     pub user_id: UserId,
     /// Market this order belongs to
     pub market_id: MarketId,
-    /// Outcome this order is for (e.g., "YES" or "NO")
+    /// Outcome this order is for (e.g., "YES" or "NO"), in canonical form
     pub outcome_id: OutcomeId,
     /// Buy or Sell
     pub side: Side,
@@ -117,6 +260,37 @@ pub struct Order {
     pub timestamp: Timestamp,
     /// Current status
     pub status: OrderStatus,
+    /// Last-look threshold while resting: if a taker's limit is more than this
+    /// many basis points through this order's price, the order is cancelled
+    /// instead of filled (None = always fill)
+    pub last_look_bps: Option<Price>,
+    /// As a taker, the most distinct price levels to trade through (None = no
+    /// limit; at least one level is always allowed). A remainder left when the
+    /// limit is hit rests at the price of the last level traded, so it never
+    /// crosses the levels it skipped.
+    pub max_levels: Option<usize>,
+    /// Opaque client reference, echoed in acks, trades, events and `get_order`
+    ///
+    /// It takes no part in matching. It must be unique among the user's live
+    /// orders, which can then be cancelled by it with `cancel_by_client_id`.
+    pub client_order_id: Option<String>,
+    /// Limit or market order
+    pub order_type: OrderType,
+    /// For a market order, how far the fill price may move from the first
+    /// fill's price, in basis points, before matching stops (None = sweep the
+    /// book)
+    pub max_slippage_bps: Option<Price>,
+    /// Most quantity left resting after matching; any excess is cancelled
+    /// and reported in `Ack::cancelled_quantity` (None = rest the whole
+    /// remainder)
+    pub max_rest_quantity: Option<Quantity>,
+    /// Whether a remainder may rest
+    pub time_in_force: TimeInForce,
+    /// Most quantity shown on the book at once, the rest held in reserve
+    /// (None = show it all); see the `iceberg` module
+    pub display_quantity: Option<Quantity>,
+    /// How an iceberg's reserve is cut into slices
+    pub iceberg_slicing: IcebergSlicing,
 }
 
 impl Order {
@@ -130,22 +304,28 @@ impl Order {
         price: Price,
         quantity: Quantity,
     ) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_micros() as u64;
+        let timestamp = current_timestamp();
 
         Self {
             id,
             user_id,
             market_id,
-            outcome_id,
+            outcome_id: canonical_outcome_id(&outcome_id),
             side,
             price,
             original_quantity: quantity,
             remaining_quantity: quantity,
             timestamp,
             status: OrderStatus::Open,
+            last_look_bps: None,
+            max_levels: None,
+            client_order_id: None,
+            order_type: OrderType::Limit,
+            max_slippage_bps: None,
+            max_rest_quantity: None,
+            time_in_force: TimeInForce::GoodTilCancelled,
+            display_quantity: None,
+            iceberg_slicing: IcebergSlicing::Remainder,
         }
     }
 
@@ -164,13 +344,33 @@ impl Order {
             id,
             user_id,
             market_id,
-            outcome_id,
+            outcome_id: canonical_outcome_id(&outcome_id),
             side,
             price,
             original_quantity: quantity,
             remaining_quantity: quantity,
             timestamp,
             status: OrderStatus::Open,
+            last_look_bps: None,
+            max_levels: None,
+            client_order_id: None,
+            order_type: OrderType::Limit,
+            max_slippage_bps: None,
+            max_rest_quantity: None,
+            time_in_force: TimeInForce::GoodTilCancelled,
+            display_quantity: None,
+            iceberg_slicing: IcebergSlicing::Remainder,
+        }
+    }
+
+    /// Whether this resting order's last look rejects a taker with limit `taker_price`
+    ///
+    /// A taker whose limit is far through the maker's quote suggests the quote
+    /// is stale, so the maker declines the fill.
+    pub fn last_look_rejects(&self, taker_price: Price) -> bool {
+        match self.last_look_bps {
+            Some(threshold) => taker_price.abs_diff(self.price) > threshold,
+            None => false,
         }
     }
 
@@ -182,7 +382,7 @@ impl Order {
         }
 
         // Must be same market and outcome
-        if self.market_id != other.market_id || self.outcome_id != other.outcome_id {
+        if !same_book(&self.market_id, &self.outcome_id, &other.market_id, &other.outcome_id) {
             return false;
         }
 
@@ -191,10 +391,112 @@ impl Order {
             return false;
         }
 
-        // Check price compatibility
-        match self.side {
-            Side::Buy => self.price >= other.price, // Buyer willing to pay at least seller's price
-            Side::Sell => self.price <= other.price, // Seller willing to accept at most buyer's price
+        // Check price compatibility (equal prices match)
+        self.side.reaches(self.price, other.price)
+    }
+}
+
+/// A resting order as its book stores it
+///
+/// The same fields as an `Order` less `market_id` and `outcome_id`, which
+/// are the book's own, so resting orders don't each carry copies of them.
+/// Orders leaving the book (`drain`, snapshots) get them back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookOrder {
+    /// Unique order identifier
+    pub id: OrderId,
+    /// User who placed the order
+    pub user_id: UserId,
+    /// Buy or Sell
+    pub side: Side,
+    /// Price in basis points
+    pub price: Price,
+    /// Original quantity
+    pub original_quantity: Quantity,
+    /// Remaining quantity to be filled
+    pub remaining_quantity: Quantity,
+    /// When the order was placed (microseconds since epoch)
+    pub timestamp: Timestamp,
+    /// Current status
+    pub status: OrderStatus,
+    /// See `Order::last_look_bps`
+    pub last_look_bps: Option<Price>,
+    /// See `Order::max_levels`
+    pub max_levels: Option<usize>,
+    /// See `Order::client_order_id`
+    pub client_order_id: Option<String>,
+    /// See `Order::order_type`
+    pub order_type: OrderType,
+    /// See `Order::max_slippage_bps`
+    pub max_slippage_bps: Option<Price>,
+    /// See `Order::max_rest_quantity`
+    pub max_rest_quantity: Option<Quantity>,
+    /// See `Order::time_in_force`
+    pub time_in_force: TimeInForce,
+    /// See `Order::display_quantity`
+    pub display_quantity: Option<Quantity>,
+    /// See `Order::iceberg_slicing`
+    pub iceberg_slicing: IcebergSlicing,
+    /// An iceberg's reserve, not counted in `remaining_quantity` until shown
+    pub hidden_quantity: Quantity,
+}
+
+impl BookOrder {
+    /// See `Order::last_look_rejects`
+    pub fn last_look_rejects(&self, taker_price: Price) -> bool {
+        match self.last_look_bps {
+            Some(threshold) => taker_price.abs_diff(self.price) > threshold,
+            None => false,
+        }
+    }
+
+    /// The public order, in the given book
+    pub(crate) fn to_order(&self, market_id: &str, outcome_id: &str) -> Order {
+        Order {
+            id: self.id,
+            user_id: self.user_id.clone(),
+            market_id: market_id.to_string(),
+            outcome_id: outcome_id.to_string(),
+            side: self.side,
+            price: self.price,
+            original_quantity: self.original_quantity,
+            remaining_quantity: self.remaining_quantity + self.hidden_quantity,
+            timestamp: self.timestamp,
+            status: self.status,
+            last_look_bps: self.last_look_bps,
+            max_levels: self.max_levels,
+            client_order_id: self.client_order_id.clone(),
+            order_type: self.order_type,
+            max_slippage_bps: self.max_slippage_bps,
+            max_rest_quantity: self.max_rest_quantity,
+            time_in_force: self.time_in_force,
+            display_quantity: self.display_quantity,
+            iceberg_slicing: self.iceberg_slicing,
+        }
+    }
+}
+
+impl From<&Order> for BookOrder {
+    fn from(order: &Order) -> Self {
+        Self {
+            id: order.id,
+            user_id: order.user_id.clone(),
+            side: order.side,
+            price: order.price,
+            original_quantity: order.original_quantity,
+            remaining_quantity: order.remaining_quantity,
+            timestamp: order.timestamp,
+            status: order.status,
+            last_look_bps: order.last_look_bps,
+            max_levels: order.max_levels,
+            client_order_id: order.client_order_id.clone(),
+            order_type: order.order_type,
+            max_slippage_bps: order.max_slippage_bps,
+            max_rest_quantity: order.max_rest_quantity,
+            time_in_force: order.time_in_force,
+            display_quantity: order.display_quantity,
+            iceberg_slicing: order.iceberg_slicing,
+            hidden_quantity: 0,
         }
     }
 }
@@ -216,7 +518,7 @@ pub struct Trade {
     pub market_id: MarketId,
     /// Outcome this trade is for
     pub outcome_id: OutcomeId,
-    /// Execution price (maker's price)
+    /// Execution price (see `ExecutionPricePolicy`)
     pub price: Price,
     /// Executed quantity
     pub quantity: Quantity,
@@ -224,24 +526,94 @@ pub struct Trade {
     pub timestamp: Timestamp,
     /// Which side the taker was on
     pub taker_side: Side,
+    /// The taker's `client_order_id`
+    pub taker_client_order_id: Option<String>,
+    /// The maker's `client_order_id`
+    pub maker_client_order_id: Option<String>,
+}
+
+impl Trade {
+    /// This trade from one participant's point of view
+    ///
+    /// Returns None if `user_id` was neither the taker nor the maker.
+    pub fn for_user(&self, user_id: &str) -> Option<UserTradeView> {
+        let (side, is_maker) = if self.taker_user_id == user_id {
+            (self.taker_side, false)
+        } else if self.maker_user_id == user_id {
+            (self.taker_side.opposite(), true)
+        } else {
+            return None;
+        };
+
+        let notional = self.price as i128 * self.quantity as i128;
+        Some(UserTradeView {
+            trade_id: self.id,
+            side,
+            is_maker,
+            price: self.price,
+            quantity: self.quantity,
+            cash_flow: match side {
+                Side::Buy => -notional,
+                Side::Sell => notional,
+            },
+        })
+    }
+}
+
+/// A trade as seen by one of its participants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserTradeView {
+    /// The trade
+    pub trade_id: TradeId,
+    /// Whether the user bought or sold
+    pub side: Side,
+    /// True if the user's order was resting (maker), false if it was the taker
+    pub is_maker: bool,
+    /// Execution price
+    pub price: Price,
+    /// Executed quantity
+    pub quantity: Quantity,
+    /// Cash paid (negative) or received (positive), in basis points x shares
+    pub cash_flow: i128,
 }
 
 /// Metadata for order lookup (used in the HashMap for O(1) access)
 #[derive(Debug, Clone)]
 struct OrderMetadata {
+    /// User who placed the order
+    user_id: UserId,
+    /// Side of the book where this order resides
+    side: Side,
     /// Price level where this order resides
     price: Price,
     /// Current status (for lazy deletion)
     status: OrderStatus,
-    /// Remaining quantity
+    /// Remaining quantity shown on the book
     remaining_quantity: Quantity,
+    /// An iceberg's reserve behind `remaining_quantity`
+    hidden_quantity: Quantity,
+    /// Client reference, for events and `get_order`
+    client_order_id: Option<String>,
+    /// When the order was placed or last lost priority, for `min_resting_micros`
+    timestamp: Timestamp,
+    /// Whether the order rests in the hidden midpoint queue, not at `price`
+    pegged: bool,
+    /// Quantity filled while resting since the order was placed, amends included
+    filled_quantity: Quantity,
+}
+
+impl OrderMetadata {
+    /// Everything the order has left to trade, any iceberg reserve included
+    fn total_remaining(&self) -> Quantity {
+        self.remaining_quantity + self.hidden_quantity
+    }
 }
 
 /// A queue of orders at a specific price level
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 struct PriceLevelQueue {
     /// Orders at this price level in FIFO order
-    orders: VecDeque<Order>,
+    orders: VecDeque<BookOrder>,
     /// Total quantity available at this price level
     total_quantity: Quantity,
 }
@@ -256,45 +628,39 @@ impl PriceLevelQueue {
     }
 
     /// Add an order to the back of the queue
-    fn push_back(&mut self, order: Order) {
+    fn push_back(&mut self, order: BookOrder) {
         self.total_quantity += order.remaining_quantity;
         self.orders.push_back(order);
     }
 
+    /// Move a queued order to the back, leaving the total unchanged
+    fn requeue(&mut self, order_id: OrderId) {
+        if let Some(position) = self.orders.iter().position(|o| o.id == order_id) {
+            if let Some(order) = self.orders.remove(position) {
+                self.orders.push_back(order);
+            }
+        }
+    }
+
     /// Check if the queue is empty
     fn is_empty(&self) -> bool {
         self.orders.is_empty()
     }
 
-    /// Get a mutable reference to the front order
-    fn front_mut(&mut self) -> Option<&mut Order> {
-        self.orders.front_mut()
-    }
-
-    /// Remove the front order
-    fn pop_front(&mut self) -> Option<Order> {
-        if let Some(order) = self.orders.pop_front() {
-            self.total_quantity = self.total_quantity.saturating_sub(order.remaining_quantity);
-            Some(order)
-        } else {
-            None
-        }
-    }
-
     /// Update total quantity after a partial fill
     fn update_quantity(&mut self, filled: Quantity) {
         self.total_quantity = self.total_quantity.saturating_sub(filled);
     }
 
     /// Clean up cancelled orders from the front of the queue
-    /// Returns the number of orders removed
-    fn cleanup_cancelled(&mut self, order_index: &HashMap<OrderId, OrderMetadata>) -> usize {
-        let mut removed = 0;
+    /// Returns the ids of the orders removed
+    fn cleanup_cancelled(&mut self, order_index: &HashMap<OrderId, OrderMetadata>) -> Vec<OrderId> {
+        let mut removed = Vec::new();
         while let Some(front) = self.orders.front() {
             if let Some(metadata) = order_index.get(&front.id) {
                 if metadata.status == OrderStatus::Cancelled {
+                    removed.push(front.id);
                     self.orders.pop_front();
-                    removed += 1;
                     continue;
                 }
             }
@@ -302,10 +668,44 @@ impl PriceLevelQueue {
         }
         removed
     }
+
+    /// Remove cancelled orders queued ahead of `order_id`
+    /// Returns the ids of the orders removed
+    fn cleanup_cancelled_before(
+        &mut self,
+        order_id: OrderId,
+        order_index: &HashMap<OrderId, OrderMetadata>,
+    ) -> Vec<OrderId> {
+        let mut removed = self.cleanup_cancelled(order_index);
+        if self.orders.front().is_none_or(|front| front.id == order_id) {
+            return removed;
+        }
+
+        // Live orders are queued ahead, so cancelled ones may sit between them
+        let Some(position) = self.orders.iter().position(|o| o.id == order_id) else {
+            return removed;
+        };
+        let mut index = 0;
+        self.orders.retain(|o| {
+            let ahead = index < position;
+            index += 1;
+            let cancelled =
+                order_index.get(&o.id).is_some_and(|m| m.status == OrderStatus::Cancelled);
+            if ahead && cancelled {
+                removed.push(o.id);
+            }
+            !(ahead && cancelled)
+        });
+        removed
+    }
 }
 
 /// The Central Limit Order Book
-#[derive(Debug)]
+///
+/// A clone is an independent copy of the book's state, for what-if runs
+/// through the real matching code. It starts with no listeners, so nothing
+/// it does is published, and shares the original's clock.
+#[derive(Debug, Clone)]
 pub struct OrderBook {
     /// Market this order book is for
     pub market_id: MarketId,
@@ -315,13 +715,234 @@ pub struct OrderBook {
     bids: BTreeMap<Price, PriceLevelQueue>,
     /// Sell orders sorted by price (lowest first when iterating)
     asks: BTreeMap<Price, PriceLevelQueue>,
-    /// O(1) lookup for all orders (active and cancelled)
+    /// O(1) lookup for all orders (active and cancelled), less any archived
     order_index: HashMap<OrderId, OrderMetadata>,
+    /// Finished orders moved out of the index (see the `archive` module)
+    archive: archive::OrderArchive,
     /// Next trade ID
     next_trade_id: TradeId,
+    /// Lowest id above every accepted order, for ids the book assigns itself
+    next_order_id: OrderId,
+    /// Resting two-sided quote (bid id, ask id) per user
+    quotes: HashMap<UserId, (OrderId, OrderId)>,
+    /// Behavioural configuration
+    config: OrderBookConfig,
+    /// How a taker's quantity is allocated within a level
+    matcher: Arc<dyn MatchingAlgorithm>,
+    /// The most recent trades, bounded by `config.trade_history_capacity`
+    trade_history: VecDeque<Trade>,
+    /// Last heartbeat per user, for cancel-on-disconnect
+    heartbeats: HashMap<UserId, Timestamp>,
+    /// Live (open or partially filled) orders per user
+    user_orders: HashMap<UserId, HashSet<OrderId>>,
+    /// Live orders per user by client order id
+    client_orders: HashMap<UserId, HashMap<String, OrderId>>,
+    /// Trade size distribution, if enabled
+    fill_histogram: Option<FillSizeHistogram>,
+    /// Recent trade prices for `realized_volatility` (None = not collected)
+    price_window: Option<analytics::PriceWindow>,
+    /// Trade price changes for `twap` (None = not collected)
+    price_history: Option<analytics::PriceHistory>,
+    /// Traded volume per unordered pair of users (None = not collected)
+    counterparty_volumes: Option<HashMap<(UserId, UserId), Quantity>>,
+    /// Number of open or partially filled orders
+    live_orders: usize,
+    /// Number of cancelled orders still queued at a level (lazy deletion)
+    queued_cancelled: usize,
+    /// Price of the most recent trade
+    last_trade_price: Option<Price>,
+    /// Cancel and fill counts per user, for surveillance
+    user_activity: HashMap<UserId, UserActivity>,
+    /// Sequence number of the last accepted order
+    sequence: u64,
+    /// Source of trade and amend timestamps
+    clock: Arc<dyn Clock>,
+    /// Callbacks receiving `OrderEvent`s
+    listeners: events::Listeners,
+    /// Top levels as last published, for throttled depth snapshots
+    depth_feed: depth::DepthFeed,
+    /// Resting midpoint-pegged orders, hidden from the levels
+    midpoint_orders: midpoint::MidpointOrders,
+    /// Recent submissions per user (None = no rate limit)
+    rate_limiter: Option<ratelimit::RateLimiter>,
+    /// Whether new orders are accepted
+    state: MarketState,
     /// Statistics
     pub total_trades: u64,
     pub total_volume: Quantity,
+    /// Sum of `price * quantity` over all trades, in bps x shares; saturates
+    /// at `u128::MAX` rather than wrapping
+    pub total_notional: u128,
+    pub total_cancelled_orders: u64,
+    pub total_cancelled_volume: Quantity,
+}
+
+/// Running cancel and fill counts for one user
+#[derive(Debug, Clone, Copy, Default)]
+struct UserActivity {
+    /// Orders cancelled
+    cancels: u64,
+    /// Trades the user took part in, on either side
+    fills: u64,
+}
+
+/// What to do when a resting order would lock the book (best bid == best ask)
+///
+/// An incoming order at exactly the opposite best price trades rather than
+/// rests (see `Side::reaches`), so only amends, which never match, can move
+/// an order onto the opposite best price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockedBookPolicy {
+    /// Allow the order to rest; `spread()` returns None while locked
+    #[default]
+    Allow,
+    /// Reject the order with `OrderBookError::WouldLock`
+    Reject,
+}
+
+/// What to do when an incoming order would match the same user's resting order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelfTradePolicy {
+    /// Cancel the user's resting order and keep matching past it
+    #[default]
+    CancelResting,
+    /// Reject the incoming order with `OrderBookError::WouldSelfCross`
+    ///
+    /// Checked before matching: the order is rejected if any of the user's
+    /// live resting orders on the opposite side is within its limit price.
+    RejectTaker,
+    /// Net the overlap: reduce both orders by the smaller remaining size
+    /// without printing a trade, then keep matching the taker's remainder
+    ///
+    /// A resting order netted to zero is cancelled. A taker netted to zero
+    /// ends up Cancelled unless it traded its full size.
+    DecrementAndCancel,
+}
+
+/// What to do with a limit price that is not a multiple of the tick size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TickPolicy {
+    /// Reject the order with `OrderBookError::InvalidTick`
+    #[default]
+    Reject,
+    /// Move the price to the nearest tick (rounded with the book's
+    /// `rounding_mode`, and never below one tick) before matching
+    SnapToNearest,
+}
+
+/// Whether the book accepts new orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarketState {
+    /// Orders are accepted and matched
+    #[default]
+    Open,
+    /// New orders, quotes and direct matches are rejected with
+    /// `OrderBookError::MarketHalted`; cancels still go through
+    Halted,
+}
+
+/// What to do with the timestamp an incoming order was submitted with
+///
+/// Order timestamps drive age-based features such as `min_resting_micros`,
+/// so a client that backdates its orders could get around them. Replays stay
+/// deterministic under every policy, as their clock is set from the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampPolicy {
+    /// Keep the client's timestamp
+    #[default]
+    Trust,
+    /// Replace it with the book's clock reading on arrival
+    Override,
+    /// Reject the order with `OrderBookError::TimestampOutOfWindow` if its
+    /// timestamp is more than `tolerance_micros` away from the clock, either way
+    Reject { tolerance_micros: Timestamp },
+}
+
+/// Which limit price a trade executes at when the taker's limit crosses the maker's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionPricePolicy {
+    /// Execute at the resting order's price; a taker whose limit is better
+    /// than needed gets the improvement (a buy at 5200 against an ask at 5000
+    /// pays 5000)
+    #[default]
+    TakerImprovement,
+    /// Execute at the taker's limit price; the resting order gets the
+    /// improvement (a buy at 5200 against an ask at 5000 pays 5200)
+    ///
+    /// Market orders have no limit and always execute at the resting price.
+    TakerPrice,
+}
+
+/// Configuration for an order book
+#[derive(Debug, Clone, Default)]
+pub struct OrderBookConfig {
+    /// Handling of orders that would rest at the opposite side's best price
+    pub locked_book_policy: LockedBookPolicy,
+    /// Handling of incoming orders that cross the same user's resting orders
+    pub self_trade_policy: SelfTradePolicy,
+    /// Whether trades print at the maker's or the taker's price
+    pub execution_price_policy: ExecutionPricePolicy,
+    /// Handling of the part of a market order the book can't fill
+    pub market_order_policy: MarketOrderPolicy,
+    /// Rounding applied wherever a derived value falls between basis points
+    pub rounding_mode: RoundingMode,
+    /// Number of recent trades retained for `recent_trades` (0 = disabled)
+    pub trade_history_capacity: usize,
+    /// How quantity is allocated among orders at the same price level
+    pub matching_mode: MatchingMode,
+    /// Custom matching algorithm, overriding `matching_mode` (None = use the mode)
+    pub matching_algorithm: Option<Arc<dyn MatchingAlgorithm>>,
+    /// Bucket boundaries for `fill_size_histogram` (None = not collected)
+    pub fill_size_buckets: Option<Vec<Quantity>>,
+    /// Number of recent trade prices retained for `realized_volatility` (None = not collected)
+    pub volatility_window: Option<usize>,
+    /// How far back, in microseconds, trade prices are kept for `twap` (None = not collected)
+    pub twap_horizon: Option<Timestamp>,
+    /// Accumulate traded volume per pair of users for `counterparty_volume`
+    /// (false = not collected)
+    pub track_counterparties: bool,
+    /// Finished orders retained in the archive once they leave the index
+    /// (None = they stay in the index; 0 = discarded)
+    pub order_archive_capacity: Option<usize>,
+    /// Report the `_displayed` views in complementary prices (10000 - price),
+    /// e.g. a NO book in YES terms; matching is unaffected
+    pub invert_display: bool,
+    /// Order ids this book accepts (None = any id)
+    ///
+    /// Giving each market a disjoint range keeps ids globally unique without
+    /// a shared allocator.
+    pub id_range: Option<Range<OrderId>>,
+    /// Time source for trades and amends (None = system clock, made monotonic)
+    pub clock: Option<Arc<dyn Clock>>,
+    /// Largest `price * quantity` (bps x shares) an order may carry (None = unlimited)
+    pub max_notional: Option<u128>,
+    /// Coalesced depth snapshot publishing (None = disabled)
+    pub depth_throttle: Option<DepthThrottle>,
+    /// Most orders a user may have accepted per sliding window (None = unlimited)
+    pub rate_limit: Option<RateLimit>,
+    /// After an order trades, cancel resting orders more than this many bps
+    /// from the last trade price (None = no band; see the `band` module)
+    pub price_band_bps: Option<Price>,
+    /// Most levels per side a depth query returns (None = unlimited)
+    ///
+    /// Larger requests to `get_depth`, `get_depth_displayed` and the depth
+    /// checksums are clamped to it, so untrusted callers can't ask for the
+    /// whole book. Snapshots and `frozen` views still copy every level.
+    pub max_query_levels: Option<usize>,
+    /// Follow each trade event with a `MakerFill` event for the maker (false = trades only)
+    pub maker_fill_events: bool,
+    /// Price increment limit prices must be a multiple of (None = any price)
+    pub tick_size: Option<Price>,
+    /// Handling of limit prices between ticks
+    pub tick_policy: TickPolicy,
+    /// How long an order must rest, from its timestamp, before `cancel_order`
+    /// accepts it (0 = no minimum)
+    ///
+    /// Cancels the book makes itself, such as self-trade prevention, last
+    /// look, cancel-on-disconnect and quote replacement, are not held back.
+    pub min_resting_micros: Timestamp,
+    /// Handling of incoming order timestamps, checked against `clock`
+    pub timestamp_policy: TimestampPolicy,
 }
 
 /// Error types for order book operations
@@ -341,6 +962,51 @@ pub enum OrderBookError {
     InvalidQuantity,
     /// Market/outcome mismatch
     MarketMismatch,
+    /// Resting the order would lock the book (bid == ask)
+    WouldLock,
+    /// Resting the order would cross the opposite side of the book
+    WouldCross,
+    /// Both orders are on the same side of the book
+    SameSide,
+    /// The orders' limit prices do not overlap
+    IncompatiblePrice,
+    /// Both orders belong to the same user
+    SelfTrade,
+    /// Order ID falls outside the book's reserved id range
+    OrderIdOutOfRange(OrderId),
+    /// Order would match a resting order from the same user
+    WouldSelfCross,
+    /// Order's price * quantity exceeds the configured maximum notional
+    NotionalExceeded,
+    /// The user already has a live order with this client order id
+    DuplicateClientOrderId(String),
+    /// A market order found no resting orders to trade with
+    NoLiquidity,
+    /// Price is not a multiple of the configured tick size
+    InvalidTick,
+    /// The order hasn't rested for `min_resting_micros` yet
+    TooSoonToCancel { remaining_micros: Timestamp },
+    /// A midpoint-pegged order can't be amended, refreshed or matched directly
+    PeggedOrder(OrderId),
+    /// The order's timestamp is outside the `TimestampPolicy::Reject` tolerance
+    TimestampOutOfWindow { timestamp: Timestamp, now: Timestamp },
+    /// The market is halted and accepts no new orders
+    MarketHalted,
+    /// A snapshot needs repairs to load (see `from_snapshot_repaired`)
+    InconsistentSnapshot,
+    /// The price is outside this order's limit
+    OutsideLimit(OrderId),
+    /// The quantity is more than this order has left
+    ExceedsRemaining(OrderId),
+    /// This order is on the other side from the one expected
+    WrongSide(OrderId),
+    /// The user is over `rate_limit`; a submission after the wait would be accepted
+    RateLimited { retry_after_micros: Timestamp },
+    /// The book can't fill this market order completely
+    /// (`MarketOrderPolicy::RejectIfIncomplete`)
+    MarketOrderIncomplete,
+    /// A basket has more than one leg for the same book
+    DuplicateBasketBook,
 }
 
 impl std::fmt::Display for OrderBookError {
@@ -353,33 +1019,153 @@ impl std::fmt::Display for OrderBookError {
             Self::InvalidPrice => write!(f, "Invalid price (must be > 0)"),
             Self::InvalidQuantity => write!(f, "Invalid quantity (must be > 0)"),
             Self::MarketMismatch => write!(f, "Market or outcome mismatch"),
+            Self::WouldLock => write!(f, "Order would lock the book"),
+            Self::WouldCross => write!(f, "Order would cross the book"),
+            Self::SameSide => write!(f, "Orders are on the same side"),
+            Self::IncompatiblePrice => write!(f, "Order prices are not compatible"),
+            Self::SelfTrade => write!(f, "Orders belong to the same user"),
+            Self::OrderIdOutOfRange(id) => write!(f, "Order ID out of range: {}", id),
+            Self::WouldSelfCross => write!(f, "Order would match the same user's resting order"),
+            Self::NotionalExceeded => write!(f, "Order notional exceeds the configured maximum"),
+            Self::DuplicateClientOrderId(id) => write!(f, "Duplicate client order ID: {}", id),
+            Self::NoLiquidity => write!(f, "No resting orders to trade with"),
+            Self::InvalidTick => write!(f, "Price is not a multiple of the tick size"),
+            Self::TooSoonToCancel { remaining_micros } => {
+                write!(f, "Too soon to cancel: {} microseconds remaining", remaining_micros)
+            }
+            Self::PeggedOrder(id) => write!(f, "Order is pegged to the midpoint: {}", id),
+            Self::TimestampOutOfWindow { timestamp, now } => {
+                write!(f, "Order timestamp {} too far from the clock ({})", timestamp, now)
+            }
+            Self::MarketHalted => write!(f, "Market is halted"),
+            Self::InconsistentSnapshot => write!(f, "Snapshot is inconsistent"),
+            Self::OutsideLimit(id) => write!(f, "Price outside the limit of order {}", id),
+            Self::ExceedsRemaining(id) => write!(f, "Quantity exceeds remaining of order {}", id),
+            Self::WrongSide(id) => write!(f, "Order {} is on the wrong side", id),
+            Self::RateLimited { retry_after_micros } => {
+                write!(f, "Rate limited, retry after {}us", retry_after_micros)
+            }
+            Self::MarketOrderIncomplete => write!(f, "Market order cannot be filled completely"),
+            Self::DuplicateBasketBook => write!(f, "Basket has more than one leg for a book"),
         }
     }
 }
 
 impl std::error::Error for OrderBookError {}
 
+/// Where the match loop delivers trades as they execute
+struct TradeSink<'a> {
+    deliver: &'a mut dyn FnMut(Trade),
+    /// Trades delivered so far
+    count: usize,
+    /// Quantity traded so far
+    quantity: Quantity,
+}
+
+impl<'a> TradeSink<'a> {
+    fn new(deliver: &'a mut dyn FnMut(Trade)) -> Self {
+        Self { deliver, count: 0, quantity: 0 }
+    }
+}
+
 /// Result of processing an order
 #[derive(Debug)]
 pub struct ProcessOrderResult {
-    /// Trades that were executed
+    /// Trades that were executed, in execution order
+    ///
+    /// This order is part of the API: fills against midpoint orders come
+    /// first, then the lit levels best maker price first, and within a level
+    /// the `MatchingMode`'s order, which for `PriceTime` is queue (FIFO) order.
+    /// Trade ids increase strictly through the list, and streamed trades
+    /// arrive in the same order.
     pub trades: Vec<Trade>,
     /// The order after processing (may be fully filled, partially filled, or open)
     pub order: Order,
+    /// Summary of how the book accepted the order
+    pub ack: Ack,
+}
+
+/// Acknowledgement of an accepted order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ack {
+    /// The accepted order
+    pub order_id: OrderId,
+    /// The order's price once accepted, after any tick snapping
+    pub price: Price,
+    /// Whether a remainder now rests on the book
+    pub resting: bool,
+    /// Book-wide sequence number of the acceptance, starting at 1
+    pub sequence: u64,
+    /// Live orders ahead of the remainder at its price level (None if not resting)
+    pub queue_position: Option<usize>,
+    /// Quantity traded on entry
+    pub filled_quantity: Quantity,
+    /// Quantity cancelled on entry instead of resting: a market or
+    /// fill-and-kill order's remainder, or the excess over `max_rest_quantity`
+    pub cancelled_quantity: Quantity,
+    /// Whether the opposite side held no live orders, lit or midpoint, when
+    /// the order arrived
+    ///
+    /// Tells an order killed for want of liquidity from one that found the
+    /// book out of reach. A market order meeting an empty side is rejected
+    /// with `NoLiquidity` instead.
+    pub no_liquidity: bool,
+    /// Resting orders the price band cancelled after this order traded
+    pub band_cancelled: Vec<OrderId>,
+    /// The order's `client_order_id`
+    pub client_order_id: Option<String>,
 }
 
 impl OrderBook {
     /// Create a new order book for a specific market and outcome
     pub fn new(market_id: MarketId, outcome_id: OutcomeId) -> Self {
+        Self::with_config(market_id, outcome_id, OrderBookConfig::default())
+    }
+
+    /// Create a new order book with explicit configuration
+    pub fn with_config(market_id: MarketId, outcome_id: OutcomeId, config: OrderBookConfig) -> Self {
         Self {
             market_id,
-            outcome_id,
+            outcome_id: canonical_outcome_id(&outcome_id),
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
             order_index: HashMap::new(),
+            archive: archive::OrderArchive::new(config.order_archive_capacity),
             next_trade_id: 1,
+            next_order_id: config.id_range.as_ref().map_or(1, |range| range.start),
+            quotes: HashMap::new(),
+            trade_history: VecDeque::with_capacity(config.trade_history_capacity),
+            heartbeats: HashMap::new(),
+            user_orders: HashMap::new(),
+            client_orders: HashMap::new(),
+            fill_histogram: config.fill_size_buckets.clone().map(FillSizeHistogram::new),
+            price_window: config.volatility_window.map(analytics::PriceWindow::new),
+            price_history: config.twap_horizon.map(analytics::PriceHistory::new),
+            counterparty_volumes: config.track_counterparties.then(HashMap::new),
+            live_orders: 0,
+            queued_cancelled: 0,
+            last_trade_price: None,
+            user_activity: HashMap::new(),
+            sequence: 0,
+            clock: config
+                .clock
+                .clone()
+                .unwrap_or_else(|| Arc::new(MonotonicClock::new(SystemClock))),
+            listeners: events::Listeners::default(),
+            depth_feed: depth::DepthFeed::default(),
+            midpoint_orders: midpoint::MidpointOrders::default(),
+            rate_limiter: config.rate_limit.map(ratelimit::RateLimiter::new),
+            state: MarketState::Open,
+            matcher: config
+                .matching_algorithm
+                .clone()
+                .unwrap_or_else(|| config.matching_mode.algorithm()),
+            config,
             total_trades: 0,
             total_volume: 0,
+            total_notional: 0,
+            total_cancelled_orders: 0,
+            total_cancelled_volume: 0,
         }
     }
 
@@ -393,6 +1179,19 @@ impl OrderBook {
         self.asks.keys().next().copied()
     }
 
+    /// The front live order at the best bid, the one setting the market
+    ///
+    /// Cancelled orders are skipped, so while the best level holds only
+    /// cancelled orders this is the front of the next live level.
+    pub fn best_bid_order(&self) -> Option<OrderId> {
+        self.next_maker(Side::Buy, None)
+    }
+
+    /// The front live order at the best ask, as `best_bid_order`
+    pub fn best_ask_order(&self) -> Option<OrderId> {
+        self.next_maker(Side::Sell, None)
+    }
+
     /// Get the spread between best bid and best ask
     pub fn spread(&self) -> Option<Price> {
         match (self.best_bid(), self.best_ask()) {
@@ -417,6 +1216,27 @@ impl OrderBook {
             .unwrap_or(0)
     }
 
+    /// Fraction of the orders queued at a level that are cancelled but not
+    /// yet removed by lazy deletion (None if there is no such level)
+    ///
+    /// A high ratio marks a level worth `cleanup_cancelled_order`.
+    ///
+    /// # Time Complexity
+    /// O(N) where N is the number of orders queued at the level
+    pub fn level_cancel_ratio(&self, side: Side, price: Price) -> Option<f64> {
+        let level = self.book(side).get(&price).filter(|level| !level.orders.is_empty())?;
+        let cancelled = level
+            .orders
+            .iter()
+            .filter(|order| {
+                self.order_index
+                    .get(&order.id)
+                    .is_some_and(|m| m.status == OrderStatus::Cancelled)
+            })
+            .count();
+        Some(cancelled as f64 / level.orders.len() as f64)
+    }
+
     /// Get the number of price levels on the bid side
     pub fn bid_levels(&self) -> usize {
         self.bids.len()
@@ -429,10 +1249,7 @@ impl OrderBook {
 
     /// Get the total number of active orders
     pub fn active_orders(&self) -> usize {
-        self.order_index
-            .values()
-            .filter(|m| m.status == OrderStatus::Open || m.status == OrderStatus::PartiallyFilled)
-            .count()
+        self.live_orders
     }
 
     /// Process a limit order: match against existing orders, then add remainder to book
@@ -441,345 +1258,899 @@ impl OrderBook {
     /// - Best case (no match): O(log P) for BTreeMap insertion
     /// - Average case: O(log P + M) where M is number of matched orders
     /// - Worst case: O(log P + N) where N is total orders on opposite side
-    pub fn process_limit_order(&mut self, mut order: Order) -> Result<ProcessOrderResult, OrderBookError> {
-        // Validate order
+    pub fn process_limit_order(&mut self, order: Order) -> Result<ProcessOrderResult, OrderBookError> {
+        let mut trades = Vec::new();
+        let (order, ack) = self.submit(order, &mut |trade| trades.push(trade))?;
+        Ok(ProcessOrderResult { trades, order, ack })
+    }
+
+    /// Process a limit order like `process_limit_order`, handing each trade to
+    /// `on_trade` as it executes instead of collecting them
+    ///
+    /// The trades are the same, in the same order; only the final order and
+    /// its ack are returned. `on_trade` runs mid-match, before the book has
+    /// settled, so it should hand the trade off rather than do slow work.
+    pub fn process_limit_order_streaming(
+        &mut self,
+        order: Order,
+        mut on_trade: impl FnMut(&Trade),
+    ) -> Result<(Order, Ack), OrderBookError> {
+        self.submit(order, &mut |trade| on_trade(&trade))
+    }
+
+    /// Process an order, reporting a rejection to listeners
+    fn submit(
+        &mut self,
+        order: Order,
+        on_trade: &mut dyn FnMut(Trade),
+    ) -> Result<(Order, Ack), OrderBookError> {
+        let order_id = order.id;
+        let client_order_id = order.client_order_id.clone();
+        let result = self.execute_limit_order(order, &mut TradeSink::new(on_trade));
+        if let Err(error) = &result {
+            let code = error.code();
+            self.listeners.emit(OrderEvent::Rejected { order_id, client_order_id, code });
+        }
+        self.poll_depth_updates();
+        result
+    }
+
+    /// Process a market order: trade against the opposite side, then cancel any remainder
+    ///
+    /// Sets `order_type` to `Market`. The order may trade through every level
+    /// unless `max_slippage_bps` is set, in which case matching stops before
+    /// any level more than that many basis points worse than the first fill's
+    /// price. Trades execute at the resting orders' prices. In the result,
+    /// `price` is the worst price the order was allowed to trade at.
+    ///
+    /// Returns `NoLiquidity` if the opposite side is empty.
+    pub fn process_market_order(
+        &mut self,
+        mut order: Order,
+    ) -> Result<ProcessOrderResult, OrderBookError> {
+        order.order_type = OrderType::Market;
+        self.process_limit_order(order)
+    }
+
+    fn execute_limit_order(
+        &mut self,
+        mut order: Order,
+        trades: &mut TradeSink<'_>,
+    ) -> Result<(Order, Ack), OrderBookError> {
+        self.prepare_order(&mut order)?;
+        self.record_submission(&order.user_id);
+
+        self.sequence += 1;
+        self.next_order_id = self.next_order_id.max(order.id.saturating_add(1));
+        let opposite = order.side.opposite();
+        let no_liquidity =
+            self.best_live_price(opposite).is_none() && self.midpoint_quantity(opposite) == 0;
+
+        // Match against opposite side
+        self.match_order(&mut order, trades);
+        let band_cancelled =
+            if trades.count > 0 { self.enforce_price_band() } else { Vec::new() };
+
+        // Add remainder to book if not fully filled, less any excess over
+        // what the order may rest
+        let filled_quantity = trades.quantity;
+        let rest_limit = match (order.order_type, order.time_in_force) {
+            (OrderType::Market, _) | (_, TimeInForce::FillAndKill) => 0,
+            (OrderType::Limit | OrderType::MidpointPeg, TimeInForce::GoodTilCancelled) => {
+                order.max_rest_quantity.unwrap_or(Quantity::MAX)
+            }
+        };
+        let cancelled_quantity = order.remaining_quantity.saturating_sub(rest_limit);
+        let mut queue_position = None;
+        if cancelled_quantity > 0 && cancelled_quantity == order.remaining_quantity {
+            order.status = OrderStatus::Cancelled;
+        } else if order.remaining_quantity > 0 {
+            // Only the remainder shrinks; `original_quantity` stays what was sent
+            order.remaining_quantity -= cancelled_quantity;
+            self.add_to_book(BookOrder::from(&order));
+            queue_position = Some(if order.order_type == OrderType::MidpointPeg {
+                self.midpoint_orders.ahead(order.side, order.id)
+            } else {
+                self.live_orders_ahead(order.side, order.price, order.id)
+            });
+        }
+
+        let ack = Ack {
+            order_id: order.id,
+            price: order.price,
+            resting: queue_position.is_some(),
+            sequence: self.sequence,
+            queue_position,
+            filled_quantity,
+            cancelled_quantity,
+            no_liquidity,
+            band_cancelled,
+            client_order_id: order.client_order_id.clone(),
+        };
+        Ok((order, ack))
+    }
+
+    /// Reject new orders while the market is halted
+    fn check_open(&self) -> Result<(), OrderBookError> {
+        match self.state {
+            MarketState::Open => Ok(()),
+            MarketState::Halted => Err(OrderBookError::MarketHalted),
+        }
+    }
+
+    /// Run every check `process_limit_order` makes, without submitting the order
+    ///
+    /// Returns the error `process_limit_order` would return, checked in the
+    /// same order, and changes nothing: no rejection event, no sequence
+    /// number. An order that passes may still be rejected if the book changes
+    /// before it is submitted.
+    pub fn validate_order(&self, order: &Order) -> Result<(), OrderBookError> {
+        self.prepare_order(&mut order.clone())
+    }
+
+    /// Validate an incoming order and fix its price and timestamp for matching
+    ///
+    /// A market order's price becomes the worst opposite price, a limit price
+    /// is snapped per `TickPolicy`, and the timestamp is set per
+    /// `TimestampPolicy`.
+    fn prepare_order(&self, order: &mut Order) -> Result<(), OrderBookError> {
+        self.check_open()?;
+        self.check_rate(&order.user_id)?;
+        if order.order_type == OrderType::Market {
+            // Until the first fill sets the slippage limit, the whole side is in reach
+            let worst = self.live_levels(order.side.opposite()).last();
+            order.price = worst.ok_or(OrderBookError::NoLiquidity)?.0;
+        } else if order.order_type == OrderType::Limit {
+            order.price = self.check_tick(order.price)?;
+        }
+        order.timestamp = self.check_timestamp(order.timestamp)?;
+        self.check_order(order)?;
+        if self.config.self_trade_policy == SelfTradePolicy::RejectTaker
+            && order.order_type != OrderType::MidpointPeg
+            && self.crosses_own_order(order, &[])
+        {
+            return Err(OrderBookError::WouldSelfCross);
+        }
+        if order.order_type == OrderType::Market
+            && self.config.market_order_policy == MarketOrderPolicy::RejectIfIncomplete
+            && !self.fills_completely(order)
+        {
+            return Err(OrderBookError::MarketOrderIncomplete);
+        }
+        Ok(())
+    }
+
+    /// Check an incoming order against everything but the resting orders
+    fn check_order(&self, order: &Order) -> Result<(), OrderBookError> {
         if order.price == 0 {
             return Err(OrderBookError::InvalidPrice);
         }
-        if order.remaining_quantity == 0 {
+        if order.remaining_quantity == 0 || order.display_quantity == Some(0) {
             return Err(OrderBookError::InvalidQuantity);
         }
-        if order.market_id != self.market_id || order.outcome_id != self.outcome_id {
+        self.check_notional(order.price, order.remaining_quantity)?;
+        if !same_book(&order.market_id, &order.outcome_id, &self.market_id, &self.outcome_id) {
             return Err(OrderBookError::MarketMismatch);
         }
-        if self.order_index.contains_key(&order.id) {
+        if !self.id_in_range(order.id) {
+            return Err(OrderBookError::OrderIdOutOfRange(order.id));
+        }
+        if self.order_index.contains_key(&order.id) || self.archive.get(order.id).is_some() {
             return Err(OrderBookError::DuplicateOrderId(order.id));
         }
-
-        let mut trades = Vec::new();
-
-        // Match against opposite side
-        match order.side {
-            Side::Buy => {
-                self.match_buy_order(&mut order, &mut trades);
+        if let Some(client_order_id) = &order.client_order_id {
+            if self.live_client_order(&order.user_id, client_order_id).is_some() {
+                return Err(OrderBookError::DuplicateClientOrderId(client_order_id.clone()));
             }
-            Side::Sell => {
-                self.match_sell_order(&mut order, &mut trades);
-            }
-        }
-
-        // Add remainder to book if not fully filled
-        if order.remaining_quantity > 0 {
-            self.add_to_book(order.clone());
         }
-
-        // Update statistics
-        self.total_trades += trades.len() as u64;
-        self.total_volume += trades.iter().map(|t| t.quantity).sum::<u64>();
-
-        Ok(ProcessOrderResult { trades, order })
+        Ok(())
     }
 
-    /// Match a buy order against asks (lowest ask first)
-    fn match_buy_order(&mut self, order: &mut Order, trades: &mut Vec<Trade>) {
-        // Get price levels to match (lowest ask first)
-        let price_levels: Vec<Price> = self
-            .asks
-            .keys()
-            .filter(|&&ask_price| ask_price <= order.price)
-            .copied()
-            .collect();
+    /// Number of live orders queued ahead of `order_id` at its price level
+    fn live_orders_ahead(&self, side: Side, price: Price, order_id: OrderId) -> usize {
+        let Some(level) = self.book(side).get(&price) else {
+            return 0;
+        };
+        level
+            .orders
+            .iter()
+            .take_while(|o| o.id != order_id)
+            .filter(|o| {
+                self.order_index
+                    .get(&o.id)
+                    .is_some_and(|m| m.status != OrderStatus::Cancelled)
+            })
+            .count()
+    }
 
-        for ask_price in price_levels {
-            if order.remaining_quantity == 0 {
-                break;
+    /// Apply the configured `TickPolicy` to a limit price
+    ///
+    /// Returns the price to use: unchanged if it is on the tick grid.
+    fn check_tick(&self, price: Price) -> Result<Price, OrderBookError> {
+        let Some(tick) = self.config.tick_size.filter(|&tick| tick > 0) else {
+            return Ok(price);
+        };
+        if price.is_multiple_of(tick) {
+            return Ok(price);
+        }
+        match self.config.tick_policy {
+            TickPolicy::Reject => Err(OrderBookError::InvalidTick),
+            TickPolicy::SnapToNearest => {
+                let ticks = self.config.rounding_mode.div(price as u128, tick as u128) as Price;
+                Ok(ticks.max(1) * tick)
             }
+        }
+    }
 
-            // Match against orders at this price level
-            loop {
-                if order.remaining_quantity == 0 {
-                    break;
+    /// Apply the configured `TimestampPolicy` to an incoming order's timestamp
+    ///
+    /// Returns the timestamp to use.
+    fn check_timestamp(&self, timestamp: Timestamp) -> Result<Timestamp, OrderBookError> {
+        match self.config.timestamp_policy {
+            TimestampPolicy::Trust => Ok(timestamp),
+            TimestampPolicy::Override => Ok(self.clock.now()),
+            TimestampPolicy::Reject { tolerance_micros } => {
+                let now = self.clock.now();
+                if timestamp.abs_diff(now) > tolerance_micros {
+                    return Err(OrderBookError::TimestampOutOfWindow { timestamp, now });
                 }
+                Ok(timestamp)
+            }
+        }
+    }
 
-                // Get level and check front order
-                let level = match self.asks.get_mut(&ask_price) {
-                    Some(l) => l,
-                    None => break,
-                };
-
-                // Clean up cancelled orders at the front
-                level.cleanup_cancelled(&self.order_index);
-
-                // Extract maker data to avoid borrow conflicts
-                let maker_data = match level.front_mut() {
-                    Some(maker) => {
-                        // Check if cancelled
-                        if let Some(metadata) = self.order_index.get(&maker.id) {
-                            if metadata.status == OrderStatus::Cancelled {
-                                level.pop_front();
-                                continue;
-                            }
-                        }
-                        // Prevent self-trading
-                        if maker.user_id == order.user_id {
-                            break;
-                        }
-                        // Extract data needed for trade
-                        Some((
-                            maker.id,
-                            maker.user_id.clone(),
-                            maker.market_id.clone(),
-                            maker.outcome_id.clone(),
-                            maker.price,
-                            maker.remaining_quantity,
-                        ))
-                    }
-                    None => None,
-                };
+    /// Check an order's size against the configured `max_notional`
+    ///
+    /// Computed in u128, so it cannot overflow.
+    fn check_notional(&self, price: Price, quantity: Quantity) -> Result<(), OrderBookError> {
+        match self.config.max_notional {
+            Some(max) if price as u128 * quantity as u128 > max => {
+                Err(OrderBookError::NotionalExceeded)
+            }
+            _ => Ok(()),
+        }
+    }
 
-                let (maker_id, maker_user_id, market_id, outcome_id, maker_price, maker_remaining) =
-                    match maker_data {
-                        Some(data) => data,
-                        None => break,
-                    };
-
-                // Calculate fill quantity
-                let fill_quantity = order.remaining_quantity.min(maker_remaining);
-
-                // Create trade
-                let trade_id = self.next_trade_id;
-                self.next_trade_id += 1;
-
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_micros() as u64;
-
-                let trade = Trade {
-                    id: trade_id,
-                    taker_order_id: order.id,
-                    maker_order_id: maker_id,
-                    taker_user_id: order.user_id.clone(),
-                    maker_user_id,
-                    market_id,
-                    outcome_id,
-                    price: maker_price,
-                    quantity: fill_quantity,
-                    timestamp,
-                    taker_side: order.side,
-                };
-                trades.push(trade);
-
-                // Update taker
-                order.remaining_quantity -= fill_quantity;
-
-                // Update maker in the queue
-                let new_maker_remaining = maker_remaining - fill_quantity;
-                if let Some(level) = self.asks.get_mut(&ask_price) {
-                    if let Some(maker) = level.front_mut() {
-                        maker.remaining_quantity = new_maker_remaining;
-                        if new_maker_remaining == 0 {
-                            maker.status = OrderStatus::Filled;
-                        } else {
-                            maker.status = OrderStatus::PartiallyFilled;
-                        }
-                    }
-                    level.update_quantity(fill_quantity);
+    /// Check an incoming order id against the configured `id_range`
+    fn id_in_range(&self, id: OrderId) -> bool {
+        self.config
+            .id_range
+            .as_ref()
+            .is_none_or(|range| range.contains(&id))
+    }
 
-                    // Remove fully filled orders
-                    if new_maker_remaining == 0 {
-                        level.pop_front();
-                    }
-                }
+    /// Update statistics and retained history for newly executed trades
+    fn record_trades(&mut self, trades: &[Trade]) {
+        self.debug_check_trade_ids(trades);
+        self.total_trades += trades.len() as u64;
+        self.total_volume += trades.iter().map(|t| t.quantity).sum::<u64>();
+        for trade in trades {
+            let notional = trade.price as u128 * trade.quantity as u128;
+            self.total_notional = self.total_notional.saturating_add(notional);
+        }
+        if let Some(last) = trades.last() {
+            self.last_trade_price = Some(last.price);
+        }
+        for trade in trades {
+            for user_id in [&trade.taker_user_id, &trade.maker_user_id] {
+                self.user_activity.entry(user_id.clone()).or_default().fills += 1;
+            }
+        }
 
-                // Update maker in index
-                if let Some(metadata) = self.order_index.get_mut(&maker_id) {
-                    metadata.remaining_quantity = new_maker_remaining;
-                    if new_maker_remaining == 0 {
-                        metadata.status = OrderStatus::Filled;
-                    } else {
-                        metadata.status = OrderStatus::PartiallyFilled;
-                    }
-                }
+        if let Some(histogram) = &mut self.fill_histogram {
+            for trade in trades {
+                histogram.record(trade.quantity);
+            }
+        }
+        if let Some(window) = &mut self.price_window {
+            for trade in trades {
+                window.record(trade.price);
+            }
+        }
+        if let Some(history) = &mut self.price_history {
+            for trade in trades {
+                history.record(trade.timestamp, trade.price);
+            }
+        }
+        if let Some(volumes) = &mut self.counterparty_volumes {
+            for trade in trades {
+                let (a, b) =
+                    analytics::counterparty_pair(&trade.maker_user_id, &trade.taker_user_id);
+                *volumes.entry((a.to_string(), b.to_string())).or_insert(0) += trade.quantity;
             }
+        }
 
-            // Clean up empty price levels
-            if self.asks.get(&ask_price).is_some_and(|l| l.is_empty()) {
-                self.asks.remove(&ask_price);
+        let capacity = self.config.trade_history_capacity;
+        if capacity > 0 {
+            for trade in trades {
+                if self.trade_history.len() == capacity {
+                    self.trade_history.pop_front();
+                }
+                self.trade_history.push_back(trade.clone());
             }
         }
 
-        // Update taker order status
-        if order.remaining_quantity == 0 {
-            order.status = OrderStatus::Filled;
-        } else if order.remaining_quantity < order.original_quantity {
-            order.status = OrderStatus::PartiallyFilled;
+        for trade in trades {
+            self.listeners.emit(OrderEvent::Trade(trade.clone()));
         }
     }
 
-    /// Match a sell order against bids (highest bid first)
-    fn match_sell_order(&mut self, order: &mut Order, trades: &mut Vec<Trade>) {
-        // Get price levels to match (highest bid first)
-        let price_levels: Vec<Price> = self
-            .bids
-            .keys()
-            .rev()
-            .filter(|&&bid_price| bid_price >= order.price)
-            .copied()
-            .collect();
+    /// Trade counts per size bucket (empty unless `fill_size_buckets` is configured)
+    pub fn fill_size_histogram(&self) -> Vec<(QtyBucket, u64)> {
+        self.fill_histogram
+            .as_ref()
+            .map(FillSizeHistogram::buckets)
+            .unwrap_or_default()
+    }
+
+    /// Get up to the `n` most recent retained trades, oldest first
+    ///
+    /// Only the last `trade_history_capacity` trades are retained.
+    pub fn recent_trades(&self, n: usize) -> impl DoubleEndedIterator<Item = &Trade> + '_ {
+        let skip = self.trade_history.len().saturating_sub(n);
+        self.trade_history.iter().skip(skip)
+    }
+
+    /// Write the retained trade history as CSV, oldest first
+    ///
+    /// Rows are written one at a time, so nothing beyond the history itself
+    /// is buffered. Wrap unbuffered writers (files, sockets) in a `BufWriter`.
+    /// Columns: `id,timestamp,price,quantity,taker_order_id,maker_order_id,taker_side`
+    pub fn export_trades_csv<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "id,timestamp,price,quantity,taker_order_id,maker_order_id,taker_side")?;
+        for trade in &self.trade_history {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                trade.id,
+                trade.timestamp,
+                trade.price,
+                trade.quantity,
+                trade.taker_order_id,
+                trade.maker_order_id,
+                trade.taker_side
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Whether `order` would reach a live resting order from the same user
+    ///
+    /// Orders listed in `ignoring` don't count, e.g. ones about to be replaced.
+    fn crosses_own_order(&self, order: &Order, ignoring: &[OrderId]) -> bool {
+        // Inclusive ranges: equal prices trade (`Side::reaches`)
+        let levels: Box<dyn Iterator<Item = &PriceLevelQueue>> = match order.side {
+            Side::Buy => Box::new(self.asks.range(..=order.price).map(|(_, l)| l)),
+            Side::Sell => Box::new(self.bids.range(order.price..).map(|(_, l)| l)),
+        };
+        levels.flat_map(|level| &level.orders).any(|maker| {
+            maker.user_id == order.user_id
+                && !ignoring.contains(&maker.id)
+                && self
+                    .order_index
+                    .get(&maker.id)
+                    .is_some_and(|m| m.status != OrderStatus::Cancelled)
+        })
+    }
+
+    /// Match an incoming order against the opposite side of the book
+    ///
+    /// Levels are visited best price first (lowest ask for a buy, highest bid
+    /// for a sell). Within a level, quantity is allocated according to the
+    /// configured `MatchingMode`.
+    fn match_order(&mut self, order: &mut Order, trades: &mut TradeSink<'_>) {
+        // Hidden midpoint liquidity first: the mid is inside the spread
+        self.match_midpoint(order, trades);
+
+        // Get price levels to match (best first), including one at the
+        // order's own price: equal prices trade (`Side::reaches`). Midpoint
+        // orders never take lit liquidity.
+        let price_levels: Vec<Price> = match (order.order_type, order.side) {
+            (OrderType::MidpointPeg, _) => Vec::new(),
+            (_, Side::Buy) => self.asks.range(..=order.price).map(|(&p, _)| p).collect(),
+            (_, Side::Sell) => self.bids.range(order.price..).rev().map(|(&p, _)| p).collect(),
+        };
 
-        for bid_price in price_levels {
+        let mut levels_traded = 0;
+        let mut last_level_traded = None;
+        for price in price_levels {
             if order.remaining_quantity == 0 {
                 break;
             }
-
-            // Match against orders at this price level
-            loop {
-                if order.remaining_quantity == 0 {
-                    break;
+            if !order.side.reaches(order.price, price) {
+                // A market order's slippage limit, set by its first fill
+                break;
+            }
+            if levels_traded > 0 && order.max_levels.is_some_and(|max| levels_traded >= max) {
+                // Rest at the last level traded rather than crossing the rest.
+                // That is the level's price, not the trade's: under
+                // `ExecutionPricePolicy::TakerPrice` trades print at the taker's limit
+                if let Some(last) = last_level_traded {
+                    order.price = last;
                 }
+                break;
+            }
 
-                // Get level and check front order
-                let level = match self.bids.get_mut(&bid_price) {
-                    Some(l) => l,
-                    None => break,
-                };
+            let trades_before = trades.count;
 
-                // Clean up cancelled orders at the front
-                level.cleanup_cancelled(&self.order_index);
-
-                // Extract maker data to avoid borrow conflicts
-                let maker_data = match level.front_mut() {
-                    Some(maker) => {
-                        // Check if cancelled
-                        if let Some(metadata) = self.order_index.get(&maker.id) {
-                            if metadata.status == OrderStatus::Cancelled {
-                                level.pop_front();
-                                continue;
-                            }
-                        }
-                        // Prevent self-trading
-                        if maker.user_id == order.user_id {
-                            break;
-                        }
-                        // Extract data needed for trade
-                        Some((
-                            maker.id,
-                            maker.user_id.clone(),
-                            maker.market_id.clone(),
-                            maker.outcome_id.clone(),
-                            maker.price,
-                            maker.remaining_quantity,
-                        ))
-                    }
-                    None => None,
-                };
+            // A refreshed iceberg slice still trades before any worse price
+            while self.match_level(order, price, trades) && order.remaining_quantity > 0 {}
 
-                let (maker_id, maker_user_id, market_id, outcome_id, maker_price, maker_remaining) =
-                    match maker_data {
-                        Some(data) => data,
-                        None => break,
-                    };
-
-                // Calculate fill quantity
-                let fill_quantity = order.remaining_quantity.min(maker_remaining);
-
-                // Create trade
-                let trade_id = self.next_trade_id;
-                self.next_trade_id += 1;
-
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_micros() as u64;
-
-                let trade = Trade {
-                    id: trade_id,
-                    taker_order_id: order.id,
-                    maker_order_id: maker_id,
-                    taker_user_id: order.user_id.clone(),
-                    maker_user_id,
-                    market_id,
-                    outcome_id,
-                    price: maker_price,
-                    quantity: fill_quantity,
-                    timestamp,
-                    taker_side: order.side,
-                };
-                trades.push(trade);
-
-                // Update taker
-                order.remaining_quantity -= fill_quantity;
-
-                // Update maker in the queue
-                let new_maker_remaining = maker_remaining - fill_quantity;
-                if let Some(level) = self.bids.get_mut(&bid_price) {
-                    if let Some(maker) = level.front_mut() {
-                        maker.remaining_quantity = new_maker_remaining;
-                        if new_maker_remaining == 0 {
-                            maker.status = OrderStatus::Filled;
-                        } else {
-                            maker.status = OrderStatus::PartiallyFilled;
-                        }
+            if trades.count > trades_before {
+                if last_level_traded.is_none() && order.order_type == OrderType::Market {
+                    if let Some(bps) = order.max_slippage_bps {
+                        order.price = match order.side {
+                            Side::Buy => price.saturating_add(bps),
+                            Side::Sell => price.saturating_sub(bps),
+                        };
                     }
-                    level.update_quantity(fill_quantity);
+                }
+                levels_traded += 1;
+                last_level_traded = Some(price);
+            }
 
-                    // Remove fully filled orders
-                    if new_maker_remaining == 0 {
-                        level.pop_front();
-                    }
+            // Clean up price levels left without live quantity
+            self.remove_drained_level(order.side.opposite(), price);
+        }
+
+        // Update taker order status; quantity netted by self-trade
+        // prevention is gone but was never filled
+        let traded = trades.quantity;
+        if order.remaining_quantity == 0 {
+            order.status = if traded == order.original_quantity {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::Cancelled
+            };
+        } else if traded > 0 {
+            order.status = OrderStatus::PartiallyFilled;
+        }
+    }
+
+    /// Net a taker against the same user's resting order (`DecrementAndCancel`)
+    ///
+    /// Both orders lose the overlapping quantity; no trade is printed.
+    fn net_self_trade(
+        &mut self,
+        order: &mut Order,
+        price: Price,
+        maker_id: OrderId,
+        maker_remaining: Quantity,
+    ) {
+        let overlap = order.remaining_quantity.min(maker_remaining);
+        order.remaining_quantity -= overlap;
+
+        if overlap == maker_remaining {
+            let _ = self.cancel_resting(maker_id);
+            return;
+        }
+
+        let level = match self.book_mut(order.side.opposite()).get_mut(&price) {
+            Some(l) => l,
+            None => return,
+        };
+        if let Some(maker) = level.orders.iter_mut().find(|o| o.id == maker_id) {
+            maker.remaining_quantity -= overlap;
+        }
+        level.update_quantity(overlap);
+        if let Some(metadata) = self.order_index.get_mut(&maker_id) {
+            metadata.remaining_quantity -= overlap;
+        }
+        self.debug_check_fill(order.side.opposite(), price, maker_id);
+    }
+
+    /// Match against one price level with the configured `MatchingAlgorithm`
+    ///
+    /// The algorithm plans the level; this applies the plan. Cancelled orders
+    /// queued ahead of each maker the plan reaches are dropped on the way, as
+    /// is the level's cancelled remainder if the taker exhausts it.
+    ///
+    /// Icebergs that show a new slice sit out the rest of the plan, as they
+    /// have lost their place. Returns whether any did, in which case the level
+    /// may have more to trade.
+    fn match_level(&mut self, order: &mut Order, price: Price, trades: &mut TradeSink<'_>) -> bool {
+        let maker_side = order.side.opposite();
+        let Some(level) = self.book(maker_side).get(&price) else {
+            return false;
+        };
+        let makers: Vec<&BookOrder> = level
+            .orders
+            .iter()
+            .filter(|maker| {
+                self.order_index
+                    .get(&maker.id)
+                    .is_some_and(|m| m.status != OrderStatus::Cancelled)
+            })
+            .collect();
+        let mut positions: HashMap<OrderId, usize> =
+            makers.iter().enumerate().map(|(position, maker)| (maker.id, position)).collect();
+        let actions = self.matcher.match_level(order, &makers, self.config.self_trade_policy);
+
+        // Runs of fills in queue order are applied to the level in one pass
+        let mut fills: Vec<(OrderId, Quantity)> = Vec::new();
+        let mut last_fill_position = None;
+        let mut refreshed = false;
+        for action in actions {
+            let maker_id = action.maker_id();
+            let Some(&position) = positions.get(&maker_id) else {
+                continue;
+            };
+            let in_order = last_fill_position.is_none_or(|last| position > last);
+            if !matches!(action, LevelAction::Fill { .. }) || !in_order {
+                for id in self.apply_level_fills(maker_side, price, &mut fills) {
+                    positions.remove(&id);
+                    refreshed = true;
                 }
+                last_fill_position = None;
+                if !positions.contains_key(&maker_id) {
+                    continue;
+                }
+            }
 
-                // Update maker in index
-                if let Some(metadata) = self.order_index.get_mut(&maker_id) {
-                    metadata.remaining_quantity = new_maker_remaining;
-                    if new_maker_remaining == 0 {
-                        metadata.status = OrderStatus::Filled;
-                    } else {
-                        metadata.status = OrderStatus::PartiallyFilled;
+            let Some(metadata) = self
+                .order_index
+                .get(&maker_id)
+                .filter(|m| m.status != OrderStatus::Cancelled && m.remaining_quantity > 0)
+            else {
+                continue;
+            };
+            let maker_remaining = metadata.remaining_quantity;
+            match action {
+                LevelAction::Fill { quantity, .. } => {
+                    let quantity = quantity.min(order.remaining_quantity).min(maker_remaining);
+                    if quantity == 0 {
+                        continue;
+                    }
+                    let maker_user_id = metadata.user_id.clone();
+                    // The fill reaches the level in the next batch, the reserve stays
+                    let left = metadata.total_remaining() - quantity;
+                    let trade = self.new_trade(order, maker_id, maker_user_id, price, quantity);
+                    self.emit_trade(trade, trades);
+                    self.record_maker_fill(maker_id, quantity, left);
+                    order.remaining_quantity -= quantity;
+                    fills.push((maker_id, quantity));
+                    last_fill_position = Some(position);
+                }
+                LevelAction::Net { .. } => {
+                    if metadata.user_id == order.user_id && order.remaining_quantity > 0 {
+                        self.drop_cancelled_ahead(maker_side, price, maker_id);
+                        self.net_self_trade(order, price, maker_id, maker_remaining);
                     }
                 }
+                LevelAction::Decline { .. } => {
+                    self.drop_cancelled_ahead(maker_side, price, maker_id);
+                    let _ = self.cancel_resting(maker_id);
+                }
             }
+        }
+        refreshed |= !self.apply_level_fills(maker_side, price, &mut fills).is_empty();
 
-            // Clean up empty price levels
-            if self.bids.get(&bid_price).is_some_and(|l| l.is_empty()) {
-                self.bids.remove(&bid_price);
+        if order.remaining_quantity > 0 {
+            let book = match maker_side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+            if let Some(level) = book.get_mut(&price) {
+                let dropped = level.cleanup_cancelled(&self.order_index);
+                self.queued_cancelled -= dropped.len();
+                self.archive_orders(&dropped);
             }
         }
+        refreshed
+    }
 
-        // Update taker order status
-        if order.remaining_quantity == 0 {
-            order.status = OrderStatus::Filled;
-        } else if order.remaining_quantity < order.original_quantity {
-            order.status = OrderStatus::PartiallyFilled;
+    /// Apply and clear a run of planned fills, `fills` being in queue order
+    ///
+    /// Returns the icebergs that showed a new slice, now at the back of the level.
+    fn apply_level_fills(
+        &mut self,
+        side: Side,
+        price: Price,
+        fills: &mut Vec<(OrderId, Quantity)>,
+    ) -> Vec<OrderId> {
+        let Some(&(last_id, _)) = fills.last() else {
+            return Vec::new();
+        };
+        self.drop_cancelled_ahead(side, price, last_id);
+        let refreshed = self.fill_level_orders(side, price, fills);
+        fills.clear();
+        refreshed
+    }
+
+    /// Drop cancelled orders queued ahead of `order_id` at a level
+    fn drop_cancelled_ahead(&mut self, side: Side, price: Price, order_id: OrderId) {
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if let Some(level) = book.get_mut(&price) {
+            let dropped = level.cleanup_cancelled_before(order_id, &self.order_index);
+            self.queued_cancelled -= dropped.len();
+            self.archive_orders(&dropped);
+        }
+    }
+
+    /// Record a trade the match loop just executed and deliver it
+    fn emit_trade(&mut self, trade: Trade, trades: &mut TradeSink<'_>) {
+        self.record_trades(std::slice::from_ref(&trade));
+        trades.count += 1;
+        trades.quantity += trade.quantity;
+        (trades.deliver)(trade);
+    }
+
+    /// Create a trade between a taker and a maker, assigning the next trade id
+    ///
+    /// The execution price is chosen by the configured `ExecutionPricePolicy`.
+    fn new_trade(
+        &mut self,
+        taker: &Order,
+        maker_order_id: OrderId,
+        maker_user_id: UserId,
+        maker_price: Price,
+        quantity: Quantity,
+    ) -> Trade {
+        let trade_id = self.next_trade_id;
+        self.next_trade_id += 1;
+        let price = match (self.config.execution_price_policy, taker.order_type) {
+            (ExecutionPricePolicy::TakerPrice, OrderType::Limit) => taker.price,
+            _ => maker_price,
+        };
+        let maker_client_order_id = self
+            .order_index
+            .get(&maker_order_id)
+            .and_then(|m| m.client_order_id.clone());
+
+        Trade {
+            id: trade_id,
+            taker_order_id: taker.id,
+            maker_order_id,
+            taker_user_id: taker.user_id.clone(),
+            maker_user_id,
+            market_id: self.market_id.clone(),
+            outcome_id: self.outcome_id.clone(),
+            price,
+            quantity,
+            timestamp: self.clock.now(),
+            taker_side: taker.side,
+            taker_client_order_id: taker.client_order_id.clone(),
+            maker_client_order_id,
+        }
+    }
+
+    /// Apply several fills to orders at one level in a single pass
+    ///
+    /// `fills` must be in queue order.
+    fn fill_level_orders(
+        &mut self,
+        side: Side,
+        price: Price,
+        fills: &[(OrderId, Quantity)],
+    ) -> Vec<OrderId> {
+        let now = self.clock.now();
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let level = match book.get_mut(&price) {
+            Some(l) => l,
+            None => return Vec::new(),
+        };
+
+        let mut refreshed = Vec::new();
+        let mut filled = Vec::new();
+        let mut pending = fills.iter().peekable();
+        for queued in level.orders.iter_mut() {
+            let Some(&&(order_id, quantity)) = pending.peek() else {
+                break;
+            };
+            if queued.id != order_id {
+                continue;
+            }
+            pending.next();
+
+            queued.remaining_quantity -= quantity;
+            level.total_quantity = level.total_quantity.saturating_sub(quantity);
+            if queued.refresh_slice(now) {
+                level.total_quantity += queued.remaining_quantity;
+                refreshed.push(order_id);
+            }
+            queued.status = if queued.remaining_quantity == 0 {
+                self.live_orders -= 1;
+                Self::unindex_user_order(&mut self.user_orders, &queued.user_id, order_id);
+                Self::unindex_client_order(
+                    &mut self.client_orders,
+                    &queued.user_id,
+                    queued.client_order_id.as_deref(),
+                );
+                filled.push(order_id);
+                OrderStatus::Filled
+            } else {
+                OrderStatus::PartiallyFilled
+            };
+
+            if let Some(metadata) = self.order_index.get_mut(&order_id) {
+                metadata.remaining_quantity = queued.remaining_quantity;
+                metadata.hidden_quantity = queued.hidden_quantity;
+                metadata.status = queued.status;
+            }
+        }
+
+        level.orders.retain(|o| o.status != OrderStatus::Filled);
+        for &order_id in &refreshed {
+            level.requeue(order_id);
+        }
+        self.remove_drained_level(side, price);
+
+        for &(order_id, _) in fills {
+            self.debug_check_fill(side, price, order_id);
+        }
+        self.archive_orders(&filled);
+        refreshed
+    }
+
+    /// Check that trades about to be emitted carry the latest ids, in assignment order
+    ///
+    /// Runs in debug builds only. Trades must be emitted as soon as they are
+    /// created, so a batch holds exactly the ids last taken from
+    /// `next_trade_id`. As that counter only grows, every emitted id is then
+    /// unique and strictly increasing over the book's lifetime.
+    fn debug_check_trade_ids(&self, trades: &[Trade]) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        let first = self.next_trade_id - trades.len() as TradeId;
+        for (trade, expected) in trades.iter().zip(first..) {
+            assert_eq!(trade.id, expected, "trade emitted out of id assignment order");
+        }
+    }
+
+    /// Check that a filled order's queue entry and index entry agree
+    ///
+    /// Runs in debug builds only. A queued order must have the same remaining
+    /// quantity and status as its index entry; an order no longer queued must
+    /// be indexed as fully filled.
+    fn debug_check_fill(&self, side: Side, price: Price, order_id: OrderId) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        let metadata = self
+            .order_index
+            .get(&order_id)
+            .unwrap_or_else(|| panic!("filled order {} is not indexed", order_id));
+        let queued = self
+            .book(side)
+            .get(&price)
+            .and_then(|level| level.orders.iter().find(|o| o.id == order_id));
+        match queued {
+            Some(queued) => {
+                assert_eq!(
+                    queued.remaining_quantity, metadata.remaining_quantity,
+                    "order {} remaining differs between book and index",
+                    order_id
+                );
+                assert_eq!(
+                    queued.status, metadata.status,
+                    "order {} status differs between book and index",
+                    order_id
+                );
+            }
+            None => assert!(
+                metadata.remaining_quantity == 0 && metadata.status == OrderStatus::Filled,
+                "order {} left the book but is indexed as {:?} with {} remaining",
+                order_id,
+                metadata.status,
+                metadata.remaining_quantity
+            ),
+        }
+    }
+
+    /// The price levels of one side of the book
+    fn book(&self, side: Side) -> &BTreeMap<Price, PriceLevelQueue> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    /// Mutable price levels of one side of the book
+    fn book_mut(&mut self, side: Side) -> &mut BTreeMap<Price, PriceLevelQueue> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
         }
     }
 
     /// Add an order to the appropriate side of the book
-    fn add_to_book(&mut self, order: Order) {
+    fn add_to_book(&mut self, mut order: BookOrder) {
+        order.slice();
         let price = order.price;
         let order_id = order.id;
+        let user_id = order.user_id.clone();
+        self.user_orders.entry(user_id.clone()).or_default().insert(order.id);
+        let side = order.side;
         let remaining = order.remaining_quantity;
+        let hidden = order.hidden_quantity;
         let status = order.status;
+        let client_order_id = order.client_order_id.clone();
+        let timestamp = order.timestamp;
+        let pegged = order.order_type == OrderType::MidpointPeg;
+        if let Some(client_order_id) = &client_order_id {
+            self.client_orders
+                .entry(user_id.clone())
+                .or_default()
+                .insert(client_order_id.clone(), order_id);
+        }
 
-        let book = match order.side {
-            Side::Buy => &mut self.bids,
-            Side::Sell => &mut self.asks,
-        };
-
-        book.entry(price)
-            .or_insert_with(PriceLevelQueue::new)
-            .push_back(order);
+        if pegged {
+            self.midpoint_orders.push(order);
+        } else {
+            let book = match order.side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+            book.entry(price)
+                .or_insert_with(PriceLevelQueue::new)
+                .push_back(order);
+        }
 
-        // Add to index
-        self.order_index.insert(
+        // Add to index (an amended order replaces its own entry)
+        let filled_quantity = self.order_index.get(&order_id).map_or(0, |m| m.filled_quantity);
+        let previous = self.order_index.insert(
             order_id,
             OrderMetadata {
+                user_id,
+                side,
                 price,
                 status,
                 remaining_quantity: remaining,
+                hidden_quantity: hidden,
+                client_order_id,
+                timestamp,
+                pegged,
+                filled_quantity,
             },
         );
+        if previous.is_none() {
+            self.live_orders += 1;
+        }
+    }
+
+    /// Remove an order that is no longer live from its user's index entry
+    fn unindex_user_order(
+        user_orders: &mut HashMap<UserId, HashSet<OrderId>>,
+        user_id: &str,
+        order_id: OrderId,
+    ) {
+        if let Some(orders) = user_orders.get_mut(user_id) {
+            orders.remove(&order_id);
+            if orders.is_empty() {
+                user_orders.remove(user_id);
+            }
+        }
+    }
+
+    /// Remove an order that is no longer live from its user's client id index
+    fn unindex_client_order(
+        client_orders: &mut HashMap<UserId, HashMap<String, OrderId>>,
+        user_id: &str,
+        client_order_id: Option<&str>,
+    ) {
+        let Some(client_order_id) = client_order_id else {
+            return;
+        };
+        if let Some(orders) = client_orders.get_mut(user_id) {
+            orders.remove(client_order_id);
+            if orders.is_empty() {
+                client_orders.remove(user_id);
+            }
+        }
+    }
+
+    /// Engine id of the user's live order with this client order id
+    fn live_client_order(&self, user_id: &str, client_order_id: &str) -> Option<OrderId> {
+        self.client_orders.get(user_id)?.get(client_order_id).copied()
     }
 
     /// Cancel an order using lazy deletion
@@ -789,11 +2160,45 @@ impl OrderBook {
     ///
     /// The order remains in the VecDeque but will be skipped during matching
     /// and cleaned up when encountered.
+    ///
+    /// With `min_resting_micros` configured, a live order that has rested for
+    /// less than that (by the clock, since its timestamp) is not cancelled:
+    /// `TooSoonToCancel` reports how much longer it must rest.
     pub fn cancel_order(&mut self, order_id: OrderId) -> Result<(), OrderBookError> {
+        self.cancellable(order_id)?;
+        self.cancel_resting(order_id)
+    }
+
+    /// The index entry of an order `cancel_order` would cancel, or the error it would return
+    fn cancellable(&self, order_id: OrderId) -> Result<&OrderMetadata, OrderBookError> {
+        let metadata = self
+            .order_index
+            .get(&order_id)
+            .ok_or_else(|| self.archive.missing(order_id))?;
+        match metadata.status {
+            OrderStatus::Cancelled => return Err(OrderBookError::OrderAlreadyCancelled(order_id)),
+            OrderStatus::Filled => return Err(OrderBookError::OrderAlreadyFilled(order_id)),
+            _ => {}
+        }
+
+        let min_resting = self.config.min_resting_micros;
+        if min_resting > 0 {
+            let rested = self.clock.now().saturating_sub(metadata.timestamp);
+            if rested < min_resting {
+                return Err(OrderBookError::TooSoonToCancel {
+                    remaining_micros: min_resting - rested,
+                });
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// Cancel an order on the book's own initiative, ignoring `min_resting_micros`
+    fn cancel_resting(&mut self, order_id: OrderId) -> Result<(), OrderBookError> {
         let metadata = self
             .order_index
             .get_mut(&order_id)
-            .ok_or(OrderBookError::OrderNotFound(order_id))?;
+            .ok_or_else(|| self.archive.missing(order_id))?;
 
         match metadata.status {
             OrderStatus::Cancelled => {
@@ -805,112 +2210,830 @@ impl OrderBook {
             _ => {
                 // Mark as cancelled (lazy deletion)
                 metadata.status = OrderStatus::Cancelled;
-                metadata.remaining_quantity = 0;
+            }
+        }
+        self.live_orders -= 1;
+        if !metadata.pegged {
+            self.queued_cancelled += 1;
+        }
+        Self::unindex_user_order(&mut self.user_orders, &metadata.user_id, order_id);
+        Self::unindex_client_order(
+            &mut self.client_orders,
+            &metadata.user_id,
+            metadata.client_order_id.as_deref(),
+        );
+
+        // The order stays queued, but its size no longer counts towards the level
+        let cancelled_quantity = std::mem::take(&mut metadata.remaining_quantity);
+        let reserve = std::mem::take(&mut metadata.hidden_quantity);
+        let price = metadata.price;
+
+        self.total_cancelled_orders += 1;
+        self.total_cancelled_volume += cancelled_quantity + reserve;
+        self.user_activity.entry(metadata.user_id.clone()).or_default().cancels += 1;
+        if metadata.pegged {
+            // Midpoint queues are short and hidden, so they skip lazy deletion
+            self.midpoint_orders.remove(metadata.side, order_id);
+        } else {
+            let book = match metadata.side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+            if let Some(level) = book.get_mut(&price) {
+                level.update_quantity(cancelled_quantity);
             }
         }
 
+        let client_order_id = metadata.client_order_id.clone();
+        let (pegged, side) = (metadata.pegged, metadata.side);
+        self.listeners.emit(OrderEvent::Cancelled { order_id, client_order_id });
+        if pegged {
+            self.archive_orders(&[order_id]);
+        } else {
+            self.remove_drained_level(side, price);
+        }
+        self.poll_depth_updates();
         Ok(())
     }
 
-    /// Force cleanup of a cancelled order and its price level if empty
+    /// Execute a taker against one specific resting order, bypassing priority
     ///
-    /// This is optional - cancelled orders are naturally cleaned up during matching.
-    /// Use this for explicit cleanup when needed.
+    /// This is an audited escape hatch for settling pre-agreed trades; regular
+    /// flow must go through `process_limit_order`. The maker must be live, on
+    /// the opposite side, owned by a different user and price-compatible with
+    /// the taker. The trade executes at the maker's price (or the taker's, per
+    /// `ExecutionPricePolicy`) for the smaller of
+    /// the two quantities. The taker never rests: any quantity beyond the
+    /// maker's remaining size is not executed.
+    ///
+    /// The taker is checked as a submitted limit order is (rate limit, tick
+    /// and timestamp policies, notional, ids), and takes a sequence number. A
+    /// refused taker is reported to listeners as a rejected submission is.
     ///
     /// # Time Complexity
-    /// O(N) where N is the number of orders at the price level
-    pub fn cleanup_cancelled_order(&mut self, order_id: OrderId) -> Result<(), OrderBookError> {
+    /// O(log P + K) where K is the number of orders at the maker's price level
+    pub fn match_against(
+        &mut self,
+        taker: Order,
+        maker_order_id: OrderId,
+    ) -> Result<Trade, OrderBookError> {
+        let order_id = taker.id;
+        let client_order_id = taker.client_order_id.clone();
+        let result = self.execute_match_against(taker, maker_order_id);
+        if let Err(error) = &result {
+            let code = error.code();
+            self.listeners.emit(OrderEvent::Rejected { order_id, client_order_id, code });
+        }
+        result
+    }
+
+    fn execute_match_against(
+        &mut self,
+        mut taker: Order,
+        maker_order_id: OrderId,
+    ) -> Result<Trade, OrderBookError> {
+        self.check_open()?;
+        self.check_rate(&taker.user_id)?;
+        taker.price = self.check_tick(taker.price)?;
+        taker.timestamp = self.check_timestamp(taker.timestamp)?;
+        self.check_order(&taker)?;
+
         let metadata = self
             .order_index
-            .get(&order_id)
-            .ok_or(OrderBookError::OrderNotFound(order_id))?;
+            .get(&maker_order_id)
+            .ok_or_else(|| self.archive.missing(maker_order_id))?;
 
-        if metadata.status != OrderStatus::Cancelled {
-            return Ok(()); // Nothing to clean up
+        match metadata.status {
+            OrderStatus::Cancelled => {
+                return Err(OrderBookError::OrderAlreadyCancelled(maker_order_id))
+            }
+            OrderStatus::Filled => return Err(OrderBookError::OrderAlreadyFilled(maker_order_id)),
+            _ => {}
+        }
+        if metadata.pegged {
+            return Err(OrderBookError::PeggedOrder(maker_order_id));
+        }
+        if metadata.side == taker.side {
+            return Err(OrderBookError::SameSide);
+        }
+        if metadata.user_id == taker.user_id {
+            return Err(OrderBookError::SelfTrade);
+        }
+        if !taker.side.reaches(taker.price, metadata.price) {
+            return Err(OrderBookError::IncompatiblePrice);
         }
 
-        let price = metadata.price;
+        let maker_side = metadata.side;
+        let maker_price = metadata.price;
+        let maker_user_id = metadata.user_id.clone();
+        let quantity = taker.remaining_quantity.min(metadata.remaining_quantity);
+
+        self.record_submission(&taker.user_id);
+        self.sequence += 1;
+        self.next_order_id = self.next_order_id.max(taker.id.saturating_add(1));
+        self.fill_resting_order(maker_side, maker_price, maker_order_id, quantity);
+        let trade = self.new_trade(&taker, maker_order_id, maker_user_id, maker_price, quantity);
+        self.record_trades(std::slice::from_ref(&trade));
+        let left = self.order_index.get(&maker_order_id).map_or(0, |m| m.total_remaining());
+        self.record_maker_fill(maker_order_id, quantity, left);
+        if self.get_order_status(maker_order_id) == Some(OrderStatus::Filled) {
+            self.archive_orders(&[maker_order_id]);
+        }
+        self.poll_depth_updates();
 
-        // Try to find and remove from bids
-        if let Some(level) = self.bids.get_mut(&price) {
-            level.orders.retain(|o| o.id != order_id);
-            level.total_quantity = level.orders.iter().map(|o| o.remaining_quantity).sum();
-            if level.is_empty() {
-                self.bids.remove(&price);
+        Ok(trade)
+    }
+
+    /// Fill part or all of a resting order wherever it sits in its level
+    ///
+    /// Updates the queued order, the level total and the index, and removes
+    /// the order from its level once fully filled. An iceberg showing a new
+    /// slice moves to the back of the level instead.
+    fn fill_resting_order(&mut self, side: Side, price: Price, order_id: OrderId, quantity: Quantity) {
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        let now = self.clock.now();
+        let (mut new_remaining, mut new_hidden) = (0, 0);
+        if let Some(level) = book.get_mut(&price) {
+            if let Some(position) = level.orders.iter().position(|o| o.id == order_id) {
+                let order = &mut level.orders[position];
+                order.remaining_quantity -= quantity;
+                let refreshed = order.refresh_slice(now);
+                new_remaining = order.remaining_quantity;
+                new_hidden = order.hidden_quantity;
+                order.status = if new_remaining == 0 {
+                    OrderStatus::Filled
+                } else {
+                    OrderStatus::PartiallyFilled
+                };
+                if refreshed {
+                    level.total_quantity += new_remaining;
+                    level.requeue(order_id);
+                } else if new_remaining == 0 {
+                    if let Some(filled) = level.orders.remove(position) {
+                        Self::unindex_user_order(&mut self.user_orders, &filled.user_id, order_id);
+                        Self::unindex_client_order(
+                            &mut self.client_orders,
+                            &filled.user_id,
+                            filled.client_order_id.as_deref(),
+                        );
+                    }
+                    self.live_orders -= 1;
+                }
             }
-            self.order_index.remove(&order_id);
-            return Ok(());
+            level.update_quantity(quantity);
         }
+        self.remove_drained_level(side, price);
+
+        if let Some(metadata) = self.order_index.get_mut(&order_id) {
+            metadata.remaining_quantity = new_remaining;
+            metadata.hidden_quantity = new_hidden;
+            metadata.status = if new_remaining == 0 {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::PartiallyFilled
+            };
+        }
+        self.debug_check_fill(side, price, order_id);
+    }
 
-        // Try to find and remove from asks
-        if let Some(level) = self.asks.get_mut(&price) {
-            level.orders.retain(|o| o.id != order_id);
-            level.total_quantity = level.orders.iter().map(|o| o.remaining_quantity).sum();
-            if level.is_empty() {
-                self.asks.remove(&price);
-            }
-            self.order_index.remove(&order_id);
-            return Ok(());
+    /// Cancel every live order belonging to a user
+    ///
+    /// Returns the ids of the orders that were cancelled, ascending.
+    ///
+    /// # Time Complexity
+    /// O(K log K) where K is the number of the user's live orders
+    pub fn cancel_user_orders(&mut self, user_id: &str) -> Vec<OrderId> {
+        let order_ids = self.user_orders(user_id);
+
+        for &order_id in &order_ids {
+            // Only live orders were collected, so this cannot fail
+            let _ = self.cancel_resting(order_id);
         }
 
-        Ok(())
+        order_ids
     }
 
-    /// Get order status
-    pub fn get_order_status(&self, order_id: OrderId) -> Option<OrderStatus> {
-        self.order_index.get(&order_id).map(|m| m.status)
+    /// Cancel several orders in one call, each as `cancel_order` would
+    ///
+    /// Returns every id with its outcome, in the order given, so ids that
+    /// were already filled, cancelled or unknown show up as errors without
+    /// stopping the rest of the batch.
+    ///
+    /// # Time Complexity
+    /// O(K) cancels where K is the number of ids
+    pub fn cancel_many(&mut self, ids: &[OrderId]) -> Vec<(OrderId, Result<(), OrderBookError>)> {
+        ids.iter().map(|&id| (id, self.cancel_order(id))).collect()
     }
 
-    /// Get remaining quantity for an order
-    pub fn get_order_remaining(&self, order_id: OrderId) -> Option<Quantity> {
-        self.order_index.get(&order_id).map(|m| m.remaining_quantity)
-    }
+    /// Halt the market and cancel every live order, in one step
+    ///
+    /// The operator's panic button: the book stops accepting orders before
+    /// the first cancel, and as this holds the book mutably throughout,
+    /// nothing can be submitted in between. Returns the cancelled orders in
+    /// `drain` order, with the quantity each had left and status Cancelled.
+    /// Cancel events are emitted as usual. `resume` reopens the market.
+    ///
+    /// # Time Complexity
+    /// O(N) where N is the number of queued orders
+    pub fn emergency_halt(&mut self) -> Vec<Order> {
+        self.state = MarketState::Halted;
 
-    /// Get a snapshot of the top N levels of the order book
-    pub fn get_depth(&self, levels: usize) -> (Vec<(Price, Quantity)>, Vec<(Price, Quantity)>) {
-        let bids: Vec<(Price, Quantity)> = self
+        let live: Vec<Order> = self
             .bids
-            .iter()
+            .values()
             .rev()
-            .take(levels)
-            .map(|(&price, level)| (price, level.total_quantity))
+            .chain(self.asks.values())
+            .flat_map(|level| &level.orders)
+            .chain(self.midpoint_orders.iter())
+            .filter_map(|order| {
+                let metadata = self.order_index.get(&order.id)?;
+                let live = matches!(
+                    metadata.status,
+                    OrderStatus::Open | OrderStatus::PartiallyFilled
+                );
+                live.then(|| {
+                    let mut order = order.to_order(&self.market_id, &self.outcome_id);
+                    order.remaining_quantity = metadata.total_remaining();
+                    order.status = OrderStatus::Cancelled;
+                    order
+                })
+            })
             .collect();
 
-        let asks: Vec<(Price, Quantity)> = self
-            .asks
-            .iter()
-            .take(levels)
-            .map(|(&price, level)| (price, level.total_quantity))
+        for order in &live {
+            let _ = self.cancel_resting(order.id);
+        }
+        // Everything queued is cancelled now, so the levels can go at once
+        let queued: Vec<OrderId> = self
+            .bids
+            .values()
+            .chain(self.asks.values())
+            .flat_map(|level| level.orders.iter().map(|order| order.id))
             .collect();
+        self.bids.clear();
+        self.asks.clear();
+        self.queued_cancelled = 0;
+        self.archive_orders(&queued);
+        self.poll_depth_updates();
+        live
+    }
 
-        (bids, asks)
+    /// Whether the market is open or halted
+    pub fn market_state(&self) -> MarketState {
+        self.state
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Reopen a halted market to new orders
+    ///
+    /// Orders cancelled by `emergency_halt` stay cancelled.
+    pub fn resume(&mut self) {
+        self.state = MarketState::Open;
+    }
 
-    fn create_test_order(
-        id: OrderId,
-        user_id: &str,
-        side: Side,
+    /// Cancel a user's live order by its client order id
+    ///
+    /// Returns the engine id of the cancelled order, or `OrderNotFound` (with
+    /// id 0, as the engine id is unknown) if the user has no live order with
+    /// that client id.
+    ///
+    /// # Time Complexity
+    /// O(1)
+    pub fn cancel_by_client_id(
+        &mut self,
+        user_id: &str,
+        client_order_id: &str,
+    ) -> Result<OrderId, OrderBookError> {
+        let order_id = self
+            .live_client_order(user_id, client_order_id)
+            .ok_or(OrderBookError::OrderNotFound(0))?;
+        self.cancel_order(order_id)?;
+        Ok(order_id)
+    }
+
+    /// Ids of a user's live (open or partially filled) orders, ascending
+    ///
+    /// The order is fixed so that mass cancels built on it, and their
+    /// results, are the same on every replay.
+    ///
+    /// # Time Complexity
+    /// O(K log K) where K is the number of the user's live orders
+    pub fn user_orders(&self, user_id: &str) -> Vec<OrderId> {
+        let mut ids: Vec<OrderId> = self
+            .user_orders
+            .get(user_id)
+            .map(|orders| orders.iter().copied().collect())
+            .unwrap_or_default();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Record a heartbeat from a user at `now`
+    ///
+    /// Users who have heartbeated at least once are subject to `cancel_stale_users`.
+    pub fn touch_user(&mut self, user_id: &str, now: Timestamp) {
+        self.heartbeats.insert(user_id.to_string(), now);
+    }
+
+    /// Cancel all orders of users whose last heartbeat is older than `max_idle`
+    ///
+    /// Stale users are removed from the heartbeat registry until they touch
+    /// again. Returns the ids of every order cancelled, by user id, then
+    /// ascending.
+    pub fn cancel_stale_users(&mut self, now: Timestamp, max_idle: Timestamp) -> Vec<OrderId> {
+        let mut stale_users: Vec<UserId> = self
+            .heartbeats
+            .iter()
+            .filter(|(_, &last_seen)| now.saturating_sub(last_seen) > max_idle)
+            .map(|(user_id, _)| user_id.clone())
+            .collect();
+        stale_users.sort_unstable();
+
+        let mut cancelled = Vec::new();
+        for user_id in stale_users {
+            self.heartbeats.remove(&user_id);
+            cancelled.extend(self.cancel_user_orders(&user_id));
+        }
+
+        cancelled
+    }
+
+    /// Amend a resting order's price and/or remaining quantity
+    ///
+    /// Reducing the quantity at the same price keeps time priority. Any price
+    /// change or quantity increase moves the order to the back of its (new)
+    /// price level. Amends never match: a new price that would cross the
+    /// opposite side is rejected with `WouldCross`, and one that would lock
+    /// it is subject to the configured `LockedBookPolicy`. A new price between
+    /// ticks is handled by the configured `TickPolicy`.
+    ///
+    /// # Time Complexity
+    /// O(log P + K) where K is the number of orders at the original price level
+    pub fn amend_order(
+        &mut self,
+        order_id: OrderId,
+        new_price: Price,
+        new_quantity: Quantity,
+    ) -> Result<(), OrderBookError> {
+        if new_price == 0 {
+            return Err(OrderBookError::InvalidPrice);
+        }
+        if new_quantity == 0 {
+            return Err(OrderBookError::InvalidQuantity);
+        }
+        let new_price = self.check_tick(new_price)?;
+        self.check_notional(new_price, new_quantity)?;
+
+        let metadata = self
+            .order_index
+            .get(&order_id)
+            .ok_or_else(|| self.archive.missing(order_id))?;
+
+        match metadata.status {
+            OrderStatus::Cancelled => return Err(OrderBookError::OrderAlreadyCancelled(order_id)),
+            OrderStatus::Filled => return Err(OrderBookError::OrderAlreadyFilled(order_id)),
+            _ => {}
+        }
+
+        if metadata.pegged {
+            return Err(OrderBookError::PeggedOrder(order_id));
+        }
+        let side = metadata.side;
+        let old_price = metadata.price;
+        let old_shown = metadata.remaining_quantity;
+        let old_remaining = metadata.total_remaining();
+
+        if new_price != old_price {
+            self.check_resting_price(side, new_price)?;
+        }
+
+        // Size reduction at the same price keeps queue position; an iceberg
+        // gives up its reserve before its shown slice
+        if new_price == old_price && new_quantity <= old_remaining {
+            let new_shown = old_shown.min(new_quantity);
+            let book = match side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+            if let Some(level) = book.get_mut(&old_price) {
+                if let Some(order) = level.orders.iter_mut().find(|o| o.id == order_id) {
+                    order.original_quantity -= old_remaining - new_quantity;
+                    order.remaining_quantity = new_shown;
+                    order.hidden_quantity = new_quantity - new_shown;
+                }
+                level.update_quantity(old_shown - new_shown);
+            }
+            if let Some(metadata) = self.order_index.get_mut(&order_id) {
+                metadata.remaining_quantity = new_shown;
+                metadata.hidden_quantity = new_quantity - new_shown;
+            }
+            self.poll_depth_updates();
+            return Ok(());
+        }
+
+        // Anything else loses priority
+        if let Some(mut order) = self.remove_from_level(side, old_price, order_id) {
+            // Filled, or cancelled over `max_rest_quantity` when placed
+            let done = order.original_quantity - old_remaining;
+            order.price = new_price;
+            order.original_quantity = done + new_quantity;
+            order.remaining_quantity = new_quantity;
+            order.hidden_quantity = 0;
+            order.timestamp = self.clock.now();
+            self.add_to_book(order);
+        }
+        self.poll_depth_updates();
+
+        Ok(())
+    }
+
+    /// Change a resting order's remaining quantity, keeping its price
+    ///
+    /// A decrease keeps the order's place in the queue. An increase re-queues
+    /// the whole order at the back of its level, behind every order already
+    /// there, as if the extra quantity were a new arrival.
+    pub fn modify_quantity(
+        &mut self,
+        order_id: OrderId,
+        new_quantity: Quantity,
+    ) -> Result<(), OrderBookError> {
+        let price = self
+            .order_index
+            .get(&order_id)
+            .map(|m| m.price)
+            .ok_or_else(|| self.archive.missing(order_id))?;
+        self.amend_order(order_id, price, new_quantity)
+    }
+
+    /// Move a resting order to the back of its price level
+    ///
+    /// The order keeps its price and size and stays live, but its timestamp
+    /// is reset from the clock and it loses time priority to every order
+    /// already at the level.
+    ///
+    /// # Time Complexity
+    /// O(log P + K) where K is the number of orders at the order's price level
+    pub fn refresh_order(&mut self, order_id: OrderId) -> Result<(), OrderBookError> {
+        let metadata = self
+            .order_index
+            .get(&order_id)
+            .ok_or_else(|| self.archive.missing(order_id))?;
+
+        match metadata.status {
+            OrderStatus::Cancelled => return Err(OrderBookError::OrderAlreadyCancelled(order_id)),
+            OrderStatus::Filled => return Err(OrderBookError::OrderAlreadyFilled(order_id)),
+            _ => {}
+        }
+        if metadata.pegged {
+            return Err(OrderBookError::PeggedOrder(order_id));
+        }
+
+        if let Some(mut order) = self.remove_from_level(metadata.side, metadata.price, order_id) {
+            order.timestamp = self.clock.now();
+            self.add_to_book(order);
+        }
+        Ok(())
+    }
+
+    /// Check that an order could rest at `price` without matching
+    ///
+    /// A price equal to the opposite best would trade if submitted, but this
+    /// path doesn't match, so it locks the book instead, subject to the
+    /// configured `LockedBookPolicy`.
+    fn check_resting_price(&self, side: Side, price: Price) -> Result<(), OrderBookError> {
+        match self.best_live_price(side.opposite()) {
+            Some(best) if best == price => match self.config.locked_book_policy {
+                LockedBookPolicy::Allow => Ok(()),
+                LockedBookPolicy::Reject => Err(OrderBookError::WouldLock),
+            },
+            Some(best) if side.reaches(price, best) => Err(OrderBookError::WouldCross),
+            _ => Ok(()),
+        }
+    }
+
+    /// Best price on a side that still has live (non-cancelled) quantity
+    fn best_live_price(&self, side: Side) -> Option<Price> {
+        self.live_levels(side).next().map(|(price, _)| price)
+    }
+
+    /// Remove a live order from its price level, dropping the level if it drains
+    ///
+    /// The order stays in the index; callers either re-add it or drop it.
+    fn remove_from_level(
+        &mut self,
+        side: Side,
         price: Price,
-        quantity: Quantity,
-        timestamp: Timestamp,
-    ) -> Order {
-        Order::with_timestamp(
-            id,
-            user_id.to_string(),
-            "market1".to_string(),
-            "YES".to_string(),
-            side,
-            price,
-            quantity,
-            timestamp,
+        order_id: OrderId,
+    ) -> Option<BookOrder> {
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        let level = book.get_mut(&price)?;
+        let position = level.orders.iter().position(|o| o.id == order_id)?;
+        let order = level.orders.remove(position)?;
+        level.update_quantity(order.remaining_quantity);
+        self.remove_drained_level(side, price);
+
+        Some(order)
+    }
+
+    /// Remove the level at `price` once no live quantity is left there
+    ///
+    /// Cancelled orders still queued at the level go with it, as lazy
+    /// deletion would drop them, so every level in the price maps has live
+    /// quantity and the best prices and level counts read straight off them.
+    /// Each queued order is dropped once, so cancels stay amortized O(1).
+    fn remove_drained_level(&mut self, side: Side, price: Price) {
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        if book.get(&price).is_none_or(|level| level.total_quantity > 0) {
+            return;
+        }
+        if let Some(level) = book.remove(&price) {
+            let dropped: Vec<OrderId> = level.orders.iter().map(|o| o.id).collect();
+            self.queued_cancelled -= dropped.len();
+            self.archive_orders(&dropped);
+        }
+    }
+
+    /// Force cleanup of a cancelled order and its price level if empty
+    ///
+    /// This is optional - cancelled orders are naturally cleaned up during matching.
+    /// Use this for explicit cleanup when needed.
+    ///
+    /// # Time Complexity
+    /// O(N) where N is the number of orders at the price level
+    pub fn cleanup_cancelled_order(&mut self, order_id: OrderId) -> Result<(), OrderBookError> {
+        let metadata = self
+            .order_index
+            .get(&order_id)
+            .ok_or_else(|| self.archive.missing(order_id))?;
+
+        if metadata.status != OrderStatus::Cancelled {
+            return Ok(()); // Nothing to clean up
+        }
+
+        let price = metadata.price;
+        let book = match metadata.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        // Level totals already exclude cancelled orders, so only the queue changes
+        if let Some(level) = book.get_mut(&price) {
+            let queued = level.orders.len();
+            level.orders.retain(|o| o.id != order_id);
+            self.queued_cancelled -= queued - level.orders.len();
+            if level.is_empty() {
+                book.remove(&price);
+            }
+        }
+        if self.archive.enabled() {
+            self.archive_orders(&[order_id]);
+        } else {
+            self.order_index.remove(&order_id);
+        }
+
+        Ok(())
+    }
+
+    /// Remove every live order from the book and return them
+    ///
+    /// Orders come back bids first, then asks, each side in priority order
+    /// (best price first, then time), with their timestamps, quantities and
+    /// status intact, followed by midpoint orders, bids then asks, in time
+    /// order. Submitting them in that order to an empty book with the same
+    /// configuration reproduces this book's levels and queue priority.
+    ///
+    /// Afterwards the book is empty and forgets every order it has seen, so
+    /// drained ids may be resubmitted. Quotes are dropped; trade history,
+    /// statistics and the order and trade id counters are kept (see
+    /// `reset_statistics`). No cancel events are emitted.
+    ///
+    /// # Time Complexity
+    /// O(N) where N is the number of queued orders
+    pub fn drain(&mut self) -> Vec<Order> {
+        let bids = std::mem::take(&mut self.bids);
+        let asks = std::mem::take(&mut self.asks);
+        let orders = bids
+            .into_values()
+            .rev()
+            .chain(asks.into_values())
+            .flat_map(|level| level.orders)
+            .filter(|order| {
+                self.order_index
+                    .get(&order.id)
+                    .is_some_and(|m| m.status != OrderStatus::Cancelled)
+            })
+            .chain(self.midpoint_orders.take_all())
+            .map(|order| order.to_order(&self.market_id, &self.outcome_id))
+            .collect();
+
+        self.order_index.clear();
+        self.archive.clear();
+        self.user_orders.clear();
+        self.client_orders.clear();
+        self.quotes.clear();
+        self.live_orders = 0;
+        self.queued_cancelled = 0;
+        self.poll_depth_updates();
+        orders
+    }
+
+    /// Zero the trade and cancel statistics
+    ///
+    /// Resets the public totals, the last trade price, the fill size
+    /// histogram, the volatility window, the TWAP price history, per-user
+    /// activity and counterparty volumes. Trade history is kept.
+    pub fn reset_statistics(&mut self) {
+        self.total_trades = 0;
+        self.total_volume = 0;
+        self.total_notional = 0;
+        self.total_cancelled_orders = 0;
+        self.total_cancelled_volume = 0;
+        self.last_trade_price = None;
+        self.fill_histogram = self.config.fill_size_buckets.clone().map(FillSizeHistogram::new);
+        self.price_window = self.config.volatility_window.map(analytics::PriceWindow::new);
+        self.price_history = self.config.twap_horizon.map(analytics::PriceHistory::new);
+        self.counterparty_volumes = self.config.track_counterparties.then(HashMap::new);
+        self.user_activity.clear();
+    }
+
+    /// Ratio of a user's cancelled orders to the trades they took part in
+    ///
+    /// Every cancellation counts, including those made by self-trade
+    /// prevention or last look. A high ratio is a classic spoofing signal.
+    /// Returns None if the user has no fills (the ratio is undefined).
+    pub fn cancel_fill_ratio(&self, user_id: &str) -> Option<f64> {
+        let activity = self.user_activity.get(user_id)?;
+        (activity.fills > 0).then(|| activity.cancels as f64 / activity.fills as f64)
+    }
+
+    /// Get order status
+    ///
+    /// Finished orders that have been archived report their final status.
+    pub fn get_order_status(&self, order_id: OrderId) -> Option<OrderStatus> {
+        self.order_index
+            .get(&order_id)
+            .map(|m| m.status)
+            .or_else(|| self.archive.get(order_id).map(|order| order.status))
+    }
+
+    /// Whether an order is live on the book: resting at a level (or in the
+    /// midpoint queue) with quantity left to trade
+    ///
+    /// False for filled, cancelled and unknown orders. A cancelled order still
+    /// queued under lazy deletion is not resting.
+    pub fn is_resting(&self, order_id: OrderId) -> bool {
+        self.order_index.get(&order_id).is_some_and(|m| {
+            matches!(m.status, OrderStatus::Open | OrderStatus::PartiallyFilled)
+                && m.total_remaining() > 0
+        })
+    }
+
+    /// Get remaining quantity for an order
+    pub fn get_order_remaining(&self, order_id: OrderId) -> Option<Quantity> {
+        self.order_index.get(&order_id).map(OrderMetadata::total_remaining)
+    }
+
+    /// Timestamp of the oldest live order at a price level
+    ///
+    /// Levels are FIFO, so this is the front order once cancelled orders are
+    /// skipped. Returns None if the level doesn't exist or holds no live orders.
+    pub fn oldest_order_timestamp(&self, side: Side, price: Price) -> Option<Timestamp> {
+        let book = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        book.get(&price)
+            .and_then(|level| self.front_live_order(level))
+            .map(|order| order.timestamp)
+    }
+
+    /// Levels whose oldest live order is more than `older_than` before `now`
+    ///
+    /// Returns `(price, oldest_timestamp)` pairs in priority order (best first).
+    pub fn stalest_levels(
+        &self,
+        side: Side,
+        older_than: Timestamp,
+        now: Timestamp,
+    ) -> Vec<(Price, Timestamp)> {
+        let cutoff = now.saturating_sub(older_than);
+        let levels: Box<dyn Iterator<Item = (&Price, &PriceLevelQueue)>> = match side {
+            Side::Buy => Box::new(self.bids.iter().rev()),
+            Side::Sell => Box::new(self.asks.iter()),
+        };
+
+        levels
+            .filter_map(|(&price, level)| {
+                self.front_live_order(level)
+                    .filter(|order| order.timestamp < cutoff)
+                    .map(|order| (price, order.timestamp))
+            })
+            .collect()
+    }
+
+    /// The resting order on `side` a crossing taker would trade with first
+    ///
+    /// That is the front live order of the best level. With a
+    /// `taker_user_id`, that user's own orders are skipped too, as a taker
+    /// never trades with them. Midpoint orders are not considered, since
+    /// whether they trade depends on the taker's price. Nothing is executed.
+    pub fn next_maker(&self, side: Side, taker_user_id: Option<&str>) -> Option<OrderId> {
+        let levels: Box<dyn Iterator<Item = &PriceLevelQueue>> = match side {
+            Side::Buy => Box::new(self.bids.values().rev()),
+            Side::Sell => Box::new(self.asks.values()),
+        };
+        levels
+            .flat_map(|level| &level.orders)
+            .find(|order| {
+                Some(order.user_id.as_str()) != taker_user_id
+                    && order.remaining_quantity > 0
+                    && self
+                        .order_index
+                        .get(&order.id)
+                        .is_some_and(|m| m.status != OrderStatus::Cancelled)
+            })
+            .map(|order| order.id)
+    }
+
+    /// First order in a level that hasn't been cancelled
+    fn front_live_order<'a>(&self, level: &'a PriceLevelQueue) -> Option<&'a BookOrder> {
+        level.orders.iter().find(|order| {
+            self.order_index
+                .get(&order.id)
+                .is_some_and(|m| m.status != OrderStatus::Cancelled)
+        })
+    }
+
+    /// Live price levels on a side in priority order (best first)
+    ///
+    /// Yields `(price, live quantity)`, skipping levels that only hold cancelled orders.
+    fn live_levels(&self, side: Side) -> Box<dyn Iterator<Item = (Price, Quantity)> + '_> {
+        let levels: Box<dyn Iterator<Item = (&Price, &PriceLevelQueue)>> = match side {
+            Side::Buy => Box::new(self.bids.iter().rev()),
+            Side::Sell => Box::new(self.asks.iter()),
+        };
+        Box::new(
+            levels
+                .filter(|(_, level)| level.total_quantity > 0)
+                .map(|(&price, level)| (price, level.total_quantity)),
         )
     }
 
+    /// Get a snapshot of the top N levels of the order book
+    ///
+    /// At most `max_query_levels` levels per side are returned, if configured.
+    pub fn get_depth(&self, levels: usize) -> Depth {
+        self.depth(self.query_levels(levels))
+    }
+
+    /// A requested level count clamped to `max_query_levels`
+    fn query_levels(&self, levels: usize) -> usize {
+        self.config.max_query_levels.map_or(levels, |max| levels.min(max))
+    }
+
+    /// The top `levels` levels per side, uncapped
+    fn depth(&self, levels: usize) -> Depth {
+        let bids: Vec<(Price, Quantity)> = self
+            .bids
+            .iter()
+            .rev()
+            .take(levels)
+            .map(|(&price, level)| (price, level.total_quantity))
+            .collect();
+
+        let asks: Vec<(Price, Quantity)> = self
+            .asks
+            .iter()
+            .take(levels)
+            .map(|(&price, level)| (price, level.total_quantity))
+            .collect();
+
+        (bids, asks)
+    }
+}
+
+#[cfg(test)]
+mod fuzz;
+#[cfg(test)]
+mod proptests;
+#[cfg(test)]
+pub(crate) mod test_support;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_test_order;
+
     #[test]
     fn test_liquidity_addition() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
@@ -1085,267 +3208,2065 @@ mod tests {
     }
 
     #[test]
-    fn test_cancellation() {
-        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+    fn test_cancellation() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add orders
+        let order1 = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
+        let order2 = create_test_order(2, "user2", Side::Sell, 5000, 100, 2000);
+
+        book.process_limit_order(order1).unwrap();
+        book.process_limit_order(order2).unwrap();
+
+        assert_eq!(book.ask_quantity_at(5000), 200);
+
+        // Cancel first order
+        book.cancel_order(1).unwrap();
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+
+        // Verify the cancelled order is skipped during matching
+        let buy_order = create_test_order(3, "buyer", Side::Buy, 5000, 50, 3000);
+        let result = book.process_limit_order(buy_order).unwrap();
+
+        // Should match with order 2, not the cancelled order 1
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].maker_order_id, 2);
+    }
+
+    #[test]
+    fn test_cancellation_cleanup() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add a single order
+        let order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
+        book.process_limit_order(order).unwrap();
+
+        assert_eq!(book.ask_levels(), 1);
+
+        // Cancel and cleanup
+        book.cancel_order(1).unwrap();
+        book.cleanup_cancelled_order(1).unwrap();
+
+        // Verify empty price level is removed
+        assert_eq!(book.ask_levels(), 0);
+    }
+
+    #[test]
+    fn test_cancelling_last_live_order_removes_level() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "user1", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "user2", Side::Sell, 5100, 50, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "user3", Side::Buy, 4900, 70, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "user4", Side::Sell, 5100, 20, 4)).unwrap();
+
+        // The 5000 level had nothing else live; 5100 keeps order 4
+        book.cancel_order(1).unwrap();
+        book.cancel_order(2).unwrap();
+        assert_eq!(book.get_depth(5), (vec![(4900, 70)], vec![(5100, 20)]));
+        assert_eq!((book.best_ask(), book.spread(), book.ask_levels()), (Some(5100), Some(200), 1));
+        assert_eq!(book.frozen().get_depth(5), book.get_depth(5));
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+
+        // Order 2 stays queued behind no one, and goes with its level
+        assert_eq!(book.metrics().pending_cancelled, 1);
+        book.cancel_order(4).unwrap();
+        assert_eq!((book.best_ask(), book.ask_levels()), (None, 0));
+        assert_eq!(book.metrics().pending_cancelled, 0);
+        assert_eq!(book.cleanup_cancelled_order(2), Ok(()));
+    }
+
+    #[test]
+    fn test_cancel_nonexistent_order() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let result = book.cancel_order(999);
+        assert_eq!(result, Err(OrderBookError::OrderNotFound(999)));
+    }
+
+    #[test]
+    fn test_cancel_already_cancelled() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
+        book.process_limit_order(order).unwrap();
+        book.cancel_order(1).unwrap();
+
+        let result = book.cancel_order(1);
+        assert_eq!(result, Err(OrderBookError::OrderAlreadyCancelled(1)));
+    }
+
+    #[test]
+    fn test_cancel_filled_order() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add and fill an order
+        let sell_order = create_test_order(1, "seller", Side::Sell, 5000, 100, 1000);
+        book.process_limit_order(sell_order).unwrap();
+
+        let buy_order = create_test_order(2, "buyer", Side::Buy, 5000, 100, 2000);
+        book.process_limit_order(buy_order).unwrap();
+
+        // Try to cancel the filled order
+        let result = book.cancel_order(1);
+        assert_eq!(result, Err(OrderBookError::OrderAlreadyFilled(1)));
+    }
+
+    #[test]
+    fn test_self_trading_prevention() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add a sell order
+        let sell_order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
+        book.process_limit_order(sell_order).unwrap();
+
+        // Try to match with own order
+        let buy_order = create_test_order(2, "user1", Side::Buy, 5000, 100, 2000);
+        let result = book.process_limit_order(buy_order).unwrap();
+
+        // No trades should occur
+        assert_eq!(result.trades.len(), 0);
+        assert_eq!(result.order.remaining_quantity, 100);
+
+        // The resting sell is cancelled instead, so the user's book isn't locked
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+        assert_eq!(book.get_order_status(2), Some(OrderStatus::Open));
+        assert_eq!(book.best_bid(), Some(5000));
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn test_self_trade_cancel_resting_keeps_matching() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "user1", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "user2", Side::Sell, 5000, 100, 2)).unwrap();
+
+        // user1's own ask is cancelled and the buy fills against user2 behind it
+        let buy_order = create_test_order(3, "user1", Side::Buy, 5000, 150, 3);
+        let result = book.process_limit_order(buy_order).unwrap();
+
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].maker_order_id, 2);
+        assert_eq!(result.order.remaining_quantity, 50);
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+        assert_eq!(book.best_bid(), Some(5000));
+        assert_eq!(book.ask_quantity_at(5000), 0);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_across_levels() {
+        // The taker's own asks sit at the second and third levels, each ahead
+        // of another user's ask
+        fn sweep_book(config: OrderBookConfig) -> OrderBook {
+            let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+            for (id, user, price, quantity) in [
+                (1, "user2", 5000, 30),
+                (2, "user1", 5100, 40),
+                (3, "user3", 5100, 20),
+                (4, "user1", 5200, 10),
+                (5, "user4", 5200, 50),
+            ] {
+                let order = create_test_order(id, user, Side::Sell, price, quantity, id);
+                book.process_limit_order(order).unwrap();
+            }
+            book
+        }
+
+        for matching_mode in [
+            MatchingMode::PriceTime,
+            MatchingMode::ProRata,
+            MatchingMode::Hybrid { fifo_portion: 5 },
+        ] {
+            let mut book = sweep_book(OrderBookConfig { matching_mode, ..Default::default() });
+            let buy_order = create_test_order(6, "user1", Side::Buy, 5200, 100, 6);
+            let result = book.process_limit_order(buy_order).unwrap();
+
+            // Every other user's ask fills, and each of the user's own is cancelled
+            let fills: Vec<_> =
+                result.trades.iter().map(|t| (t.maker_order_id, t.price, t.quantity)).collect();
+            let expected = vec![(1, 5000, 30), (3, 5100, 20), (5, 5200, 50)];
+            assert_eq!(fills, expected, "{:?}", matching_mode);
+            assert_eq!(result.order.status, OrderStatus::Filled);
+            assert_eq!(book.get_order_status(2), Some(OrderStatus::Cancelled));
+            assert_eq!(book.get_order_status(4), Some(OrderStatus::Cancelled));
+            assert_eq!(book.best_ask(), None);
+        }
+
+        // Netting uses up the taker's size on its own asks as it goes
+        let config = OrderBookConfig {
+            self_trade_policy: SelfTradePolicy::DecrementAndCancel,
+            ..Default::default()
+        };
+        let mut book = sweep_book(config);
+        let buy_order = create_test_order(6, "user1", Side::Buy, 5200, 100, 6);
+        let result = book.process_limit_order(buy_order).unwrap();
+        let fills: Vec<_> = result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(1, 30), (3, 20)]);
+        assert_eq!(result.order.remaining_quantity, 0);
+        assert_eq!(book.get_order_status(2), Some(OrderStatus::Cancelled));
+        assert_eq!(book.get_order_status(4), Some(OrderStatus::Cancelled));
+        assert_eq!(book.get_order_remaining(5), Some(50));
+    }
+
+    #[test]
+    fn test_self_trade_skips_stacked_orders_at_one_level() {
+        for matching_mode in [
+            MatchingMode::PriceTime,
+            MatchingMode::ProRata,
+            MatchingMode::Hybrid { fifo_portion: 5 },
+        ] {
+            let config = OrderBookConfig { matching_mode, ..Default::default() };
+            let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+            for id in 1..=3 {
+                let order = create_test_order(id, "user1", Side::Sell, 5000, 20, id);
+                book.process_limit_order(order).unwrap();
+            }
+            let other = create_test_order(4, "user2", Side::Sell, 5000, 40, 4);
+            book.process_limit_order(other).unwrap();
+
+            let result = book
+                .process_limit_order(create_test_order(5, "user1", Side::Buy, 5000, 50, 5))
+                .unwrap();
+            let fills: Vec<_> =
+                result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+            assert_eq!(fills, vec![(4, 40)], "{:?}", matching_mode);
+            for id in 1..=3 {
+                assert_eq!(book.get_order_status(id), Some(OrderStatus::Cancelled));
+            }
+            assert_eq!(book.best_ask(), None);
+            assert_eq!(book.live_levels(Side::Buy).collect::<Vec<_>>(), vec![(5000, 10)]);
+            assert!(book.is_healthy());
+        }
+    }
+
+    #[test]
+    fn test_self_trade_reject_taker() {
+        let config = OrderBookConfig {
+            self_trade_policy: SelfTradePolicy::RejectTaker,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        book.process_limit_order(create_test_order(1, "user2", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "user1", Side::Sell, 5100, 100, 2)).unwrap();
+
+        let buy_order = create_test_order(3, "user1", Side::Buy, 5100, 150, 3);
+        assert_eq!(book.process_limit_order(buy_order).unwrap_err(), OrderBookError::WouldSelfCross);
+
+        // Nothing matched and both asks are untouched
+        assert_eq!(book.get_order_remaining(1), Some(100));
+        assert_eq!(book.get_order_status(2), Some(OrderStatus::Open));
+
+        // A buy that doesn't reach the user's own ask is fine
+        let buy_order = create_test_order(4, "user1", Side::Buy, 5000, 50, 4);
+        assert_eq!(book.process_limit_order(buy_order).unwrap().trades.len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_order_id() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let order1 = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
+        book.process_limit_order(order1).unwrap();
+
+        let order2 = create_test_order(1, "user2", Side::Sell, 5500, 100, 2000);
+        let result = book.process_limit_order(order2);
+
+        assert!(matches!(result, Err(OrderBookError::DuplicateOrderId(1))));
+    }
+
+    #[test]
+    fn test_invalid_price() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let order = create_test_order(1, "user1", Side::Sell, 0, 100, 1000);
+        let result = book.process_limit_order(order);
+
+        assert!(matches!(result, Err(OrderBookError::InvalidPrice)));
+    }
+
+    #[test]
+    fn test_invalid_quantity() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let mut order = create_test_order(1, "user1", Side::Sell, 5000, 0, 1000);
+        order.remaining_quantity = 0;
+        let result = book.process_limit_order(order);
+
+        assert!(matches!(result, Err(OrderBookError::InvalidQuantity)));
+    }
+
+    #[test]
+    fn test_market_mismatch() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let mut order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
+        order.market_id = "market2".to_string();
+        let result = book.process_limit_order(order);
+
+        assert!(matches!(result, Err(OrderBookError::MarketMismatch)));
+    }
+
+    #[test]
+    fn test_bid_priority_highest_first() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add buy orders at different prices
+        let buy_low = create_test_order(1, "buyer1", Side::Buy, 5000, 100, 1000);
+        let buy_high = create_test_order(2, "buyer2", Side::Buy, 6000, 100, 2000);
+
+        book.process_limit_order(buy_low).unwrap();
+        book.process_limit_order(buy_high).unwrap();
+
+        // Sell order should match with highest bid first
+        let sell_order = create_test_order(3, "seller", Side::Sell, 5000, 150, 3000);
+        let result = book.process_limit_order(sell_order).unwrap();
+
+        // Verify: highest bid matches first
+        assert_eq!(result.trades.len(), 2);
+        assert_eq!(result.trades[0].price, 6000);
+        assert_eq!(result.trades[0].maker_order_id, 2);
+        assert_eq!(result.trades[1].price, 5000);
+        assert_eq!(result.trades[1].maker_order_id, 1);
+    }
+
+    #[test]
+    fn test_get_depth() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add bids
+        let bid1 = create_test_order(1, "user1", Side::Buy, 5000, 100, 1000);
+        let bid2 = create_test_order(2, "user2", Side::Buy, 5100, 200, 2000);
+        let bid3 = create_test_order(3, "user3", Side::Buy, 5200, 150, 3000);
+
+        // Add asks
+        let ask1 = create_test_order(4, "user4", Side::Sell, 5500, 100, 4000);
+        let ask2 = create_test_order(5, "user5", Side::Sell, 5600, 200, 5000);
+
+        book.process_limit_order(bid1).unwrap();
+        book.process_limit_order(bid2).unwrap();
+        book.process_limit_order(bid3).unwrap();
+        book.process_limit_order(ask1).unwrap();
+        book.process_limit_order(ask2).unwrap();
+
+        let (bids, asks) = book.get_depth(2);
+
+        // Bids should be highest first
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[0], (5200, 150));
+        assert_eq!(bids[1], (5100, 200));
+
+        // Asks should be lowest first
+        assert_eq!(asks.len(), 2);
+        assert_eq!(asks[0], (5500, 100));
+        assert_eq!(asks[1], (5600, 200));
+    }
+
+    #[test]
+    fn test_depth_queries_clamped_to_max_query_levels() {
+        let config = OrderBookConfig { max_query_levels: Some(2), ..Default::default() };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        for i in 0..5 {
+            let bid = create_test_order(i + 1, "buyer", Side::Buy, 4000 + 100 * i, 10, i);
+            let ask = create_test_order(i + 11, "seller", Side::Sell, 6000 + 100 * i, 10, i);
+            book.process_limit_order(bid).unwrap();
+            book.process_limit_order(ask).unwrap();
+        }
+
+        let (bids, asks) = book.get_depth(usize::MAX);
+        assert_eq!(bids, vec![(4400, 10), (4300, 10)]);
+        assert_eq!(asks, vec![(6000, 10), (6100, 10)]);
+        assert_eq!(book.get_depth(1).0, vec![(4400, 10)]);
+        assert_eq!(book.depth_checksum(1_000_000), book.depth_checksum(2));
+
+        // Full exports are not queries
+        assert_eq!(book.frozen().get_depth(usize::MAX).0.len(), 5);
+        assert_eq!(book.snapshot().bids.len(), 5);
+    }
+
+    #[test]
+    fn test_best_order_skips_cancelled_fronts() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        assert_eq!((book.best_bid_order(), book.best_ask_order()), (None, None));
+
+        book.process_limit_order(create_test_order(1, "user1", Side::Buy, 5000, 10, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "user2", Side::Buy, 5000, 10, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "user3", Side::Buy, 4900, 10, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "user4", Side::Sell, 5200, 10, 4)).unwrap();
+        assert_eq!((book.best_bid_order(), book.best_ask_order()), (Some(1), Some(4)));
+
+        // Next in the same level, then the next level
+        book.cancel_order(1).unwrap();
+        assert_eq!(book.best_bid_order(), Some(2));
+        book.cancel_order(2).unwrap();
+        assert_eq!(book.best_bid_order(), Some(3));
+        book.cancel_order(4).unwrap();
+        assert_eq!(book.best_ask_order(), None);
+    }
+
+    #[test]
+    fn test_is_resting() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 5000, 100, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "buyer", Side::Buy, 5000, 150, 3)).unwrap();
+
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Filled));
+        assert!(!book.is_resting(1));
+        assert_eq!(book.get_order_status(2), Some(OrderStatus::PartiallyFilled));
+        assert!(book.is_resting(2));
+        // The taker filled completely and never rested
+        assert!(!book.is_resting(3));
+        assert!(!book.is_resting(99));
+
+        book.cancel_order(2).unwrap();
+        assert!(!book.is_resting(2));
+    }
+
+    #[test]
+    fn test_clone_runs_what_if_without_touching_original() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 5100, 100, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "buyer", Side::Buy, 4900, 50, 3)).unwrap();
+        let events = Arc::new(std::sync::Mutex::new(0));
+        let sink = Arc::clone(&events);
+        book.add_listener(Box::new(move |_| *sink.lock().unwrap() += 1));
+        let before = book.snapshot().encode();
+
+        let mut what_if = book.clone();
+        let sweep = create_test_order(4, "trader", Side::Buy, 5100, 150, 4);
+        let simulated = what_if.process_limit_order(sweep.clone()).unwrap();
+        assert_eq!(simulated.trades.len(), 2);
+        assert_eq!(what_if.get_depth(5), (vec![(4900, 50)], vec![(5100, 50)]));
+
+        // The original and its listener saw nothing
+        assert_eq!(book.snapshot().encode(), before);
+        assert_eq!(*events.lock().unwrap(), 0);
+        assert_eq!(book.metrics().total_trades, 0);
+        assert_eq!(book.get_order_remaining(1), Some(100));
+
+        // Sent for real, the order does exactly what the clone showed
+        let real = book.process_limit_order(sweep).unwrap();
+        let fills = |result: &ProcessOrderResult| {
+            result.trades.iter().map(|t| (t.id, t.maker_order_id, t.quantity)).collect::<Vec<_>>()
+        };
+        assert_eq!(fills(&real), fills(&simulated));
+        assert_eq!(book.get_depth(5), what_if.get_depth(5));
+        assert_eq!(*events.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_level_cancel_ratio() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        for id in 1..=4 {
+            let order = create_test_order(id, "seller", Side::Sell, 5000, 10, id);
+            book.process_limit_order(order).unwrap();
+        }
+        assert_eq!(book.level_cancel_ratio(Side::Sell, 5000), Some(0.0));
+
+        book.cancel_order(2).unwrap();
+        book.cancel_order(4).unwrap();
+        assert_eq!(book.level_cancel_ratio(Side::Sell, 5000), Some(0.5));
+        assert_eq!(book.level_cancel_ratio(Side::Buy, 5000), None);
+        assert_eq!(book.level_cancel_ratio(Side::Sell, 5100), None);
+
+        book.cleanup_cancelled_order(2).unwrap();
+        assert_eq!(book.level_cancel_ratio(Side::Sell, 5000), Some(1.0 / 3.0));
+    }
+
+    #[test]
+    fn test_statistics() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add and match orders
+        let sell = create_test_order(1, "seller", Side::Sell, 5000, 100, 1000);
+        book.process_limit_order(sell).unwrap();
+
+        let buy = create_test_order(2, "buyer", Side::Buy, 5000, 100, 2000);
+        book.process_limit_order(buy).unwrap();
+
+        assert_eq!(book.total_trades, 1);
+        assert_eq!(book.total_volume, 100);
+    }
+
+    #[test]
+    fn test_large_order_multiple_makers() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add 5 sell orders at same price
+        for i in 1..=5 {
+            let order = create_test_order(i, &format!("seller{}", i), Side::Sell, 5000, 100, i * 1000);
+            book.process_limit_order(order).unwrap();
+        }
+
+        assert_eq!(book.ask_quantity_at(5000), 500);
+
+        // Large buy order
+        let buy = create_test_order(10, "buyer", Side::Buy, 5000, 350, 10000);
+        let result = book.process_limit_order(buy).unwrap();
+
+        // Should have 4 trades (3 full + 1 partial)
+        assert_eq!(result.trades.len(), 4);
+        assert_eq!(result.order.status, OrderStatus::Filled);
+
+        // Verify FIFO order
+        assert_eq!(result.trades[0].maker_order_id, 1);
+        assert_eq!(result.trades[1].maker_order_id, 2);
+        assert_eq!(result.trades[2].maker_order_id, 3);
+        assert_eq!(result.trades[3].maker_order_id, 4);
+        assert_eq!(result.trades[3].quantity, 50);
+
+        // Remaining on book
+        assert_eq!(book.ask_quantity_at(5000), 150); // 50 from order 4 + 100 from order 5
+    }
+
+    #[test]
+    fn test_no_match_price_gap() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Add sell order at high price
+        let sell = create_test_order(1, "seller", Side::Sell, 7000, 100, 1000);
+        book.process_limit_order(sell).unwrap();
+
+        // Add buy order at low price (no match)
+        let buy = create_test_order(2, "buyer", Side::Buy, 5000, 100, 2000);
+        let result = book.process_limit_order(buy).unwrap();
+
+        assert_eq!(result.trades.len(), 0);
+        assert_eq!(book.bid_levels(), 1);
+        assert_eq!(book.ask_levels(), 1);
+        assert_eq!(book.spread(), Some(2000));
+    }
+
+    #[test]
+    fn test_amend_reduce_keeps_priority() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "seller1", Side::Sell, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller2", Side::Sell, 5000, 100, 2000)).unwrap();
+
+        // Shrinking in place keeps order 1 at the front
+        book.amend_order(1, 5000, 40).unwrap();
+        assert_eq!(book.ask_quantity_at(5000), 140);
+
+        let result = book.process_limit_order(create_test_order(3, "buyer", Side::Buy, 5000, 50, 3000)).unwrap();
+        assert_eq!(result.trades[0].maker_order_id, 1);
+        assert_eq!(result.trades[0].quantity, 40);
+        assert_eq!(result.trades[1].maker_order_id, 2);
+    }
+
+    #[test]
+    fn test_amend_increase_loses_priority() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "seller1", Side::Sell, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller2", Side::Sell, 5000, 100, 2000)).unwrap();
+
+        book.amend_order(1, 5000, 150).unwrap();
+        assert_eq!(book.ask_quantity_at(5000), 250);
+
+        let result = book.process_limit_order(create_test_order(3, "buyer", Side::Buy, 5000, 50, 3000)).unwrap();
+        assert_eq!(result.trades[0].maker_order_id, 2);
+    }
+
+    #[test]
+    fn test_amend_reprice_moves_level() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "buyer", Side::Buy, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 6000, 100, 2000)).unwrap();
+
+        book.amend_order(1, 5500, 100).unwrap();
+        assert_eq!(book.best_bid(), Some(5500));
+        assert_eq!(book.bid_levels(), 1);
+
+        // Repricing through the ask is never allowed
+        assert_eq!(book.amend_order(1, 6100, 100), Err(OrderBookError::WouldCross));
+    }
+
+    #[test]
+    fn test_reprice_to_best_ask_would_lock() {
+        let config = OrderBookConfig {
+            locked_book_policy: LockedBookPolicy::Reject,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        book.process_limit_order(create_test_order(1, "buyer", Side::Buy, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 6000, 100, 2000)).unwrap();
+
+        assert_eq!(book.amend_order(1, 6000, 100), Err(OrderBookError::WouldLock));
+        assert_eq!(book.best_bid(), Some(5000));
+        assert_eq!(book.spread(), Some(1000));
+    }
+
+    #[test]
+    fn test_reprice_to_best_ask_allowed_by_default() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "buyer", Side::Buy, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 6000, 100, 2000)).unwrap();
+
+        book.amend_order(1, 6000, 100).unwrap();
+        assert_eq!(book.best_bid(), Some(6000));
+        assert_eq!(book.spread(), None);
+    }
+
+    #[test]
+    fn test_recent_trades_bounded() {
+        let config = OrderBookConfig {
+            trade_history_capacity: 3,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        for i in 0..5 {
+            let sell = create_test_order(i * 2 + 1, "seller", Side::Sell, 5000, 10, i * 2);
+            let buy = create_test_order(i * 2 + 2, "buyer", Side::Buy, 5000, 10, i * 2 + 1);
+            book.process_limit_order(sell).unwrap();
+            book.process_limit_order(buy).unwrap();
+        }
+
+        let ids: Vec<TradeId> = book.recent_trades(10).map(|t| t.id).collect();
+        assert_eq!(ids, vec![3, 4, 5]);
+
+        let ids: Vec<TradeId> = book.recent_trades(2).map(|t| t.id).collect();
+        assert_eq!(ids, vec![4, 5]);
+    }
+
+    #[test]
+    fn test_recent_trades_disabled_by_default() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 10, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "buyer", Side::Buy, 5000, 10, 2000)).unwrap();
+
+        assert_eq!(book.total_trades, 1);
+        assert_eq!(book.recent_trades(10).count(), 0);
+    }
+
+    #[test]
+    fn test_cancel_user_orders() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "maker", Side::Buy, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "maker", Side::Sell, 6000, 100, 2000)).unwrap();
+        book.process_limit_order(create_test_order(3, "other", Side::Buy, 5000, 100, 3000)).unwrap();
+
+        assert_eq!(book.cancel_user_orders("maker"), vec![1, 2]);
+        assert_eq!(book.bid_quantity_at(5000), 100);
+        assert_eq!(book.active_orders(), 1);
+    }
+
+    #[test]
+    fn test_cancel_user_orders_is_deterministic() {
+        let build = || {
+            let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+            for id in (1..=40).rev() {
+                let side = if id % 2 == 0 { Side::Buy } else { Side::Sell };
+                let price = if side == Side::Buy { 4000 + id } else { 6000 + id };
+                book.process_limit_order(create_test_order(id, "maker", side, price, 10, id))
+                    .unwrap();
+            }
+            book
+        };
+        let (mut first, mut second) = (build(), build());
+        let cancelled = first.cancel_user_orders("maker");
+        assert_eq!(cancelled, second.cancel_user_orders("maker"));
+        assert_eq!(cancelled, (1..=40).collect::<Vec<OrderId>>());
+    }
+
+    #[test]
+    fn test_cancel_stale_users() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "idle", Side::Buy, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "idle", Side::Sell, 6000, 100, 2000)).unwrap();
+        book.process_limit_order(create_test_order(3, "alive", Side::Buy, 4900, 100, 3000)).unwrap();
+
+        book.touch_user("idle", 1_000);
+        book.touch_user("alive", 1_000);
+        book.touch_user("alive", 9_000);
+
+        // Not stale yet at exactly the threshold
+        assert!(book.cancel_stale_users(6_000, 5_000).is_empty());
+
+        assert_eq!(book.cancel_stale_users(10_000, 5_000), vec![1, 2]);
+        assert_eq!(book.get_order_status(3), Some(OrderStatus::Open));
+        assert_eq!(book.active_orders(), 1);
+    }
+
+    #[test]
+    fn test_match_against_specific_maker() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "seller1", Side::Sell, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller2", Side::Sell, 5100, 100, 2000)).unwrap();
+        book.process_limit_order(create_test_order(3, "seller3", Side::Sell, 5100, 100, 3000)).unwrap();
+
+        // Fill against order 3, skipping both the better price and the queue ahead of it
+        let taker = create_test_order(4, "buyer", Side::Buy, 5200, 60, 4000);
+        let trade = book.match_against(taker, 3).unwrap();
+
+        assert_eq!(trade.maker_order_id, 3);
+        assert_eq!(trade.price, 5100);
+        assert_eq!(trade.quantity, 60);
+        assert_eq!(book.get_order_remaining(3), Some(40));
+        assert_eq!(book.get_order_status(3), Some(OrderStatus::PartiallyFilled));
+
+        // Other orders are untouched
+        assert_eq!(book.ask_quantity_at(5000), 100);
+        assert_eq!(book.get_order_remaining(2), Some(100));
+        assert_eq!(book.ask_quantity_at(5100), 140);
+        assert_eq!(book.bid_levels(), 0);
+        assert_eq!(book.total_trades, 1);
+    }
+
+    #[test]
+    fn test_match_against_rejections() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 5100, 100, 2000)).unwrap();
+        book.cancel_order(2).unwrap();
+
+        let taker = |id, user: &str, price| create_test_order(id, user, Side::Buy, price, 10, 3000);
+
+        assert_eq!(
+            book.match_against(taker(10, "buyer", 5000), 99).unwrap_err(),
+            OrderBookError::OrderNotFound(99)
+        );
+        assert_eq!(
+            book.match_against(taker(10, "buyer", 5100), 2).unwrap_err(),
+            OrderBookError::OrderAlreadyCancelled(2)
+        );
+        assert_eq!(
+            book.match_against(taker(10, "buyer", 4900), 1).unwrap_err(),
+            OrderBookError::IncompatiblePrice
+        );
+        assert_eq!(
+            book.match_against(taker(10, "seller", 5000), 1).unwrap_err(),
+            OrderBookError::SelfTrade
+        );
+        assert_eq!(
+            book.match_against(create_test_order(10, "buyer", Side::Sell, 5000, 10, 3000), 1).unwrap_err(),
+            OrderBookError::SameSide
+        );
+
+        // Fully consume the maker, then it can no longer be targeted
+        book.match_against(create_test_order(11, "buyer", Side::Buy, 5000, 100, 4000), 1).unwrap();
+        assert_eq!(
+            book.match_against(taker(12, "buyer", 5000), 1).unwrap_err(),
+            OrderBookError::OrderAlreadyFilled(1)
+        );
+        assert_eq!(book.ask_quantity_at(5000), 0);
+    }
+
+    #[test]
+    fn test_match_against_checks_taker_as_submitted() {
+        let config = OrderBookConfig {
+            max_notional: Some(5000 * 100),
+            tick_size: Some(100),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 100, 1)).unwrap();
+
+        let too_big = create_test_order(2, "buyer", Side::Buy, 5000, 101, 2);
+        assert_eq!(book.match_against(too_big, 1).unwrap_err(), OrderBookError::NotionalExceeded);
+        let off_tick = create_test_order(2, "buyer", Side::Buy, 5050, 10, 2);
+        assert_eq!(book.match_against(off_tick, 1).unwrap_err(), OrderBookError::InvalidTick);
+        let mut duplicate = create_test_order(2, "buyer", Side::Buy, 4000, 10, 2);
+        duplicate.client_order_id = Some("a".to_string());
+        book.process_limit_order(duplicate.clone()).unwrap();
+        duplicate.id = 3;
+        assert_eq!(
+            book.match_against(duplicate, 1).unwrap_err(),
+            OrderBookError::DuplicateClientOrderId("a".to_string())
+        );
+        assert_eq!(book.get_order_remaining(1), Some(100));
+
+        // An accepted direct match takes the next sequence number
+        let before = book.process_limit_order(create_test_order(4, "other", Side::Buy, 4000, 1, 4));
+        book.match_against(create_test_order(5, "buyer", Side::Buy, 5000, 10, 5), 1).unwrap();
+        let after = book.process_limit_order(create_test_order(6, "other", Side::Buy, 4000, 1, 6));
+        assert_eq!(after.unwrap().ack.sequence, before.unwrap().ack.sequence + 2);
+    }
+
+    #[test]
+    fn test_oldest_order_timestamp_skips_cancelled() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "user1", Side::Buy, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "user2", Side::Buy, 5000, 100, 2000)).unwrap();
+
+        assert_eq!(book.oldest_order_timestamp(Side::Buy, 5000), Some(1000));
+        book.cancel_order(1).unwrap();
+        assert_eq!(book.oldest_order_timestamp(Side::Buy, 5000), Some(2000));
+        assert_eq!(book.oldest_order_timestamp(Side::Buy, 4000), None);
+        assert_eq!(book.oldest_order_timestamp(Side::Sell, 5000), None);
+    }
+
+    #[test]
+    fn test_stalest_levels() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "user1", Side::Sell, 6000, 100, 1_000)).unwrap();
+        book.process_limit_order(create_test_order(2, "user2", Side::Sell, 6100, 100, 8_000)).unwrap();
+        book.process_limit_order(create_test_order(3, "user3", Side::Sell, 6200, 100, 2_000)).unwrap();
+
+        // Cutoff is 10_000 - 5_000 = 5_000
+        let stale = book.stalest_levels(Side::Sell, 5_000, 10_000);
+        assert_eq!(stale, vec![(6000, 1_000), (6200, 2_000)]);
+        assert!(book.stalest_levels(Side::Buy, 5_000, 10_000).is_empty());
+    }
+
+    #[test]
+    fn test_mixed_case_outcomes_match() {
+        let mut book = OrderBook::new("market1".to_string(), "Yes".to_string());
+        assert_eq!(book.outcome_id, "YES");
+
+        let sell = Order::with_timestamp(
+            1,
+            "seller".to_string(),
+            "market1".to_string(),
+            "yes".to_string(),
+            Side::Sell,
+            5000,
+            100,
+            1000,
+        );
+        assert_eq!(sell.outcome_id, "YES");
+        book.process_limit_order(sell).unwrap();
+
+        let mut buy = create_test_order(2, "buyer", Side::Buy, 5000, 100, 2000);
+        buy.market_id = "MARKET1".to_string();
+        let result = book.process_limit_order(buy).unwrap();
+
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].outcome_id, "YES");
+        assert_eq!(result.trades[0].market_id, "market1");
+    }
+
+    #[test]
+    fn test_different_outcome_still_mismatches() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let mut order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
+        order.outcome_id = "no".to_string();
+
+        assert!(matches!(book.process_limit_order(order), Err(OrderBookError::MarketMismatch)));
+    }
+
+    #[test]
+    fn test_complementary_outcome_never_matches() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "yes_buyer", Side::Buy, 6000, 100, 1)).unwrap();
+
+        // A NO buy at 4000 complements the YES buy at 6000, but would mint shares
+        let mut no_buy = create_test_order(2, "no_buyer", Side::Buy, 4000, 100, 2);
+        no_buy.outcome_id = "NO".to_string();
+        assert_eq!(
+            book.process_limit_order(no_buy.clone()).unwrap_err(),
+            OrderBookError::MarketMismatch
+        );
+        no_buy.side = Side::Sell;
+        assert_eq!(book.match_against(no_buy, 1).unwrap_err(), OrderBookError::MarketMismatch);
+
+        assert_eq!(book.total_trades, 0);
+        assert_eq!(book.get_order_remaining(1), Some(100));
+    }
+
+    #[test]
+    fn test_pro_rata_remainder_allocation() {
+        let config = OrderBookConfig {
+            matching_mode: MatchingMode::ProRata,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        for i in 1..=3 {
+            let order = create_test_order(i, &format!("seller{}", i), Side::Sell, 5000, 100, i * 1000);
+            book.process_limit_order(order).unwrap();
+        }
+
+        let buy = create_test_order(10, "buyer", Side::Buy, 5000, 101, 10000);
+        let result = book.process_limit_order(buy).unwrap();
+
+        let fills: Vec<(OrderId, Quantity)> =
+            result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(1, 34), (2, 34), (3, 33)]);
+        assert_eq!(result.order.status, OrderStatus::Filled);
+        assert_eq!(book.ask_quantity_at(5000), 199);
+        assert_eq!(book.get_order_remaining(1), Some(66));
+        assert_eq!(book.get_order_remaining(3), Some(67));
+    }
+
+    #[test]
+    fn test_pro_rata_skips_cancelled_and_sweeps_levels() {
+        let config = OrderBookConfig {
+            matching_mode: MatchingMode::ProRata,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        book.process_limit_order(create_test_order(1, "seller1", Side::Sell, 5000, 100, 1000)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller2", Side::Sell, 5000, 300, 2000)).unwrap();
+        book.process_limit_order(create_test_order(3, "seller3", Side::Sell, 5000, 100, 3000)).unwrap();
+        book.process_limit_order(create_test_order(4, "seller4", Side::Sell, 5100, 100, 4000)).unwrap();
+        book.cancel_order(3).unwrap();
+
+        // Clears the 5000 level entirely, then 50 from 5100
+        let buy = create_test_order(10, "buyer", Side::Buy, 5100, 450, 10000);
+        let result = book.process_limit_order(buy).unwrap();
+
+        let fills: Vec<(OrderId, Quantity)> =
+            result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(1, 100), (2, 300), (4, 50)]);
+        assert_eq!(book.ask_quantity_at(5000), 0);
+        assert_eq!(book.ask_quantity_at(5100), 50);
+    }
+
+    #[test]
+    fn test_last_look_rejects_adverse_taker() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        let mut maker = create_test_order(1, "maker", Side::Sell, 5000, 100, 1000);
+        maker.last_look_bps = Some(200);
+        book.process_limit_order(maker).unwrap();
+        book.process_limit_order(create_test_order(2, "other", Side::Sell, 5100, 100, 2000)).unwrap();
+
+        // Benign: limit 5100 is only 100 bps through the maker's 5000
+        let result = book.process_limit_order(create_test_order(3, "buyer", Side::Buy, 5100, 10, 3000)).unwrap();
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].maker_order_id, 1);
+
+        // Adverse: limit 5500 is 500 bps through, so the maker pulls its quote
+        let result = book.process_limit_order(create_test_order(4, "buyer", Side::Buy, 5500, 10, 4000)).unwrap();
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].maker_order_id, 2);
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+        assert_eq!(book.ask_quantity_at(5000), 0);
+    }
+
+    #[test]
+    fn test_order_id_range() {
+        let config = OrderBookConfig {
+            id_range: Some(1000..2000),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        let order = create_test_order(1000, "user1", Side::Buy, 5000, 100, 1);
+        assert!(book.process_limit_order(order).is_ok());
+
+        for id in [999, 2000] {
+            let order = create_test_order(id, "user1", Side::Buy, 5000, 100, 2);
+            assert_eq!(
+                book.process_limit_order(order).unwrap_err(),
+                OrderBookError::OrderIdOutOfRange(id)
+            );
+        }
+        assert_eq!(book.get_order_status(999), None);
+    }
+
+    #[test]
+    fn test_self_trade_decrement_and_cancel() {
+        let config = OrderBookConfig {
+            self_trade_policy: SelfTradePolicy::DecrementAndCancel,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        book.process_limit_order(create_test_order(1, "user1", Side::Sell, 5000, 60, 1)).unwrap();
+
+        let buy_order = create_test_order(2, "user1", Side::Buy, 5000, 100, 2);
+        let result = book.process_limit_order(buy_order).unwrap();
+
+        // 60 netted on both sides, no trade and no volume
+        assert!(result.trades.is_empty());
+        assert_eq!(result.order.remaining_quantity, 40);
+        assert_eq!(result.order.status, OrderStatus::Open);
+        assert_eq!(book.total_trades, 0);
+        assert_eq!(book.total_volume, 0);
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+        assert_eq!(book.bid_quantity_at(5000), 40);
+        assert_eq!(book.ask_quantity_at(5000), 0);
+
+        // A larger resting order is decremented and keeps its place
+        book.process_limit_order(create_test_order(3, "user2", Side::Sell, 5100, 50, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "user2", Side::Buy, 5100, 20, 4)).unwrap();
+        assert_eq!(book.get_order_remaining(3), Some(30));
+        assert_eq!(book.get_order_status(3), Some(OrderStatus::Open));
+        assert_eq!(book.get_order_status(4), None);
+    }
+
+    #[test]
+    fn test_export_trades_csv() {
+        let config = OrderBookConfig {
+            trade_history_capacity: 10,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 10, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 5100, 10, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "buyer", Side::Buy, 5100, 15, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "buyer", Side::Buy, 4900, 5, 4)).unwrap();
+        book.process_limit_order(create_test_order(5, "seller", Side::Sell, 4900, 5, 5)).unwrap();
+
+        let mut out = Vec::new();
+        book.export_trades_csv(&mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "id,timestamp,price,quantity,taker_order_id,maker_order_id,taker_side");
+        assert_eq!(lines.len(), 4);
+
+        // Drop the wall-clock timestamp column before comparing
+        let rows: Vec<String> = lines[1..]
+            .iter()
+            .map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                assert_eq!(fields.len(), 7);
+                assert!(fields[1].parse::<Timestamp>().is_ok());
+                [&fields[..1], &fields[2..]].concat().join(",")
+            })
+            .collect();
+        assert_eq!(rows, vec!["1,5000,10,3,1,BUY", "2,5100,5,3,2,BUY", "3,4900,5,5,4,SELL"]);
+    }
+
+    #[test]
+    fn test_cancel_fill_ratio() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // The spoofer layers and pulls nine bids, and trades once
+        for i in 1..=9 {
+            book.process_limit_order(create_test_order(i, "spoofer", Side::Buy, 4000 + i, 100, i)).unwrap();
+            book.cancel_order(i).unwrap();
+        }
+        book.process_limit_order(create_test_order(10, "spoofer", Side::Buy, 5000, 10, 10)).unwrap();
+        book.process_limit_order(create_test_order(11, "seller", Side::Sell, 5000, 10, 11)).unwrap();
+
+        assert_eq!(book.total_cancelled_orders, 9);
+        assert_eq!(book.total_cancelled_volume, 900);
+        assert_eq!(book.cancel_fill_ratio("spoofer"), Some(9.0));
+        assert_eq!(book.cancel_fill_ratio("seller"), Some(0.0));
+
+        // No fills: the ratio is undefined
+        book.process_limit_order(create_test_order(12, "quoter", Side::Buy, 4500, 10, 12)).unwrap();
+        book.cancel_order(12).unwrap();
+        assert_eq!(book.cancel_fill_ratio("quoter"), None);
+        assert_eq!(book.cancel_fill_ratio("unknown"), None);
+    }
+
+    #[test]
+    fn test_order_ack() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // Fully resting: two live asks and a cancelled one at 5000
+        book.process_limit_order(create_test_order(1, "seller1", Side::Sell, 5000, 50, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller2", Side::Sell, 5000, 50, 2)).unwrap();
+        book.cancel_order(1).unwrap();
+        let order = create_test_order(3, "seller3", Side::Sell, 5000, 50, 3);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(
+            result.ack,
+            Ack {
+                order_id: 3,
+                price: 5000,
+                resting: true,
+                sequence: 3,
+                queue_position: Some(1),
+                filled_quantity: 0,
+                cancelled_quantity: 0,
+                no_liquidity: true,
+                band_cancelled: vec![],
+                client_order_id: None,
+            }
+        );
+
+        // Fully filled: nothing rests
+        let order = create_test_order(4, "buyer", Side::Buy, 5000, 50, 4);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(
+            result.ack,
+            Ack {
+                order_id: 4,
+                price: 5000,
+                resting: false,
+                sequence: 4,
+                queue_position: None,
+                filled_quantity: 50,
+                cancelled_quantity: 0,
+                no_liquidity: false,
+                band_cancelled: vec![],
+                client_order_id: None,
+            }
+        );
+
+        // Partially filled: the remainder rests at the front of the bid level
+        let order = create_test_order(5, "buyer", Side::Buy, 5000, 80, 5);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(
+            result.ack,
+            Ack {
+                order_id: 5,
+                price: 5000,
+                resting: true,
+                sequence: 5,
+                queue_position: Some(0),
+                filled_quantity: 50,
+                cancelled_quantity: 0,
+                no_liquidity: false,
+                band_cancelled: vec![],
+                client_order_id: None,
+            }
+        );
+
+        // Rejected orders don't consume a sequence number
+        let order = create_test_order(5, "buyer", Side::Buy, 5000, 1, 6);
+        assert!(book.process_limit_order(order).is_err());
+        let order = create_test_order(6, "buyer", Side::Buy, 4900, 1, 7);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(result.ack.sequence, 6);
+    }
+
+    #[test]
+    fn test_trades_use_configured_clock() {
+        let clock = Arc::new(MockClock::new(5_000));
+        let config = OrderBookConfig {
+            clock: Some(Arc::new(MonotonicClock::new(Arc::clone(&clock)))),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 20, 1)).unwrap();
+        let buy = create_test_order(2, "buyer", Side::Buy, 5000, 10, 2);
+        assert_eq!(book.process_limit_order(buy).unwrap().trades[0].timestamp, 5_000);
+
+        // A clock regression still produces a later trade timestamp
+        clock.set(1_000);
+        let buy = create_test_order(3, "buyer", Side::Buy, 5000, 10, 3);
+        assert_eq!(book.process_limit_order(buy).unwrap().trades[0].timestamp, 5_001);
+    }
+
+    #[test]
+    fn test_user_orders_index() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        book.process_limit_order(create_test_order(1, "maker", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "maker", Side::Sell, 5100, 100, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "maker", Side::Buy, 4900, 100, 3)).unwrap();
+
+        assert_eq!(book.user_orders("maker"), vec![1, 2, 3]);
+
+        // A partial fill keeps the order listed, a full fill removes it
+        book.process_limit_order(create_test_order(4, "taker", Side::Buy, 5000, 40, 4)).unwrap();
+        assert!(book.user_orders("maker").contains(&1));
+        book.process_limit_order(create_test_order(5, "taker", Side::Buy, 5000, 60, 5)).unwrap();
+        assert!(!book.user_orders("maker").contains(&1));
+        assert!(book.user_orders("taker").is_empty());
+
+        book.cancel_order(2).unwrap();
+        assert_eq!(book.user_orders("maker"), vec![3]);
+
+        // The taker's resting remainder is indexed too
+        book.process_limit_order(create_test_order(6, "taker", Side::Sell, 4900, 150, 6)).unwrap();
+        assert!(book.user_orders("maker").is_empty());
+        assert_eq!(book.user_orders("taker"), vec![6]);
+    }
+
+    #[test]
+    fn test_max_notional() {
+        // $10,000 of notional: 100 shares at $1.00
+        let config = OrderBookConfig {
+            max_notional: Some(10_000 * 100),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
 
-        // Add orders
-        let order1 = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
-        let order2 = create_test_order(2, "user2", Side::Sell, 5000, 100, 2000);
+        let order = create_test_order(1, "user1", Side::Buy, 10_000, 100, 1);
+        assert!(book.process_limit_order(order).is_ok());
 
-        book.process_limit_order(order1).unwrap();
-        book.process_limit_order(order2).unwrap();
+        let order = create_test_order(2, "user1", Side::Buy, 9_900, 102, 2);
+        assert_eq!(
+            book.process_limit_order(order).unwrap_err(),
+            OrderBookError::NotionalExceeded
+        );
 
-        assert_eq!(book.ask_quantity_at(5000), 200);
+        // Amends are capped too
+        assert_eq!(book.amend_order(1, 10_000, 101), Err(OrderBookError::NotionalExceeded));
+    }
 
-        // Cancel first order
-        book.cancel_order(1).unwrap();
-        assert_eq!(book.get_order_status(1), Some(OrderStatus::Cancelled));
+    #[test]
+    fn test_max_notional_does_not_overflow() {
+        let config = OrderBookConfig {
+            max_notional: Some(u64::MAX as u128),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
 
-        // Verify the cancelled order is skipped during matching
-        let buy_order = create_test_order(3, "buyer", Side::Buy, 5000, 50, 3000);
-        let result = book.process_limit_order(buy_order).unwrap();
+        // 2^32 * 2^32 wraps to 0 in u64 but is 2^64 > u64::MAX in u128
+        let order = create_test_order(1, "user1", Side::Buy, 1 << 32, 1 << 32, 1);
+        assert_eq!(
+            book.process_limit_order(order).unwrap_err(),
+            OrderBookError::NotionalExceeded
+        );
 
-        // Should match with order 2, not the cancelled order 1
-        assert_eq!(result.trades.len(), 1);
-        assert_eq!(result.trades[0].maker_order_id, 2);
+        let order = create_test_order(2, "user1", Side::Buy, u64::MAX, 1, 2);
+        assert!(book.process_limit_order(order).is_ok());
     }
 
     #[test]
-    fn test_cancellation_cleanup() {
+    fn test_max_levels_limits_sweep() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
 
-        // Add a single order
-        let order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
-        book.process_limit_order(order).unwrap();
+        // Two orders at the first level still count as one level
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 50, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 5000, 50, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "seller", Side::Sell, 5100, 100, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "seller", Side::Sell, 5200, 100, 4)).unwrap();
 
-        assert_eq!(book.ask_levels(), 1);
+        let mut buy = create_test_order(5, "buyer", Side::Buy, 5200, 300, 5);
+        buy.max_levels = Some(2);
+        let result = book.process_limit_order(buy).unwrap();
 
-        // Cancel and cleanup
-        book.cancel_order(1).unwrap();
-        book.cleanup_cancelled_order(1).unwrap();
+        let prices: Vec<Price> = result.trades.iter().map(|t| t.price).collect();
+        assert_eq!(prices, vec![5000, 5000, 5100]);
+        assert_eq!(result.order.remaining_quantity, 100);
 
-        // Verify empty price level is removed
-        assert_eq!(book.ask_levels(), 0);
+        // The remainder rests at the last level traded, below the untouched ask
+        assert_eq!(result.order.price, 5100);
+        assert_eq!(book.best_bid(), Some(5100));
+        assert_eq!(book.bid_quantity_at(5100), 100);
+        assert_eq!(book.ask_quantity_at(5200), 100);
     }
 
     #[test]
-    fn test_cancel_nonexistent_order() {
+    fn test_modify_quantity_priority() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
 
-        let result = book.cancel_order(999);
-        assert_eq!(result, Err(OrderBookError::OrderNotFound(999)));
+        book.process_limit_order(create_test_order(1, "seller1", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller2", Side::Sell, 5000, 100, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "seller3", Side::Sell, 5000, 100, 3)).unwrap();
+
+        // Decrease: order 1 stays at the front
+        book.modify_quantity(1, 40).unwrap();
+        assert_eq!(book.ask_quantity_at(5000), 240);
+        let buy = create_test_order(4, "buyer", Side::Buy, 5000, 40, 4);
+        assert_eq!(book.process_limit_order(buy).unwrap().trades[0].maker_order_id, 1);
+
+        // Increase: order 2 goes behind order 3
+        book.modify_quantity(2, 150).unwrap();
+        assert_eq!(book.ask_quantity_at(5000), 250);
+        let buy = create_test_order(5, "buyer", Side::Buy, 5000, 120, 5);
+        let makers: Vec<(OrderId, Quantity)> = book
+            .process_limit_order(buy)
+            .unwrap()
+            .trades
+            .iter()
+            .map(|t| (t.maker_order_id, t.quantity))
+            .collect();
+        assert_eq!(makers, vec![(3, 100), (2, 20)]);
+
+        assert_eq!(book.modify_quantity(99, 10), Err(OrderBookError::OrderNotFound(99)));
     }
 
     #[test]
-    fn test_cancel_already_cancelled() {
+    fn test_trade_for_user() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
 
-        let order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
-        book.process_limit_order(order).unwrap();
-        book.cancel_order(1).unwrap();
+        book.process_limit_order(create_test_order(1, "maker", Side::Buy, 6500, 10, 1)).unwrap();
+        let sell = create_test_order(2, "taker", Side::Sell, 6400, 10, 2);
+        let trade = book.process_limit_order(sell).unwrap().trades.remove(0);
 
-        let result = book.cancel_order(1);
-        assert_eq!(result, Err(OrderBookError::OrderAlreadyCancelled(1)));
+        let taker = trade.for_user("taker").unwrap();
+        assert_eq!(taker.side, Side::Sell);
+        assert!(!taker.is_maker);
+        assert_eq!(taker.price, 6500);
+        assert_eq!(taker.quantity, 10);
+        assert_eq!(taker.cash_flow, 65_000);
+
+        let maker = trade.for_user("maker").unwrap();
+        assert_eq!(maker.side, Side::Buy);
+        assert!(maker.is_maker);
+        assert_eq!(maker.cash_flow, -65_000);
+
+        assert!(trade.for_user("someone").is_none());
     }
 
     #[test]
-    fn test_cancel_filled_order() {
-        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+    fn test_execution_price_policy() {
+        for (policy, expected) in [
+            (ExecutionPricePolicy::TakerImprovement, 5000),
+            (ExecutionPricePolicy::TakerPrice, 5200),
+        ] {
+            let config = OrderBookConfig {
+                execution_price_policy: policy,
+                ..Default::default()
+            };
+            let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+            book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 10, 1)).unwrap();
+            let buy = create_test_order(2, "buyer", Side::Buy, 5200, 10, 2);
+            let result = book.process_limit_order(buy).unwrap();
+            assert_eq!(result.trades[0].price, expected, "{:?}", policy);
+        }
+    }
 
-        // Add and fill an order
-        let sell_order = create_test_order(1, "seller", Side::Sell, 5000, 100, 1000);
-        book.process_limit_order(sell_order).unwrap();
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "remaining differs between book and index")]
+    fn test_debug_check_catches_index_divergence() {
+        let config = OrderBookConfig {
+            self_trade_policy: SelfTradePolicy::DecrementAndCancel,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(create_test_order(1, "user1", Side::Sell, 5000, 100, 1)).unwrap();
 
-        let buy_order = create_test_order(2, "buyer", Side::Buy, 5000, 100, 2000);
-        book.process_limit_order(buy_order).unwrap();
+        // Corrupt the index so it no longer agrees with the queued order
+        book.order_index.get_mut(&1).unwrap().remaining_quantity = 70;
 
-        // Try to cancel the filled order
-        let result = book.cancel_order(1);
-        assert_eq!(result, Err(OrderBookError::OrderAlreadyFilled(1)));
+        // Netting decrements both copies, so the divergence survives the fill
+        let buy = create_test_order(2, "user1", Side::Buy, 5000, 10, 2);
+        let _ = book.process_limit_order(buy);
     }
 
     #[test]
-    fn test_self_trading_prevention() {
-        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
-
-        // Add a sell order
-        let sell_order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
-        book.process_limit_order(sell_order).unwrap();
-
-        // Try to match with own order
-        let buy_order = create_test_order(2, "user1", Side::Buy, 5000, 100, 2000);
-        let result = book.process_limit_order(buy_order).unwrap();
+    fn test_max_levels_rests_at_level_price_under_taker_pricing() {
+        let config = OrderBookConfig {
+            execution_price_policy: ExecutionPricePolicy::TakerPrice,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(create_test_order(1, "seller1", Side::Sell, 4920, 10, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller2", Side::Sell, 4950, 70, 2)).unwrap();
 
-        // No trades should occur
-        assert_eq!(result.trades.len(), 0);
-        assert_eq!(result.order.remaining_quantity, 100);
+        let mut buy = create_test_order(3, "buyer", Side::Buy, 5060, 30, 3);
+        buy.max_levels = Some(1);
+        let result = book.process_limit_order(buy).unwrap();
 
-        // Both orders should be on the book
-        assert_eq!(book.bid_levels(), 1);
-        assert_eq!(book.ask_levels(), 1);
+        // The trade prints at the taker's limit, but the remainder rests at the
+        // level it traded, below the remaining ask
+        assert_eq!(result.trades[0].price, 5060);
+        assert_eq!(result.order.price, 4920);
+        assert_eq!(book.best_bid(), Some(4920));
+        assert_eq!(book.best_ask(), Some(4950));
     }
 
     #[test]
-    fn test_duplicate_order_id() {
+    fn test_client_order_id_round_trips() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        book.add_listener(Box::new(move |event| sink.lock().unwrap().push(event.clone())));
+
+        let mut maker = create_test_order(1, "maker", Side::Sell, 5000, 100, 1);
+        maker.client_order_id = Some("mm-42".to_string());
+        let result = book.process_limit_order(maker).unwrap();
+        assert_eq!(result.ack.client_order_id.as_deref(), Some("mm-42"));
+
+        // A partial fill keeps the tag on the resting order and prints it on the trade
+        let mut taker = create_test_order(2, "taker", Side::Buy, 5000, 30, 2);
+        taker.client_order_id = Some("t-7".to_string());
+        let result = book.process_limit_order(taker).unwrap();
+        assert_eq!(result.order.client_order_id.as_deref(), Some("t-7"));
+        assert_eq!(result.ack.client_order_id.as_deref(), Some("t-7"));
+        let trade = &result.trades[0];
+        assert_eq!(trade.taker_client_order_id.as_deref(), Some("t-7"));
+        assert_eq!(trade.maker_client_order_id.as_deref(), Some("mm-42"));
+
+        let resting = book.get_order(1).unwrap();
+        assert_eq!(resting.status, OrderStatus::PartiallyFilled);
+        assert_eq!(resting.remaining_quantity, 70);
+        assert_eq!(resting.client_order_id.as_deref(), Some("mm-42"));
 
-        let order1 = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
-        book.process_limit_order(order1).unwrap();
+        book.cancel_order(1).unwrap();
+        let events = events.lock().unwrap();
+        assert!(matches!(
+            events.last(),
+            Some(OrderEvent::Cancelled { order_id: 1, client_order_id: Some(id) }) if id == "mm-42"
+        ));
+    }
 
-        let order2 = create_test_order(1, "user2", Side::Sell, 5500, 100, 2000);
-        let result = book.process_limit_order(order2);
+    #[test]
+    fn test_cancel_by_client_id() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let tagged = |id, user: &str, client_id: &str| {
+            let mut order = create_test_order(id, user, Side::Buy, 5000 - id, 100, id);
+            order.client_order_id = Some(client_id.to_string());
+            order
+        };
+        book.process_limit_order(tagged(1, "alice", "a-1")).unwrap();
+        book.process_limit_order(tagged(2, "alice", "a-2")).unwrap();
+        // Client ids are scoped per user
+        book.process_limit_order(tagged(3, "bob", "a-1")).unwrap();
+
+        assert_eq!(
+            book.process_limit_order(tagged(4, "alice", "a-2")).unwrap_err(),
+            OrderBookError::DuplicateClientOrderId("a-2".to_string())
+        );
+        assert_eq!(book.get_order_status(4), None);
+
+        assert_eq!(book.cancel_by_client_id("alice", "a-2"), Ok(2));
+        assert_eq!(book.get_order_status(2), Some(OrderStatus::Cancelled));
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Open));
+        assert_eq!(book.get_order_status(3), Some(OrderStatus::Open));
+        assert_eq!(book.cancel_by_client_id("alice", "a-2"), Err(OrderBookError::OrderNotFound(0)));
+        assert_eq!(book.cancel_by_client_id("carol", "a-1"), Err(OrderBookError::OrderNotFound(0)));
+
+        // Once the order is gone its client id can be reused
+        book.process_limit_order(tagged(5, "alice", "a-2")).unwrap();
+
+        // A fully filled order leaves the index too
+        let mut taker = create_test_order(6, "taker", Side::Sell, 4999, 100, 6);
+        taker.client_order_id = Some("a-1".to_string());
+        book.process_limit_order(taker).unwrap();
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Filled));
+        assert_eq!(book.cancel_by_client_id("alice", "a-1"), Err(OrderBookError::OrderNotFound(0)));
+    }
 
-        assert!(matches!(result, Err(OrderBookError::DuplicateOrderId(1))));
+    #[test]
+    fn test_market_order_slippage_protection() {
+        let config = OrderBookConfig {
+            execution_price_policy: ExecutionPricePolicy::TakerPrice,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(create_test_order(1, "maker", Side::Sell, 5000, 50, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "maker", Side::Sell, 5020, 50, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "maker", Side::Sell, 5100, 100, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "maker", Side::Sell, 5300, 100, 4)).unwrap();
+
+        // 50 bps from the first fill at 5000 allows 5020 but not 5100
+        let mut order = create_test_order(5, "taker", Side::Buy, 1, 250, 5);
+        order.max_slippage_bps = Some(50);
+        let result = book.process_market_order(order).unwrap();
+        let fills: Vec<(Price, Quantity)> = result.trades.iter().map(|t| (t.price, t.quantity)).collect();
+        assert_eq!(fills, vec![(5000, 50), (5020, 50)]);
+        assert_eq!(result.order.price, 5050);
+        assert_eq!(result.order.remaining_quantity, 150);
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert!(!result.ack.resting);
+        assert_eq!(result.ack.cancelled_quantity, 150);
+        assert_eq!(book.get_order_status(5), None);
+        assert_eq!(book.best_ask(), Some(5100));
+
+        // Without protection the order sweeps the book, still at the makers' prices
+        let result = book
+            .process_market_order(create_test_order(6, "taker", Side::Buy, 1, 300, 6))
+            .unwrap();
+        let fills: Vec<(Price, Quantity)> = result.trades.iter().map(|t| (t.price, t.quantity)).collect();
+        assert_eq!(fills, vec![(5100, 100), (5300, 100)]);
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(book.best_ask(), None);
+
+        assert_eq!(
+            book.process_market_order(create_test_order(7, "taker", Side::Buy, 1, 10, 7))
+                .unwrap_err(),
+            OrderBookError::NoLiquidity
+        );
     }
 
     #[test]
-    fn test_invalid_price() {
-        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+    fn test_refresh_order_loses_time_priority() {
+        let clock = Arc::new(MockClock::new(100));
+        let config = OrderBookConfig { clock: Some(clock.clone()), ..Default::default() };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(create_test_order(1, "maker1", Side::Sell, 5000, 50, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "maker2", Side::Sell, 5000, 50, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "maker3", Side::Sell, 5000, 50, 3)).unwrap();
+
+        clock.set(500);
+        book.refresh_order(1).unwrap();
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Open));
+        assert_eq!(book.ask_quantity_at(5000), 150);
+        assert_eq!(book.oldest_order_timestamp(Side::Sell, 5000), Some(2));
+
+        let taker = create_test_order(4, "taker", Side::Buy, 5000, 120, 4);
+        let result = book.process_limit_order(taker).unwrap();
+        let makers: Vec<OrderId> = result.trades.iter().map(|t| t.maker_order_id).collect();
+        assert_eq!(makers, vec![2, 3, 1]);
+        assert_eq!(book.get_order_remaining(1), Some(30));
+
+        assert_eq!(book.refresh_order(2), Err(OrderBookError::OrderAlreadyFilled(2)));
+        book.cancel_order(1).unwrap();
+        assert_eq!(book.refresh_order(1), Err(OrderBookError::OrderAlreadyCancelled(1)));
+        assert_eq!(book.refresh_order(9), Err(OrderBookError::OrderNotFound(9)));
+    }
 
-        let order = create_test_order(1, "user1", Side::Sell, 0, 100, 1000);
-        let result = book.process_limit_order(order);
+    #[test]
+    fn test_tick_policy() {
+        let book_with = |tick_policy| {
+            let config = OrderBookConfig { tick_size: Some(100), tick_policy, ..Default::default() };
+            OrderBook::with_config("market1".to_string(), "YES".to_string(), config)
+        };
 
-        assert!(matches!(result, Err(OrderBookError::InvalidPrice)));
+        let mut book = book_with(TickPolicy::Reject);
+        assert_eq!(
+            book.process_limit_order(create_test_order(1, "user1", Side::Buy, 6543, 10, 1))
+                .unwrap_err(),
+            OrderBookError::InvalidTick
+        );
+        book.process_limit_order(create_test_order(2, "user1", Side::Buy, 6500, 10, 2)).unwrap();
+        assert_eq!(book.amend_order(2, 6450, 10), Err(OrderBookError::InvalidTick));
+
+        let mut book = book_with(TickPolicy::SnapToNearest);
+        let order = create_test_order(1, "user1", Side::Buy, 6543, 10, 1);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(result.ack.price, 6500);
+        assert_eq!(result.order.price, 6500);
+        assert_eq!(book.best_bid(), Some(6500));
+
+        // Snapping happens before matching: 6651 becomes 6700 and crosses nothing
+        book.process_limit_order(create_test_order(2, "user2", Side::Sell, 6651, 10, 2)).unwrap();
+        assert_eq!(book.best_ask(), Some(6700));
+        book.amend_order(2, 6620, 10).unwrap();
+        assert_eq!(book.best_ask(), Some(6600));
+        // Never below one tick
+        let order = create_test_order(3, "user3", Side::Buy, 20, 10, 3);
+        assert_eq!(book.process_limit_order(order).unwrap().ack.price, 100);
     }
 
     #[test]
-    fn test_invalid_quantity() {
+    fn test_drain_and_resubmit_reproduces_book() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        for (id, side, price, quantity) in [
+            (1, Side::Buy, 4900, 100),
+            (2, Side::Buy, 4900, 50),
+            (3, Side::Buy, 4800, 70),
+            (4, Side::Buy, 4900, 30),
+            (5, Side::Sell, 5100, 100),
+            (6, Side::Sell, 5000, 40),
+            (7, Side::Sell, 5000, 60),
+        ] {
+            let order = create_test_order(id, &format!("user{}", id), side, price, quantity, id);
+            book.process_limit_order(order).unwrap();
+        }
+        book.cancel_order(2).unwrap();
+        book.process_limit_order(create_test_order(8, "taker", Side::Buy, 5000, 50, 8)).unwrap();
+        let depth = book.get_depth(10);
+        let (bbo, trades) = ((book.best_bid(), book.best_ask()), book.total_trades);
+
+        let orders = book.drain();
+        let ids: Vec<OrderId> = orders.iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![1, 4, 3, 7, 5]);
+        assert_eq!(orders[3].status, OrderStatus::PartiallyFilled);
+        assert_eq!((orders[3].original_quantity, orders[3].remaining_quantity), (60, 50));
+        assert_eq!(book.get_depth(10), (vec![], vec![]));
+        assert_eq!(book.active_orders(), 0);
+        assert_eq!(book.get_order_status(1), None);
+        assert_eq!(book.total_trades, trades);
+        book.reset_statistics();
+        assert_eq!(book.total_trades, 0);
+
+        let mut fresh = OrderBook::new("market1".to_string(), "YES".to_string());
+        for order in orders {
+            fresh.process_limit_order(order).unwrap();
+        }
+        assert_eq!(fresh.get_depth(10), depth);
+        assert_eq!((fresh.best_bid(), fresh.best_ask()), bbo);
+        assert_eq!(fresh.get_order_status(7), Some(OrderStatus::PartiallyFilled));
 
-        let mut order = create_test_order(1, "user1", Side::Sell, 5000, 0, 1000);
-        order.remaining_quantity = 0;
-        let result = book.process_limit_order(order);
+        // Priority survives: the first bid at 4900 fills first
+        let taker = create_test_order(9, "taker", Side::Sell, 4900, 100, 9);
+        let result = fresh.process_limit_order(taker).unwrap();
+        assert_eq!(result.trades[0].maker_order_id, 1);
+    }
 
-        assert!(matches!(result, Err(OrderBookError::InvalidQuantity)));
+    #[test]
+    fn test_equal_prices_trade_on_every_path() {
+        assert!(Side::Buy.reaches(5000, 5000) && Side::Sell.reaches(5000, 5000));
+        assert!(!Side::Buy.reaches(4999, 5000) && !Side::Sell.reaches(5001, 5000));
+        let bid = create_test_order(1, "buyer", Side::Buy, 5000, 10, 1);
+        let ask = create_test_order(2, "seller", Side::Sell, 5000, 10, 2);
+        assert!(bid.can_match(&ask) && ask.can_match(&bid));
+
+        // Submitting at the opposite best trades
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 100, 1)).unwrap();
+        let buy = create_test_order(2, "buyer", Side::Buy, 5000, 10, 2);
+        assert_eq!(book.process_limit_order(buy).unwrap().trades.len(), 1);
+        let taker = create_test_order(3, "buyer", Side::Buy, 5000, 10, 3);
+        assert_eq!(book.match_against(taker, 1).unwrap().price, 5000);
+
+        // Amending onto the opposite best doesn't match: it locks the book
+        book.process_limit_order(create_test_order(4, "buyer", Side::Buy, 4900, 10, 4)).unwrap();
+        book.amend_order(4, 5000, 10).unwrap();
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(5000), Some(5000)));
+        assert_eq!(book.spread(), None);
+        assert!(book.is_healthy());
+
+        // ...and an order arriving at the locked price trades with the other side
+        let sell = create_test_order(5, "seller2", Side::Sell, 5000, 10, 5);
+        let result = book.process_limit_order(sell).unwrap();
+        assert_eq!(result.trades[0].maker_order_id, 4);
+
+        let config = OrderBookConfig {
+            locked_book_policy: LockedBookPolicy::Reject,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "buyer", Side::Buy, 4900, 10, 2)).unwrap();
+        assert_eq!(book.amend_order(2, 5000, 10), Err(OrderBookError::WouldLock));
+        assert_eq!(book.amend_order(2, 5100, 10), Err(OrderBookError::WouldCross));
     }
 
     #[test]
-    fn test_market_mismatch() {
+    fn test_max_rest_quantity_cancels_excess() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 50, 1)).unwrap();
+
+        let mut order = create_test_order(2, "buyer", Side::Buy, 5000, 200, 2);
+        order.max_rest_quantity = Some(100);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(result.ack.filled_quantity, 50);
+        assert_eq!(result.ack.cancelled_quantity, 50);
+        assert!(result.ack.resting);
+        assert_eq!(result.order.status, OrderStatus::PartiallyFilled);
+        assert_eq!((result.order.original_quantity, result.order.remaining_quantity), (200, 100));
+        assert_eq!(book.bid_quantity_at(5000), 100);
+        assert_eq!(book.get_order_remaining(2), Some(100));
+
+        // Under the cap nothing is cancelled
+        let mut order = create_test_order(3, "buyer", Side::Buy, 4900, 80, 3);
+        order.max_rest_quantity = Some(100);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!((result.ack.filled_quantity, result.ack.cancelled_quantity), (0, 0));
+        assert_eq!(book.bid_quantity_at(4900), 80);
+
+        // A cap of zero rests nothing
+        let mut order = create_test_order(4, "buyer", Side::Buy, 4800, 80, 4);
+        order.max_rest_quantity = Some(0);
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(result.ack.cancelled_quantity, 80);
+        assert!(!result.ack.resting);
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(book.bid_quantity_at(4800), 0);
+    }
 
-        let mut order = create_test_order(1, "user1", Side::Sell, 5000, 100, 1000);
-        order.market_id = "market2".to_string();
-        let result = book.process_limit_order(order);
+    #[test]
+    fn test_fill_and_kill_kills_shortfall() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "seller", Side::Sell, 5000, 30, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 5100, 40, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "seller", Side::Sell, 5300, 50, 3)).unwrap();
 
-        assert!(matches!(result, Err(OrderBookError::MarketMismatch)));
+        let mut order = create_test_order(4, "buyer", Side::Buy, 5100, 100, 4);
+        order.time_in_force = TimeInForce::FillAndKill;
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!(result.trades.len(), 2);
+        assert_eq!((result.ack.filled_quantity, result.ack.cancelled_quantity), (70, 30));
+        assert!(!result.ack.resting);
+        assert_eq!(result.order.status, OrderStatus::Cancelled);
+        assert_eq!(book.get_order_remaining(4), None);
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.best_ask(), Some(5300));
+
+        // With nothing in reach, everything is killed
+        let mut order = create_test_order(5, "buyer", Side::Buy, 5200, 20, 5);
+        order.time_in_force = TimeInForce::FillAndKill;
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!((result.ack.filled_quantity, result.ack.cancelled_quantity), (0, 20));
+        assert!(!result.ack.no_liquidity);
+        assert_eq!(book.best_bid(), None);
     }
 
     #[test]
-    fn test_bid_priority_highest_first() {
+    fn test_fill_and_kill_reports_empty_book() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let mut order = create_test_order(1, "buyer", Side::Buy, 5100, 20, 1);
+        order.time_in_force = TimeInForce::FillAndKill;
+        let result = book.process_limit_order(order).unwrap();
+        assert_eq!((result.ack.filled_quantity, result.ack.cancelled_quantity), (0, 20));
+        assert!(result.ack.no_liquidity);
+
+        // A cancelled order is no liquidity either
+        book.process_limit_order(create_test_order(2, "seller", Side::Sell, 5000, 30, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "seller", Side::Sell, 5000, 30, 3)).unwrap();
+        book.cancel_order(3).unwrap();
+        let mut order = create_test_order(4, "buyer", Side::Buy, 5100, 20, 4);
+        order.time_in_force = TimeInForce::FillAndKill;
+        assert!(!book.process_limit_order(order).unwrap().ack.no_liquidity);
+        book.cancel_order(2).unwrap();
+        let mut order = create_test_order(5, "buyer", Side::Buy, 5100, 20, 5);
+        order.time_in_force = TimeInForce::FillAndKill;
+        assert!(book.process_limit_order(order).unwrap().ack.no_liquidity);
+        let market = create_test_order(6, "buyer", Side::Buy, 1, 20, 6);
+        assert_eq!(book.process_market_order(market).unwrap_err(), OrderBookError::NoLiquidity);
+    }
 
-        // Add buy orders at different prices
-        let buy_low = create_test_order(1, "buyer1", Side::Buy, 5000, 100, 1000);
-        let buy_high = create_test_order(2, "buyer2", Side::Buy, 6000, 100, 2000);
+    #[test]
+    fn test_resting_orders_regain_market_and_outcome() {
+        assert!(std::mem::size_of::<BookOrder>() < std::mem::size_of::<Order>());
+
+        let mut book = OrderBook::new("market7".to_string(), "no".to_string());
+        let order = |id, side, price| {
+            Order::with_timestamp(
+                id,
+                format!("user{}", id),
+                "market7".to_string(),
+                "NO".to_string(),
+                side,
+                price,
+                100,
+                id,
+            )
+        };
+        book.process_limit_order(order(1, Side::Sell, 5000)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 4900)).unwrap();
+        book.amend_order(2, 4800, 60).unwrap();
+        let result = book.process_limit_order(order(3, Side::Buy, 5000)).unwrap();
+        let trade = &result.trades[0];
+        assert_eq!((trade.market_id.as_str(), trade.outcome_id.as_str()), ("market7", "NO"));
+
+        book.process_limit_order(order(4, Side::Sell, 5200)).unwrap();
+        let snapshot = book.snapshot();
+        let queued = snapshot.bids.iter().chain(&snapshot.asks).flat_map(|level| &level.orders);
+        for order in queued.chain(&book.drain()) {
+            assert_eq!((order.market_id.as_str(), order.outcome_id.as_str()), ("market7", "NO"));
+        }
+    }
 
-        book.process_limit_order(buy_low).unwrap();
-        book.process_limit_order(buy_high).unwrap();
+    #[test]
+    fn test_min_resting_time_before_cancel() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let config = OrderBookConfig {
+            min_resting_micros: 500,
+            clock: Some(clock.clone()),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(create_test_order(1, "maker", Side::Sell, 5000, 100, 1_000))
+            .unwrap();
+
+        clock.advance(200);
+        assert_eq!(
+            book.cancel_order(1),
+            Err(OrderBookError::TooSoonToCancel { remaining_micros: 300 })
+        );
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Open));
 
-        // Sell order should match with highest bid first
-        let sell_order = create_test_order(3, "seller", Side::Sell, 5000, 150, 3000);
-        let result = book.process_limit_order(sell_order).unwrap();
+        clock.advance(300);
+        assert_eq!(book.cancel_order(1), Ok(()));
+        assert_eq!(book.cancel_order(1), Err(OrderBookError::OrderAlreadyCancelled(1)));
 
-        // Verify: highest bid matches first
-        assert_eq!(result.trades.len(), 2);
-        assert_eq!(result.trades[0].price, 6000);
-        assert_eq!(result.trades[0].maker_order_id, 2);
-        assert_eq!(result.trades[1].price, 5000);
-        assert_eq!(result.trades[1].maker_order_id, 1);
+        // Cancels the book makes itself are not held back
+        book.process_limit_order(create_test_order(2, "maker", Side::Sell, 5000, 100, 1_500))
+            .unwrap();
+        assert_eq!(book.cancel_user_orders("maker"), vec![2]);
     }
 
     #[test]
-    fn test_get_depth() {
+    fn test_trade_ids_unique_and_increasing() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let emitted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&emitted);
+        book.add_listener(Box::new(move |event| {
+            if let OrderEvent::Trade(trade) = event {
+                sink.lock().unwrap().push(trade.id);
+            }
+        }));
+
+        let mut seed: u64 = 7;
+        let mut next = || {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            seed >> 33
+        };
+        let mut returned = Vec::new();
+        for id in 1..=3_000 {
+            let r = next();
+            if r % 10 == 0 {
+                let _ = book.cancel_order(next() % id);
+                continue;
+            }
+            if r % 10 == 1 {
+                let taker = create_test_order(id, "direct", Side::Buy, 6000, 1 + next() % 50, id);
+                if let Ok(trade) = book.match_against(taker, next() % id) {
+                    returned.push(trade.id);
+                }
+                continue;
+            }
+            let side = if r % 2 == 0 { Side::Buy } else { Side::Sell };
+            let user = format!("user{}", next() % 4);
+            let price = 4950 + 10 * (next() % 10);
+            let mut order = create_test_order(id, &user, side, price, 1 + next() % 150, id);
+            if next() % 8 == 0 {
+                order.order_type = OrderType::Market;
+            }
+            if let Ok(result) = book.process_limit_order(order) {
+                let ids: HashSet<TradeId> = result.trades.iter().map(|t| t.id).collect();
+                assert_eq!(ids.len(), result.trades.len());
+                returned.extend(result.trades.iter().map(|t| t.id));
+            }
+        }
 
-        // Add bids
-        let bid1 = create_test_order(1, "user1", Side::Buy, 5000, 100, 1000);
-        let bid2 = create_test_order(2, "user2", Side::Buy, 5100, 200, 2000);
-        let bid3 = create_test_order(3, "user3", Side::Buy, 5200, 150, 3000);
+        let emitted = emitted.lock().unwrap();
+        assert!(emitted.len() > 1_000);
+        assert!(emitted.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(*emitted, returned);
+    }
 
-        // Add asks
-        let ask1 = create_test_order(4, "user4", Side::Sell, 5500, 100, 4000);
-        let ask2 = create_test_order(5, "user5", Side::Sell, 5600, 200, 5000);
+    #[test]
+    fn test_timestamp_policy_handles_backdated_orders() {
+        let clock = Arc::new(MockClock::new(10_000));
+        let book_with = |timestamp_policy| {
+            let config = OrderBookConfig {
+                timestamp_policy,
+                clock: Some(clock.clone()),
+                ..Default::default()
+            };
+            OrderBook::with_config("market1".to_string(), "YES".to_string(), config)
+        };
+        let backdated = create_test_order(1, "alice", Side::Buy, 5000, 100, 1_000);
 
-        book.process_limit_order(bid1).unwrap();
-        book.process_limit_order(bid2).unwrap();
-        book.process_limit_order(bid3).unwrap();
-        book.process_limit_order(ask1).unwrap();
-        book.process_limit_order(ask2).unwrap();
+        let mut book = book_with(TimestampPolicy::Trust);
+        let result = book.process_limit_order(backdated.clone()).unwrap();
+        assert_eq!(result.order.timestamp, 1_000);
 
-        let (bids, asks) = book.get_depth(2);
+        let mut book = book_with(TimestampPolicy::Override);
+        let result = book.process_limit_order(backdated.clone()).unwrap();
+        assert_eq!(result.order.timestamp, 10_000);
 
-        // Bids should be highest first
-        assert_eq!(bids.len(), 2);
-        assert_eq!(bids[0], (5200, 150));
-        assert_eq!(bids[1], (5100, 200));
+        let mut book = book_with(TimestampPolicy::Reject { tolerance_micros: 500 });
+        assert_eq!(
+            book.process_limit_order(backdated).unwrap_err(),
+            OrderBookError::TimestampOutOfWindow { timestamp: 1_000, now: 10_000 }
+        );
+        assert_eq!(book.active_orders(), 0);
+        let future = create_test_order(2, "alice", Side::Buy, 5000, 100, 10_501);
+        assert!(book.process_limit_order(future).is_err());
+        let recent = create_test_order(3, "alice", Side::Buy, 5000, 100, 9_500);
+        assert_eq!(book.process_limit_order(recent).unwrap().order.timestamp, 9_500);
+    }
 
-        // Asks should be lowest first
-        assert_eq!(asks.len(), 2);
-        assert_eq!(asks[0], (5500, 100));
-        assert_eq!(asks[1], (5600, 200));
+    #[test]
+    fn test_streamed_trades_match_collected() {
+        let sweep = |book: &mut OrderBook| {
+            for i in 0..20 {
+                let price = 5000 + 10 * (i % 5);
+                let maker = format!("maker{}", i % 3);
+                let order = create_test_order(i + 1, &maker, Side::Sell, price, 7, i);
+                book.process_limit_order(order).unwrap();
+            }
+            create_test_order(100, "taker", Side::Buy, 5030, 120, 100)
+        };
+
+        // Trades are stamped by the clock, so both books share a fixed one
+        let config = OrderBookConfig {
+            clock: Some(Arc::new(MockClock::new(1_000))),
+            ..Default::default()
+        };
+        let mut collecting =
+            OrderBook::with_config("market1".to_string(), "YES".to_string(), config.clone());
+        let taker = sweep(&mut collecting);
+        let collected = collecting.process_limit_order(taker.clone()).unwrap();
+
+        let mut streaming = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        sweep(&mut streaming);
+        let mut streamed = Vec::new();
+        let (order, ack) = streaming
+            .process_limit_order_streaming(taker, |trade| streamed.push(trade.clone()))
+            .unwrap();
+
+        assert_eq!(collected.trades.len(), 16);
+        assert_eq!(format!("{:?}", streamed), format!("{:?}", collected.trades));
+        assert_eq!((order, ack), (collected.order, collected.ack));
+        assert_eq!(streaming.get_depth(10), collecting.get_depth(10));
+        assert_eq!(streaming.total_volume, collecting.total_volume);
     }
 
     #[test]
-    fn test_statistics() {
+    fn test_emergency_halt_cancels_and_rejects() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "alice", Side::Buy, 4900, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "bob", Side::Buy, 5000, 50, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "carol", Side::Sell, 5200, 80, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "dave", Side::Sell, 5200, 30, 4)).unwrap();
+        book.process_limit_order(create_test_order(5, "erin", Side::Buy, 5200, 20, 5)).unwrap();
+        book.cancel_order(1).unwrap();
 
-        // Add and match orders
-        let sell = create_test_order(1, "seller", Side::Sell, 5000, 100, 1000);
-        book.process_limit_order(sell).unwrap();
+        let halted = book.emergency_halt();
+        let summary: Vec<_> = halted.iter().map(|o| (o.id, o.remaining_quantity)).collect();
+        assert_eq!(summary, vec![(2, 50), (3, 60), (4, 30)]);
+        assert!(halted.iter().all(|o| o.status == OrderStatus::Cancelled));
+        assert_eq!(book.market_state(), MarketState::Halted);
+        assert_eq!(book.active_orders(), 0);
+        assert_eq!(book.get_depth(10), (vec![], vec![]));
+        assert_eq!(book.get_order_status(3), Some(OrderStatus::Cancelled));
 
-        let buy = create_test_order(2, "buyer", Side::Buy, 5000, 100, 2000);
-        book.process_limit_order(buy).unwrap();
+        let order = create_test_order(6, "alice", Side::Buy, 5000, 10, 6);
+        assert_eq!(
+            book.process_limit_order(order.clone()).unwrap_err(),
+            OrderBookError::MarketHalted
+        );
+        assert_eq!(
+            book.submit_quote("bob", 4900, 10, 5100, 10).unwrap_err(),
+            OrderBookError::MarketHalted
+        );
 
-        assert_eq!(book.total_trades, 1);
-        assert_eq!(book.total_volume, 100);
+        book.resume();
+        assert!(book.process_limit_order(order).is_ok());
     }
 
     #[test]
-    fn test_large_order_multiple_makers() {
-        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+    fn test_validate_order_matches_submission() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let config = OrderBookConfig {
+            tick_size: Some(10),
+            max_notional: Some(1_000_000),
+            id_range: Some(1..1_000),
+            self_trade_policy: SelfTradePolicy::RejectTaker,
+            timestamp_policy: TimestampPolicy::Reject { tolerance_micros: 100 },
+            clock: Some(clock),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        let mut resting = create_test_order(1, "alice", Side::Sell, 5000, 10, 1_000);
+        resting.client_order_id = Some("a-1".to_string());
+        book.process_limit_order(resting).unwrap();
 
-        // Add 5 sell orders at same price
-        for i in 1..=5 {
-            let order = create_test_order(i, &format!("seller{}", i), Side::Sell, 5000, 100, i * 1000);
-            book.process_limit_order(order).unwrap();
+        let order = |id, user: &str, price, quantity| {
+            create_test_order(id, user, Side::Buy, price, quantity, 1_000)
+        };
+        let mut invalid = vec![
+            order(2, "bob", 0, 10),
+            order(2, "bob", 5000, 0),
+            order(2, "bob", 5005, 10),
+            order(2, "bob", 5000, 1_000),
+            order(1, "bob", 5000, 10),
+            order(5_000, "bob", 5000, 10),
+            order(2, "alice", 5000, 10),
+            create_test_order(2, "bob", Side::Buy, 5000, 10, 5_000),
+        ];
+        let mut other_market = order(2, "bob", 5000, 10);
+        other_market.market_id = "market2".to_string();
+        invalid.push(other_market);
+        let mut duplicate_client_id = create_test_order(2, "alice", Side::Sell, 5100, 10, 1_000);
+        duplicate_client_id.client_order_id = Some("a-1".to_string());
+        invalid.push(duplicate_client_id);
+        let mut market = create_test_order(2, "bob", Side::Sell, 5000, 10, 1_000);
+        market.order_type = OrderType::Market;
+        invalid.push(market);
+
+        let mut errors = Vec::new();
+        for order in invalid {
+            let validated = book.validate_order(&order).unwrap_err();
+            assert_eq!(book.process_limit_order(order).unwrap_err(), validated);
+            errors.push(validated);
         }
+        assert_eq!(
+            errors,
+            vec![
+                OrderBookError::InvalidPrice,
+                OrderBookError::InvalidQuantity,
+                OrderBookError::InvalidTick,
+                OrderBookError::NotionalExceeded,
+                OrderBookError::DuplicateOrderId(1),
+                OrderBookError::OrderIdOutOfRange(5_000),
+                OrderBookError::WouldSelfCross,
+                OrderBookError::TimestampOutOfWindow { timestamp: 5_000, now: 1_000 },
+                OrderBookError::MarketMismatch,
+                OrderBookError::DuplicateClientOrderId("a-1".to_string()),
+                OrderBookError::NoLiquidity,
+            ]
+        );
 
-        assert_eq!(book.ask_quantity_at(5000), 500);
-
-        // Large buy order
-        let buy = create_test_order(10, "buyer", Side::Buy, 5000, 350, 10000);
-        let result = book.process_limit_order(buy).unwrap();
-
-        // Should have 4 trades (3 full + 1 partial)
-        assert_eq!(result.trades.len(), 4);
-        assert_eq!(result.order.status, OrderStatus::Filled);
-
-        // Verify FIFO order
-        assert_eq!(result.trades[0].maker_order_id, 1);
-        assert_eq!(result.trades[1].maker_order_id, 2);
-        assert_eq!(result.trades[2].maker_order_id, 3);
-        assert_eq!(result.trades[3].maker_order_id, 4);
-        assert_eq!(result.trades[3].quantity, 50);
+        // A valid order passes, and validating it changes nothing
+        let valid = order(2, "bob", 4900, 10);
+        assert_eq!(book.validate_order(&valid), Ok(()));
+        assert_eq!((book.active_orders(), book.get_order(2)), (1, None));
+        assert!(book.process_limit_order(valid).is_ok());
 
-        // Remaining on book
-        assert_eq!(book.ask_quantity_at(5000), 150); // 50 from order 4 + 100 from order 5
+        book.emergency_halt();
+        let order = order(3, "bob", 4900, 10);
+        assert_eq!(book.validate_order(&order), Err(OrderBookError::MarketHalted));
     }
 
     #[test]
-    fn test_no_match_price_gap() {
+    fn test_sweep_trades_in_price_then_queue_order() {
         let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
 
-        // Add sell order at high price
-        let sell = create_test_order(1, "seller", Side::Sell, 7000, 100, 1000);
-        book.process_limit_order(sell).unwrap();
+        // Three levels, queued out of price order; order 7 joins the best level last
+        let makers = [(1, 5100), (2, 5000), (3, 5200), (4, 5100), (5, 5000), (6, 5200), (7, 5000)];
+        for (id, price) in makers {
+            let maker = create_test_order(id, &format!("maker{}", id), Side::Sell, price, 10, id);
+            book.process_limit_order(maker).unwrap();
+        }
+        book.cancel_order(5).unwrap();
+
+        let taker = create_test_order(8, "taker", Side::Buy, 5200, 55, 8);
+        let result = book.process_limit_order(taker).unwrap();
+        let tape: Vec<_> =
+            result.trades.iter().map(|t| (t.price, t.maker_order_id, t.quantity)).collect();
+        assert_eq!(
+            tape,
+            vec![
+                (5000, 2, 10),
+                (5000, 7, 10),
+                (5100, 1, 10),
+                (5100, 4, 10),
+                (5200, 3, 10),
+                (5200, 6, 5),
+            ]
+        );
+        assert!(result.trades.windows(2).all(|pair| pair[0].id < pair[1].id));
+    }
 
-        // Add buy order at low price (no match)
-        let buy = create_test_order(2, "buyer", Side::Buy, 5000, 100, 2000);
-        let result = book.process_limit_order(buy).unwrap();
+    #[test]
+    fn test_next_maker_skips_cancelled_and_own_orders() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        assert_eq!(book.next_maker(Side::Sell, None), None);
+
+        book.process_limit_order(create_test_order(1, "alice", Side::Sell, 5100, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "bob", Side::Sell, 5000, 100, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "carol", Side::Sell, 5000, 100, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "dave", Side::Buy, 4900, 100, 4)).unwrap();
+        assert_eq!(book.next_maker(Side::Sell, None), Some(2));
+        assert_eq!(book.next_maker(Side::Buy, None), Some(4));
+        assert_eq!(book.next_maker(Side::Sell, Some("bob")), Some(3));
+
+        // The front is cancelled: the next order at the level is up
+        book.cancel_order(2).unwrap();
+        assert_eq!(book.next_maker(Side::Sell, None), Some(3));
+        book.cancel_order(3).unwrap();
+        assert_eq!(book.next_maker(Side::Sell, None), Some(1));
+        assert_eq!(book.next_maker(Side::Sell, Some("alice")), None);
+
+        // Peeking executed nothing
+        let result =
+            book.process_limit_order(create_test_order(5, "erin", Side::Buy, 5100, 10, 5)).unwrap();
+        assert_eq!(result.trades[0].maker_order_id, 1);
+    }
 
-        assert_eq!(result.trades.len(), 0);
-        assert_eq!(book.bid_levels(), 1);
-        assert_eq!(book.ask_levels(), 1);
-        assert_eq!(book.spread(), Some(2000));
+    #[test]
+    fn test_cancel_many_reports_each_outcome() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(create_test_order(1, "alice", Side::Sell, 5000, 100, 1)).unwrap();
+        book.process_limit_order(create_test_order(2, "alice", Side::Sell, 5000, 50, 2)).unwrap();
+        book.process_limit_order(create_test_order(3, "bob", Side::Sell, 5100, 70, 3)).unwrap();
+        book.process_limit_order(create_test_order(4, "carol", Side::Sell, 5200, 10, 4)).unwrap();
+        book.process_limit_order(create_test_order(5, "dave", Side::Buy, 5000, 100, 5)).unwrap();
+
+        let outcomes = book.cancel_many(&[2, 1, 9, 3]);
+        assert_eq!(
+            outcomes,
+            vec![
+                (2, Ok(())),
+                (1, Err(OrderBookError::OrderAlreadyFilled(1))),
+                (9, Err(OrderBookError::OrderNotFound(9))),
+                (3, Ok(())),
+            ]
+        );
+        assert_eq!(book.get_depth(5).1, vec![(5200, 10)]);
+        assert_eq!(book.get_order_status(4), Some(OrderStatus::Open));
+        assert_eq!(book.active_orders(), 1);
     }
 }
@@ -0,0 +1,194 @@
+//! All-or-nothing market orders
+//!
+//! A market order normally takes what the book offers and cancels the rest.
+//! With `OrderBookConfig::market_order_policy` set to
+//! [`MarketOrderPolicy::RejectIfIncomplete`], an order the book can't fill
+//! completely is rejected with `OrderBookError::MarketOrderIncomplete`
+//! instead, before anything trades.
+//!
+//! Fills reach listeners and streaming callers as they execute, so they
+//! can't be taken back afterwards. The order is matched in a dry run first,
+//! walking the book as the match loop would: midpoint orders, then the lit
+//! levels through the configured matching algorithm, within the order's
+//! slippage and level limits, counting the iceberg reserves a sweep would
+//! uncover. The taker's own orders never fill it, so under
+//! `SelfTradePolicy::DecrementAndCancel` reaching one makes it incomplete.
+
+use crate::matching::LevelAction;
+use crate::{BookOrder, Order, OrderBook, OrderStatus, OrderType, Price, Quantity, Side};
+
+/// What to do with the part of a market order the book can't fill
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarketOrderPolicy {
+    /// Fill what is available and cancel the remainder
+    #[default]
+    CancelRemainder,
+    /// Reject the whole order with `OrderBookError::MarketOrderIncomplete`,
+    /// leaving the book untouched
+    RejectIfIncomplete,
+}
+
+impl OrderBook {
+    /// Whether matching would fill all of `order`, without changing anything
+    ///
+    /// `order` must already be prepared, with its market price set to the
+    /// worst opposite price.
+    pub(crate) fn fills_completely(&self, order: &Order) -> bool {
+        let maker_side = order.side.opposite();
+        let mut taker = order.clone();
+
+        if let Some(mid) = self.midpoint().filter(|&mid| taker.side.reaches(taker.price, mid)) {
+            let hidden = self
+                .midpoint_orders
+                .iter()
+                .filter(|maker| maker.side == maker_side && maker.user_id != taker.user_id)
+                .filter(|maker| maker_side.reaches(maker.price, mid))
+                .map(|maker| maker.remaining_quantity)
+                .sum::<Quantity>();
+            taker.remaining_quantity -= taker.remaining_quantity.min(hidden);
+        }
+
+        let prices: Vec<Price> = match taker.side {
+            Side::Buy => self.asks.range(..=taker.price).map(|(&p, _)| p).collect(),
+            Side::Sell => self.bids.range(taker.price..).rev().map(|(&p, _)| p).collect(),
+        };
+        let mut levels_traded = 0;
+        for price in prices {
+            if taker.remaining_quantity == 0 || !taker.side.reaches(taker.price, price) {
+                break;
+            }
+            if levels_traded > 0 && taker.max_levels.is_some_and(|max| levels_traded >= max) {
+                break;
+            }
+
+            let Some(filled) = self.dry_run_level(&taker, price) else {
+                return false;
+            };
+            if filled == 0 {
+                continue;
+            }
+            if levels_traded == 0 && taker.order_type == OrderType::Market {
+                if let Some(bps) = taker.max_slippage_bps {
+                    taker.price = match taker.side {
+                        Side::Buy => price.saturating_add(bps),
+                        Side::Sell => price.saturating_sub(bps),
+                    };
+                }
+            }
+            taker.remaining_quantity -= filled;
+            levels_traded += 1;
+        }
+        taker.remaining_quantity == 0
+    }
+
+    /// Quantity `taker` would fill at one level, or None if it would be
+    /// netted against its own order there
+    fn dry_run_level(&self, taker: &Order, price: Price) -> Option<Quantity> {
+        let level = self.book(taker.side.opposite()).get(&price)?;
+        let makers: Vec<&BookOrder> = level
+            .orders
+            .iter()
+            .filter(|maker| {
+                self.order_index
+                    .get(&maker.id)
+                    .is_some_and(|m| m.status != OrderStatus::Cancelled)
+            })
+            .collect();
+
+        let mut remaining = taker.remaining_quantity;
+        let mut reserve: Quantity = 0;
+        for action in self.matcher.match_level(taker, &makers, self.config.self_trade_policy) {
+            match action {
+                LevelAction::Fill { maker_id, quantity } => {
+                    let Some(maker) = makers.iter().find(|maker| maker.id == maker_id) else {
+                        continue;
+                    };
+                    let quantity = quantity.min(remaining).min(maker.remaining_quantity);
+                    remaining -= quantity;
+                    // A slice filled out shows the next, which trades on the next pass
+                    if quantity == maker.remaining_quantity {
+                        reserve = reserve.saturating_add(maker.hidden_quantity);
+                    }
+                }
+                LevelAction::Net { .. } => return None,
+                LevelAction::Decline { .. } => {}
+            }
+        }
+        remaining -= remaining.min(reserve);
+        Some(taker.remaining_quantity - remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::{OrderBookConfig, OrderBookError, OrderEvent};
+    use std::sync::{Arc, Mutex};
+
+    fn all_or_nothing_book() -> OrderBook {
+        let config = OrderBookConfig {
+            market_order_policy: MarketOrderPolicy::RejectIfIncomplete,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(order(1, Side::Sell, 5000, 30)).unwrap();
+        let mut iceberg = order(2, Side::Sell, 5100, 50);
+        iceberg.display_quantity = Some(10);
+        book.process_limit_order(iceberg).unwrap();
+        book
+    }
+
+    #[test]
+    fn test_incomplete_market_order_leaves_book_unchanged() {
+        let mut book = all_or_nothing_book();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        book.add_listener(Box::new(move |event| {
+            if let OrderEvent::Trade(trade) = event {
+                sink.lock().unwrap().push(trade.id);
+            }
+        }));
+        let before = book.get_depth(10);
+
+        let result = book.process_market_order(order(3, Side::Buy, 0, 81));
+        assert_eq!(result.unwrap_err(), OrderBookError::MarketOrderIncomplete);
+        assert!(events.lock().unwrap().is_empty());
+        assert_eq!(book.get_depth(10), before);
+        assert_eq!(book.get_order_remaining(1), Some(30));
+        assert_eq!(book.get_order_remaining(2), Some(50));
+        assert_eq!((book.total_trades, book.total_volume), (0, 0));
+
+        // Exactly what the book holds, iceberg reserve included, fills
+        let result = book.process_market_order(order(4, Side::Buy, 0, 80)).unwrap();
+        assert_eq!(result.order.status, OrderStatus::Filled);
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn test_limits_and_own_orders_count_against_completion() {
+        let mut book = all_or_nothing_book();
+        let mut capped = order(3, Side::Buy, 0, 40);
+        capped.max_levels = Some(1);
+        assert_eq!(
+            book.process_market_order(capped).unwrap_err(),
+            OrderBookError::MarketOrderIncomplete
+        );
+
+        // Only the iceberg's 50 is someone else's
+        let mut own = order(3, Side::Buy, 0, 60);
+        own.user_id = "user1".to_string();
+        assert_eq!(
+            book.process_market_order(own).unwrap_err(),
+            OrderBookError::MarketOrderIncomplete
+        );
+        assert_eq!(book.get_order_status(1), Some(OrderStatus::Open));
+
+        // The default policy cancels the remainder
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Sell, 5000, 30)).unwrap();
+        let result = book.process_market_order(order(2, Side::Buy, 0, 50)).unwrap();
+        assert_eq!(result.ack.filled_quantity, 30);
+        assert_eq!(result.ack.cancelled_quantity, 20);
+    }
+}
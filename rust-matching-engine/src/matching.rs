@@ -0,0 +1,562 @@
+//! Allocation of an incoming order's quantity across a price level
+//!
+//! Price priority is always enforced by the book: levels are visited best
+//! price first. What differs between matching regimes is how the quantity
+//! taken from a single level is split among the orders resting there, which
+//! is the job of a [`MatchingAlgorithm`]. An algorithm only plans: it returns
+//! the [`LevelAction`]s for one level and the book applies them, so
+//! algorithms can be tested without a book.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::{BookOrder, Order, OrderId, Quantity, SelfTradePolicy};
+
+/// How quantity is allocated among the orders at one price level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchingMode {
+    /// Strict FIFO: the earliest order fills completely before the next one
+    #[default]
+    PriceTime,
+    /// Proportional to resting size, see [`pro_rata_allocation`]
+    ProRata,
+    /// The first `fifo_portion` of each level's fill by strict FIFO, the rest
+    /// pro-rata, see [`hybrid_allocation`]
+    Hybrid { fifo_portion: Quantity },
+}
+
+impl MatchingMode {
+    /// The algorithm implementing this mode
+    pub fn algorithm(self) -> Arc<dyn MatchingAlgorithm> {
+        match self {
+            MatchingMode::PriceTime => Arc::new(PriceTimeMatching),
+            MatchingMode::ProRata => Arc::new(ProRataMatching),
+            MatchingMode::Hybrid { fifo_portion } => Arc::new(HybridMatching { fifo_portion }),
+        }
+    }
+}
+
+/// One step of matching a taker against a price level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelAction {
+    /// Trade `quantity` with a maker
+    Fill { maker_id: OrderId, quantity: Quantity },
+    /// Net the taker against the same user's resting order without trading
+    /// (`SelfTradePolicy::DecrementAndCancel`)
+    Net { maker_id: OrderId },
+    /// Cancel a maker instead of trading with it: the taker's own order, or
+    /// a maker declining on last look
+    Decline { maker_id: OrderId },
+}
+
+impl LevelAction {
+    /// The maker the action applies to
+    pub fn maker_id(&self) -> OrderId {
+        match *self {
+            LevelAction::Fill { maker_id, .. }
+            | LevelAction::Net { maker_id }
+            | LevelAction::Decline { maker_id } => maker_id,
+        }
+    }
+}
+
+/// A matching regime: how a taker trades against the orders at one level
+pub trait MatchingAlgorithm: Debug + Send + Sync {
+    /// Plan how `taker` trades against one price level
+    ///
+    /// `makers` are the level's live orders in queue order. The book applies
+    /// the returned actions in order, clamping each fill to what the taker
+    /// and maker have left and ignoring actions on orders that are no longer
+    /// live. A fill for a maker queued ahead of the previous fill's maker is
+    /// applied separately, so plans need not be in queue order.
+    fn match_level(
+        &self,
+        taker: &Order,
+        makers: &[&BookOrder],
+        self_trade_policy: SelfTradePolicy,
+    ) -> Vec<LevelAction>;
+}
+
+/// Strict FIFO: [`MatchingMode::PriceTime`]
+///
+/// Makers are taken in queue order. The taker's own orders and makers
+/// declining on last look are handled as they are reached, so those queued
+/// behind the point where the taker runs out are left alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PriceTimeMatching;
+
+impl MatchingAlgorithm for PriceTimeMatching {
+    fn match_level(
+        &self,
+        taker: &Order,
+        makers: &[&BookOrder],
+        self_trade_policy: SelfTradePolicy,
+    ) -> Vec<LevelAction> {
+        let mut remaining = taker.remaining_quantity;
+        let mut actions = Vec::new();
+
+        for maker in makers {
+            if remaining == 0 {
+                break;
+            }
+            let maker_id = maker.id;
+            if maker.user_id == taker.user_id
+                && self_trade_policy == SelfTradePolicy::DecrementAndCancel
+            {
+                actions.push(LevelAction::Net { maker_id });
+                remaining -= remaining.min(maker.remaining_quantity);
+            } else if maker.user_id == taker.user_id || maker.last_look_rejects(taker.price) {
+                actions.push(LevelAction::Decline { maker_id });
+            } else {
+                let quantity = remaining.min(maker.remaining_quantity);
+                actions.push(LevelAction::Fill { maker_id, quantity });
+                remaining -= quantity;
+            }
+        }
+
+        actions
+    }
+}
+
+/// Proportional to resting size: [`MatchingMode::ProRata`]
+///
+/// The taker's own orders are netted (or declined) and makers declining on
+/// last look are cancelled before the split, across the whole level. The rest
+/// is divided by [`pro_rata_allocation`], with fills in queue order.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProRataMatching;
+
+impl MatchingAlgorithm for ProRataMatching {
+    fn match_level(
+        &self,
+        taker: &Order,
+        makers: &[&BookOrder],
+        self_trade_policy: SelfTradePolicy,
+    ) -> Vec<LevelAction> {
+        let plan = screen_level(taker, makers, self_trade_policy);
+        let allocation = pro_rata_allocation(plan.remaining, &plan.eligible);
+        plan.fill(allocation)
+    }
+}
+
+/// FIFO for the first shares, pro-rata for the rest: [`MatchingMode::Hybrid`]
+///
+/// The taker's own orders and last-look declines are handled across the
+/// whole level first, as in [`ProRataMatching`]; the split is then made by
+/// [`hybrid_allocation`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HybridMatching {
+    /// Quantity allocated by time priority before the pro-rata split
+    pub fifo_portion: Quantity,
+}
+
+impl MatchingAlgorithm for HybridMatching {
+    fn match_level(
+        &self,
+        taker: &Order,
+        makers: &[&BookOrder],
+        self_trade_policy: SelfTradePolicy,
+    ) -> Vec<LevelAction> {
+        let plan = screen_level(taker, makers, self_trade_policy);
+        let allocation = hybrid_allocation(plan.remaining, self.fifo_portion, &plan.eligible);
+        plan.fill(allocation)
+    }
+}
+
+/// A level plan after self-trade and last-look screening
+struct ScreenedLevel {
+    /// Nets and declines, in queue order
+    actions: Vec<LevelAction>,
+    /// Taker quantity left after netting
+    remaining: Quantity,
+    /// `(order_id, remaining)` of the makers that may trade, in queue order
+    eligible: Vec<(OrderId, Quantity)>,
+}
+
+impl ScreenedLevel {
+    /// Append fills for the eligible makers, skipping zero allocations
+    fn fill(mut self, allocation: Vec<Quantity>) -> Vec<LevelAction> {
+        self.actions.extend(
+            self.eligible
+                .iter()
+                .zip(allocation)
+                .filter(|&(_, quantity)| quantity > 0)
+                .map(|(&(maker_id, _), quantity)| LevelAction::Fill { maker_id, quantity }),
+        );
+        self.actions
+    }
+}
+
+/// Handle a whole level's self-trades and last-look declines up front
+///
+/// Under `DecrementAndCancel` the taker's own orders are netted in queue
+/// order; otherwise they are declined, as are makers whose last look rejects
+/// the taker. If netting uses up the taker, no maker is eligible.
+fn screen_level(
+    taker: &Order,
+    makers: &[&BookOrder],
+    self_trade_policy: SelfTradePolicy,
+) -> ScreenedLevel {
+    let netting = self_trade_policy == SelfTradePolicy::DecrementAndCancel;
+    let mut plan = ScreenedLevel {
+        actions: Vec::new(),
+        remaining: taker.remaining_quantity,
+        eligible: Vec::with_capacity(makers.len()),
+    };
+
+    if netting {
+        for maker in makers.iter().filter(|maker| maker.user_id == taker.user_id) {
+            if plan.remaining == 0 {
+                return plan;
+            }
+            plan.actions.push(LevelAction::Net { maker_id: maker.id });
+            plan.remaining -= plan.remaining.min(maker.remaining_quantity);
+        }
+    }
+
+    for maker in makers {
+        if maker.user_id == taker.user_id {
+            if !netting {
+                plan.actions.push(LevelAction::Decline { maker_id: maker.id });
+            }
+        } else if maker.last_look_rejects(taker.price) {
+            plan.actions.push(LevelAction::Decline { maker_id: maker.id });
+        } else {
+            plan.eligible.push((maker.id, maker.remaining_quantity));
+        }
+    }
+    plan
+}
+
+/// Split `quantity` across makers in proportion to their resting size
+///
+/// `makers` holds `(order_id, remaining)` pairs in queue order and the result
+/// holds each maker's allocation in the same order.
+///
+/// Each maker first receives `floor(quantity * size / total)`. The shares lost
+/// to flooring (always fewer than the number of makers) are then handed out one
+/// each by descending maker size, breaking ties by ascending order id. No RNG
+/// is involved, so two engines replaying the same log agree to the share. If
+/// `quantity` covers the whole level every maker is filled completely.
+pub fn pro_rata_allocation(quantity: Quantity, makers: &[(OrderId, Quantity)]) -> Vec<Quantity> {
+    let total: Quantity = makers.iter().map(|&(_, size)| size).sum();
+    if quantity >= total {
+        return makers.iter().map(|&(_, size)| size).collect();
+    }
+
+    let mut allocation: Vec<Quantity> = makers
+        .iter()
+        .map(|&(_, size)| (quantity as u128 * size as u128 / total as u128) as Quantity)
+        .collect();
+
+    let allocated: Quantity = allocation.iter().sum();
+    let mut leftover = quantity - allocated;
+
+    let mut order: Vec<usize> = (0..makers.len()).collect();
+    order.sort_by(|&a, &b| makers[b].1.cmp(&makers[a].1).then(makers[a].0.cmp(&makers[b].0)));
+
+    for index in order {
+        if leftover == 0 {
+            break;
+        }
+        // Flooring never reaches a maker's full size when quantity < total
+        allocation[index] += 1;
+        leftover -= 1;
+    }
+
+    allocation
+}
+
+/// Split `quantity` across makers: FIFO up to `fifo_portion`, then pro-rata
+///
+/// `makers` holds `(order_id, remaining)` pairs in queue order and the result
+/// holds each maker's allocation in the same order.
+///
+/// The first `min(quantity, fifo_portion)` is allocated in queue order, each
+/// maker filling completely before the next. The rest is split by
+/// [`pro_rata_allocation`] over what each maker has left after that phase.
+/// A `fifo_portion` of 0 is plain pro-rata; one covering the fill is plain
+/// FIFO.
+pub fn hybrid_allocation(
+    quantity: Quantity,
+    fifo_portion: Quantity,
+    makers: &[(OrderId, Quantity)],
+) -> Vec<Quantity> {
+    let mut fifo_left = quantity.min(fifo_portion);
+    let mut allocation: Vec<Quantity> = makers
+        .iter()
+        .map(|&(_, size)| {
+            let filled = fifo_left.min(size);
+            fifo_left -= filled;
+            filled
+        })
+        .collect();
+
+    let allocated: Quantity = allocation.iter().sum();
+    let residual: Vec<(OrderId, Quantity)> = makers
+        .iter()
+        .zip(&allocation)
+        .map(|(&(order_id, size), &filled)| (order_id, size - filled))
+        .collect();
+    let pro_rata = pro_rata_allocation(quantity - allocated, &residual);
+    for (filled, share) in allocation.iter_mut().zip(pro_rata) {
+        *filled += share;
+    }
+    allocation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockClock, Order, OrderBook, OrderBookConfig, SelfTradePolicy, Side};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_remainder_goes_to_lowest_order_id() {
+        // 101 against three equal makers: 33 each, the 2 leftover to ids 1 and 2
+        let allocation = pro_rata_allocation(101, &[(3, 100), (1, 100), (2, 100)]);
+        assert_eq!(allocation, vec![33, 34, 34]);
+    }
+
+    #[test]
+    fn test_remainder_prefers_larger_makers() {
+        // 10 against 50/30/20: floors are 5/3/2 with nothing left over
+        assert_eq!(pro_rata_allocation(10, &[(1, 50), (2, 30), (3, 20)]), vec![5, 3, 2]);
+        // 7 against 50/30/20: floors 3/2/1, leftover 1 to the largest
+        assert_eq!(pro_rata_allocation(7, &[(1, 20), (2, 50), (3, 30)]), vec![1, 4, 2]);
+    }
+
+    #[test]
+    fn test_quantity_covering_level_fills_everyone() {
+        assert_eq!(pro_rata_allocation(500, &[(1, 100), (2, 50)]), vec![100, 50]);
+        assert!(pro_rata_allocation(10, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_hybrid_fifo_phase_then_pro_rata() {
+        let level = [(1, 100), (2, 200), (3, 100)];
+
+        // 50 to the front order by time priority; the other 200 split over
+        // what is left (50/200/100 of 350): floors 28/114/57, leftover 1 to
+        // the largest
+        assert_eq!(hybrid_allocation(250, 50, &level), vec![78, 115, 57]);
+
+        // The FIFO phase can span orders
+        assert_eq!(hybrid_allocation(400, 150, &level), vec![100, 200, 100]);
+        assert_eq!(hybrid_allocation(160, 150, &level), vec![100, 56, 4]);
+
+        // Degenerate portions are the pure modes
+        assert_eq!(hybrid_allocation(101, 0, &level), pro_rata_allocation(101, &level));
+        assert_eq!(hybrid_allocation(150, 150, &level), vec![100, 50, 0]);
+    }
+
+    #[test]
+    fn test_hybrid_mode_in_book() {
+        let config = OrderBookConfig {
+            matching_mode: MatchingMode::Hybrid { fifo_portion: 50 },
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(resting(1, "maker1", 100)).unwrap();
+        book.process_limit_order(resting(2, "maker2", 200)).unwrap();
+        book.process_limit_order(resting(3, "maker3", 100)).unwrap();
+
+        let mut taker = resting(4, "taker", 250);
+        taker.side = Side::Buy;
+        let result = book.process_limit_order(taker).unwrap();
+        let fills: Vec<(OrderId, Quantity)> =
+            result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(1, 78), (2, 115), (3, 57)]);
+        assert_eq!(book.get_depth(1).1, vec![(5000, 150)]);
+    }
+
+    /// Every queued order (price, id, remaining, status), level by level
+    fn queue_state(book: &OrderBook) -> Vec<u64> {
+        let mut state = Vec::new();
+        for level in book.bids.iter().chain(book.asks.iter()) {
+            let (&price, level) = level;
+            state.extend([price, level.total_quantity]);
+            for order in &level.orders {
+                let status = book.get_order_status(order.id).map_or(0, |s| s as u64 + 1);
+                state.extend([order.id, order.remaining_quantity, status]);
+            }
+        }
+        state.push(book.metrics().pending_cancelled as u64);
+        state
+    }
+
+    /// FNV-1a digest of a pseudo-random session: every trade and the queue
+    /// state after every step
+    fn session_digest(config: OrderBookConfig) -> u64 {
+        let clock = Arc::new(MockClock::new(1));
+        let config = OrderBookConfig { clock: Some(clock.clone()), ..config };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        let mut digest: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut absorb = |bytes: &[u8]| {
+            for &byte in bytes {
+                digest = (digest ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        let mut seed: u64 = 42;
+        let mut next = || {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            seed >> 33
+        };
+
+        for id in 1..=400 {
+            clock.advance(1);
+            if next() % 4 == 0 {
+                let _ = book.cancel_order(next() % id);
+            } else {
+                let r = next();
+                let side = if r % 2 == 0 { Side::Buy } else { Side::Sell };
+                let mut order = Order::with_timestamp(
+                    id,
+                    format!("user{}", r % 3),
+                    "market1".to_string(),
+                    "YES".to_string(),
+                    side,
+                    4950 + 10 * (next() % 8),
+                    1 + next() % 120,
+                    id,
+                );
+                if next() % 5 == 0 {
+                    order.last_look_bps = Some(20);
+                }
+                if next() % 7 == 0 {
+                    order.max_levels = Some(1 + next() as usize % 2);
+                }
+                if let Ok(result) = book.process_limit_order(order) {
+                    for trade in &result.trades {
+                        for value in [
+                            trade.id,
+                            trade.taker_order_id,
+                            trade.maker_order_id,
+                            trade.price,
+                            trade.quantity,
+                            trade.timestamp,
+                        ] {
+                            absorb(&value.to_le_bytes());
+                        }
+                    }
+                }
+            }
+            for value in queue_state(&book) {
+                absorb(&value.to_le_bytes());
+            }
+        }
+        digest
+    }
+
+    #[test]
+    fn test_price_time_matches_legacy_fifo() {
+        // Digests recorded from the inline FIFO matcher this module replaced,
+        // re-recorded once levels left without live quantity were dropped at
+        // once; the trades were unchanged, only the queued cancelled orders
+        for (policy, expected) in [
+            (SelfTradePolicy::CancelResting, 9_661_825_817_262_984_400),
+            (SelfTradePolicy::RejectTaker, 3_218_957_275_696_672_308),
+            (SelfTradePolicy::DecrementAndCancel, 841_756_998_780_159_381),
+        ] {
+            let config = OrderBookConfig { self_trade_policy: policy, ..Default::default() };
+            assert_eq!(session_digest(config), expected, "{:?}", policy);
+        }
+    }
+
+    fn resting(id: OrderId, user: &str, quantity: Quantity) -> Order {
+        Order::with_timestamp(
+            id,
+            user.to_string(),
+            "market1".to_string(),
+            "YES".to_string(),
+            Side::Sell,
+            5000,
+            quantity,
+            id,
+        )
+    }
+
+    #[test]
+    fn test_price_time_plan_without_book() {
+        let mut stale = resting(2, "maker2", 40);
+        stale.last_look_bps = Some(50);
+        let makers = [
+            resting(1, "maker1", 30),
+            stale,
+            resting(3, "taker", 20),
+            resting(4, "maker4", 100),
+        ];
+        let makers: Vec<BookOrder> = makers.iter().map(BookOrder::from).collect();
+        let makers: Vec<&BookOrder> = makers.iter().collect();
+
+        let mut taker = resting(9, "taker", 80);
+        taker.side = Side::Buy;
+        taker.price = 5100;
+
+        let plan = PriceTimeMatching.match_level(&taker, &makers, SelfTradePolicy::CancelResting);
+        assert_eq!(
+            plan,
+            vec![
+                LevelAction::Fill { maker_id: 1, quantity: 30 },
+                LevelAction::Decline { maker_id: 2 },
+                LevelAction::Decline { maker_id: 3 },
+                LevelAction::Fill { maker_id: 4, quantity: 50 },
+            ]
+        );
+
+        let netting = SelfTradePolicy::DecrementAndCancel;
+        let plan = PriceTimeMatching.match_level(&taker, &makers, netting);
+        assert_eq!(plan[2], LevelAction::Net { maker_id: 3 });
+        assert_eq!(plan[3], LevelAction::Fill { maker_id: 4, quantity: 30 });
+    }
+
+    /// Newest order first, to check a custom algorithm drives the book
+    #[derive(Debug)]
+    struct LastInFirstOut;
+
+    impl MatchingAlgorithm for LastInFirstOut {
+        fn match_level(
+            &self,
+            taker: &Order,
+            makers: &[&BookOrder],
+            _: SelfTradePolicy,
+        ) -> Vec<LevelAction> {
+            let mut remaining = taker.remaining_quantity;
+            let mut actions = Vec::new();
+            for maker in makers.iter().rev() {
+                let quantity = remaining.min(maker.remaining_quantity);
+                if quantity == 0 {
+                    break;
+                }
+                actions.push(LevelAction::Fill { maker_id: maker.id, quantity });
+                remaining -= quantity;
+            }
+            actions
+        }
+    }
+
+    #[test]
+    fn test_custom_algorithm_overrides_mode() {
+        let config = OrderBookConfig {
+            matching_algorithm: Some(Arc::new(LastInFirstOut)),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        for id in 1..=3 {
+            book.process_limit_order(resting(id, &format!("maker{}", id), 10)).unwrap();
+        }
+
+        let mut taker = resting(4, "taker", 25);
+        taker.side = Side::Buy;
+        let result = book.process_limit_order(taker).unwrap();
+        let fills: Vec<(OrderId, Quantity)> =
+            result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(3, 10), (2, 10), (1, 5)]);
+        assert_eq!(book.get_order_remaining(1), Some(5));
+        assert_eq!(book.get_depth(1).1, vec![(5000, 5)]);
+    }
+}
@@ -0,0 +1,221 @@
+//! Midpoint-pegged dark orders
+//!
+//! An order with [`OrderType::MidpointPeg`] never rests on a price level.
+//! It waits in a hidden queue per side, pegged to the midpoint of the live
+//! best bid and ask, so it shows in no depth, best price or snapshot level,
+//! and it reprices whenever the best prices move. Any incoming order that
+//! reaches the midpoint trades against the opposite queue first, in time
+//! order, at the midpoint, then goes on to the lit levels. A midpoint order
+//! itself only trades against the opposite queue; it never takes lit
+//! liquidity.
+//!
+//! The order's `price` is a limit on the peg: a midpoint buy doesn't trade
+//! while the midpoint is above it, nor a sell while it is below. With one
+//! side of the book empty there is no midpoint and every midpoint order is
+//! parked until both sides are live again. Midpoint orders skip the taker's
+//! own orders instead of applying the self-trade policy.
+
+use std::collections::VecDeque;
+
+use crate::{
+    BookOrder, Order, OrderBook, OrderId, OrderStatus, Price, Quantity, Side, TradeSink,
+};
+
+/// The hidden queues of resting midpoint orders, in time order
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MidpointOrders {
+    bids: VecDeque<BookOrder>,
+    asks: VecDeque<BookOrder>,
+}
+
+impl MidpointOrders {
+    fn side(&self, side: Side) -> &VecDeque<BookOrder> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut VecDeque<BookOrder> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    /// Queue an order behind the others on its side
+    pub(crate) fn push(&mut self, order: BookOrder) {
+        self.side_mut(order.side).push_back(order);
+    }
+
+    /// Take an order out of its queue
+    pub(crate) fn remove(&mut self, side: Side, order_id: OrderId) -> Option<BookOrder> {
+        let queue = self.side_mut(side);
+        let position = queue.iter().position(|o| o.id == order_id)?;
+        queue.remove(position)
+    }
+
+    /// Number of orders queued ahead of `order_id` on its side
+    pub(crate) fn ahead(&self, side: Side, order_id: OrderId) -> usize {
+        self.side(side).iter().take_while(|o| o.id != order_id).count()
+    }
+
+    /// Both queues, bids first, each in time order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &BookOrder> {
+        self.bids.iter().chain(&self.asks)
+    }
+
+    /// Empty both queues, bids first, each in time order
+    pub(crate) fn take_all(&mut self) -> Vec<BookOrder> {
+        let mut orders = Vec::from(std::mem::take(&mut self.bids));
+        orders.extend(std::mem::take(&mut self.asks));
+        orders
+    }
+}
+
+impl OrderBook {
+    /// Midpoint of the live best bid and ask, rounded with the book's `RoundingMode`
+    ///
+    /// None if either side has no live orders.
+    pub fn midpoint(&self) -> Option<Price> {
+        let bid = self.best_live_price(Side::Buy)?;
+        let ask = self.best_live_price(Side::Sell)?;
+        Some(self.config.rounding_mode.div(bid as u128 + ask as u128, 2) as Price)
+    }
+
+    /// Quantity resting in midpoint orders on a side, hidden from depth
+    pub fn midpoint_quantity(&self, side: Side) -> Quantity {
+        self.midpoint_orders.side(side).iter().map(|o| o.remaining_quantity).sum()
+    }
+
+    /// Trade an incoming order against the opposite midpoint queue
+    ///
+    /// Every trade prints at the midpoint as it stood when the order arrived,
+    /// whatever the `ExecutionPricePolicy`.
+    pub(crate) fn match_midpoint(&mut self, order: &mut Order, trades: &mut TradeSink<'_>) {
+        let Some(mid) = self.midpoint() else {
+            return;
+        };
+        if !order.side.reaches(order.price, mid) {
+            return;
+        }
+        let maker_side = order.side.opposite();
+
+        let mut position = 0;
+        while order.remaining_quantity > 0 {
+            let queue = self.midpoint_orders.side_mut(maker_side);
+            let Some(maker) = queue.get_mut(position) else {
+                break;
+            };
+            if maker.user_id == order.user_id || !maker_side.reaches(maker.price, mid) {
+                position += 1;
+                continue;
+            }
+
+            let quantity = order.remaining_quantity.min(maker.remaining_quantity);
+            maker.remaining_quantity -= quantity;
+            maker.status = if maker.remaining_quantity == 0 {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::PartiallyFilled
+            };
+            let (maker_id, maker_user_id) = (maker.id, maker.user_id.clone());
+            let (remaining, status) = (maker.remaining_quantity, maker.status);
+            if status == OrderStatus::Filled {
+                if let Some(filled) = queue.remove(position) {
+                    self.live_orders -= 1;
+                    Self::unindex_user_order(&mut self.user_orders, &filled.user_id, maker_id);
+                    Self::unindex_client_order(
+                        &mut self.client_orders,
+                        &filled.user_id,
+                        filled.client_order_id.as_deref(),
+                    );
+                }
+            } else {
+                position += 1;
+            }
+            if let Some(metadata) = self.order_index.get_mut(&maker_id) {
+                metadata.remaining_quantity = remaining;
+                metadata.status = status;
+            }
+
+            let mut trade = self.new_trade(order, maker_id, maker_user_id, mid, quantity);
+            trade.price = mid;
+            self.emit_trade(trade, trades);
+            self.record_maker_fill(maker_id, quantity, remaining);
+            order.remaining_quantity -= quantity;
+            if status == OrderStatus::Filled {
+                self.archive_orders(&[maker_id]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::{OrderBookError, OrderType};
+
+    fn pegged(id: OrderId, side: Side, limit: Price, quantity: Quantity) -> Order {
+        let mut order = order(id, side, limit, quantity);
+        order.order_type = OrderType::MidpointPeg;
+        order
+    }
+
+    #[test]
+    fn test_midpoint_order_fills_at_mid() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Buy, 5000, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 6000, 100)).unwrap();
+        assert_eq!(book.midpoint(), Some(5500));
+
+        // Rests hidden: depth and best prices don't move
+        let result = book.process_limit_order(pegged(3, Side::Buy, 9000, 50)).unwrap();
+        assert!(result.trades.is_empty());
+        assert_eq!((result.ack.resting, result.ack.queue_position), (true, Some(0)));
+        assert_eq!(book.get_depth(5), (vec![(5000, 100)], vec![(6000, 100)]));
+        assert_eq!(book.midpoint_quantity(Side::Buy), 50);
+
+        // A sell reaching the mid trades with it first, at the mid
+        let result = book.process_limit_order(order(4, Side::Sell, 5000, 80)).unwrap();
+        let fills: Vec<_> =
+            result.trades.iter().map(|t| (t.maker_order_id, t.price, t.quantity)).collect();
+        assert_eq!(fills, vec![(3, 5500, 50), (1, 5000, 30)]);
+        assert_eq!(book.get_order_status(3), Some(OrderStatus::Filled));
+        assert_eq!(book.midpoint_quantity(Side::Buy), 0);
+    }
+
+    #[test]
+    fn test_midpoint_order_reprices_and_parks() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+
+        // No mid yet: parked, even against an opposite midpoint order
+        book.process_limit_order(pegged(1, Side::Sell, 4000, 40)).unwrap();
+        let result = book.process_limit_order(pegged(2, Side::Buy, 9000, 10)).unwrap();
+        assert!(result.trades.is_empty());
+        book.cancel_order(2).unwrap();
+        assert_eq!(book.midpoint_quantity(Side::Buy), 0);
+
+        book.process_limit_order(order(3, Side::Buy, 5000, 100)).unwrap();
+        let result = book.process_limit_order(order(4, Side::Buy, 5100, 10)).unwrap();
+        assert!(result.trades.is_empty(), "no mid with the ask side empty");
+
+        // The mid follows the best prices
+        book.process_limit_order(order(5, Side::Sell, 5400, 100)).unwrap();
+        let result = book.process_limit_order(pegged(6, Side::Buy, 9000, 10)).unwrap();
+        assert_eq!((result.trades[0].price, result.trades[0].quantity), (5250, 10));
+        book.process_limit_order(order(7, Side::Sell, 5200, 100)).unwrap();
+        let result = book.process_limit_order(pegged(8, Side::Buy, 9000, 10)).unwrap();
+        assert_eq!(result.trades[0].price, 5150);
+
+        // A limit below the mid holds the order back
+        book.process_limit_order(pegged(9, Side::Sell, 5200, 10)).unwrap();
+        let result = book.process_limit_order(pegged(10, Side::Buy, 9000, 30)).unwrap();
+        let fills: Vec<_> = result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(1, 20)]);
+        assert_eq!(book.midpoint_quantity(Side::Buy), 10);
+
+        assert_eq!(book.amend_order(9, 5100, 10), Err(OrderBookError::PeggedOrder(9)));
+    }
+}
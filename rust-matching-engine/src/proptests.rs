@@ -0,0 +1,318 @@
+//! Property-based tests for the order book invariants.
+//!
+//! Random sequences of submits, cancels and amends are applied to a fresh book
+//! while a small model tracks every accepted order. After each operation the
+//! book is checked against the model:
+//!
+//! - Quantity is conserved: every order's fills, its live remainder and the
+//!   quantity withdrawn from it (cancelled or netted) equal its original size
+//! - No order is filled beyond its original quantity
+//! - The best live bid never exceeds the best live ask, and the best bid and
+//!   ask are the live ones
+//! - Each level's total equals the sum of its live orders' remainders, and no
+//!   level is left without live quantity
+//! - No trade has the same user on both sides
+//! - The maintained live and queued-cancelled order counters match a scan
+//! - The per-user index lists exactly the live orders
+//! - The book's own health check passes
+
+use super::*;
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Submit {
+        user: u8,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    },
+    /// Cancel the n-th accepted order (modulo the number accepted so far)
+    Cancel { nth: usize },
+    /// Amend the n-th accepted order to a new price and remaining quantity
+    Amend {
+        nth: usize,
+        price: Price,
+        quantity: Quantity,
+    },
+}
+
+fn side_strategy() -> impl Strategy<Value = Side> {
+    prop_oneof![Just(Side::Buy), Just(Side::Sell)]
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0u8..4, side_strategy(), 0u64..10, 1u64..200).prop_map(
+            |(user, side, tick, quantity)| Op::Submit {
+                user,
+                side,
+                price: 4950 + tick * 10,
+                quantity,
+            }
+        ),
+        1 => any::<usize>().prop_map(|nth| Op::Cancel { nth }),
+        1 => (any::<usize>(), 0u64..10, 1u64..200).prop_map(|(nth, tick, quantity)| Op::Amend {
+            nth,
+            price: 4950 + tick * 10,
+            quantity,
+        }),
+    ]
+}
+
+/// What the model knows about an accepted order
+#[derive(Debug)]
+struct ModelOrder {
+    original: Quantity,
+    filled: Quantity,
+    /// Quantity removed without trading: cancelled, or netted by self-trade
+    /// prevention
+    withdrawn: Quantity,
+}
+
+/// Remaining quantity of an order if it is still live, else 0
+fn live_remaining(book: &OrderBook, id: OrderId) -> Quantity {
+    match book.get_order_status(id) {
+        Some(OrderStatus::Open | OrderStatus::PartiallyFilled) => {
+            book.get_order_remaining(id).unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+fn live_remaining_at(book: &OrderBook, level: &PriceLevelQueue) -> Quantity {
+    level
+        .orders
+        .iter()
+        .filter(|o| {
+            matches!(
+                book.get_order_status(o.id),
+                Some(OrderStatus::Open | OrderStatus::PartiallyFilled)
+            )
+        })
+        .map(|o| book.get_order_remaining(o.id).unwrap_or(0))
+        .sum()
+}
+
+fn best_live(book: &OrderBook, side: Side) -> Option<Price> {
+    let live = |level: &PriceLevelQueue| live_remaining_at(book, level) > 0;
+    match side {
+        Side::Buy => book
+            .bids
+            .iter()
+            .rev()
+            .find(|(_, l)| live(l))
+            .map(|(&p, _)| p),
+        Side::Sell => book.asks.iter().find(|(_, l)| live(l)).map(|(&p, _)| p),
+    }
+}
+
+fn check_invariants(
+    book: &OrderBook,
+    model: &HashMap<OrderId, ModelOrder>,
+) -> Result<(), TestCaseError> {
+    for (&id, order) in model {
+        prop_assert!(
+            order.filled <= order.original,
+            "order {} overfilled: {} > {}",
+            id,
+            order.filled,
+            order.original
+        );
+
+        prop_assert_eq!(
+            order.filled + order.withdrawn + live_remaining(book, id),
+            order.original,
+            "quantity not conserved for order {}",
+            id
+        );
+    }
+
+    if let (Some(bid), Some(ask)) = (best_live(book, Side::Buy), best_live(book, Side::Sell)) {
+        prop_assert!(bid <= ask, "crossed book: bid {} > ask {}", bid, ask);
+    }
+    prop_assert_eq!(best_live(book, Side::Buy), book.best_bid(), "stale best bid");
+    prop_assert_eq!(best_live(book, Side::Sell), book.best_ask(), "stale best ask");
+
+    let live = model
+        .keys()
+        .filter(|&&id| {
+            matches!(
+                book.get_order_status(id),
+                Some(OrderStatus::Open | OrderStatus::PartiallyFilled)
+            )
+        })
+        .count();
+    prop_assert_eq!(book.active_orders(), live, "live order counter drifted");
+
+    let mut indexed = 0;
+    for user in 0..4 {
+        for id in book.user_orders(&format!("user{}", user)) {
+            prop_assert!(
+                matches!(
+                    book.get_order_status(id),
+                    Some(OrderStatus::Open | OrderStatus::PartiallyFilled)
+                ),
+                "user index lists order {} that is not live",
+                id
+            );
+            indexed += 1;
+        }
+    }
+    prop_assert_eq!(indexed, live, "user index misses live orders");
+
+    let queued_cancelled = book
+        .bids
+        .values()
+        .chain(book.asks.values())
+        .flat_map(|level| &level.orders)
+        .filter(|o| book.get_order_status(o.id) == Some(OrderStatus::Cancelled))
+        .count();
+    prop_assert_eq!(
+        book.metrics().pending_cancelled,
+        queued_cancelled,
+        "queued cancelled counter drifted"
+    );
+
+    prop_assert!(book.is_healthy(), "unhealthy book: {:?}", book.health());
+
+    for (price, level) in book.bids.iter().chain(book.asks.iter()) {
+        prop_assert_eq!(
+            level.total_quantity,
+            live_remaining_at(book, level),
+            "level total mismatch at {}",
+            price
+        );
+        prop_assert!(level.total_quantity > 0, "level without live quantity at {}", price);
+    }
+
+    Ok(())
+}
+
+fn run_operations(ops: Vec<Op>, config: OrderBookConfig) -> Result<(), TestCaseError> {
+    let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+    let mut model: HashMap<OrderId, ModelOrder> = HashMap::new();
+    let mut accepted: Vec<OrderId> = Vec::new();
+    let mut next_id: OrderId = 1;
+
+    for (step, op) in ops.into_iter().enumerate() {
+        match op {
+            Op::Submit { user, side, price, quantity } => {
+                let id = next_id;
+                next_id += 1;
+                let order = Order::with_timestamp(
+                    id,
+                    format!("user{}", user),
+                    "market1".to_string(),
+                    "YES".to_string(),
+                    side,
+                    price,
+                    quantity,
+                    step as Timestamp,
+                );
+                // Self-trade prevention may cancel or net resting orders while
+                // matching, so note what each one had left beforehand
+                let remaining_before: Vec<(OrderId, Quantity)> = accepted
+                    .iter()
+                    .map(|&id| (id, live_remaining(&book, id)))
+                    .collect();
+
+                let result = book.process_limit_order(order).unwrap();
+
+                let mut step_fills: HashMap<OrderId, Quantity> = HashMap::new();
+                for trade in &result.trades {
+                    prop_assert_ne!(&trade.taker_user_id, &trade.maker_user_id);
+                    for party in [trade.taker_order_id, trade.maker_order_id] {
+                        *step_fills.entry(party).or_default() += trade.quantity;
+                    }
+                }
+
+                for (id, before) in remaining_before {
+                    let filled = step_fills.get(&id).copied().unwrap_or(0);
+                    let order = model.get_mut(&id).unwrap();
+                    order.filled += filled;
+                    order.withdrawn += before - live_remaining(&book, id) - filled;
+                }
+
+                let filled = step_fills.get(&id).copied().unwrap_or(0);
+                let remaining = result.order.remaining_quantity;
+                if remaining > 0 {
+                    prop_assert_eq!(live_remaining(&book, id), remaining);
+                }
+                model.insert(
+                    id,
+                    ModelOrder { original: quantity, filled, withdrawn: quantity - filled - remaining },
+                );
+                accepted.push(id);
+            }
+            Op::Amend { nth, price, quantity } => {
+                if accepted.is_empty() {
+                    continue;
+                }
+                let id = accepted[nth % accepted.len()];
+                let remaining = live_remaining(&book, id);
+                if book.amend_order(id, price, quantity).is_ok() {
+                    // The amended size replaces what was left unfilled
+                    let order = model.get_mut(&id).unwrap();
+                    order.original = order.original - remaining + quantity;
+                }
+            }
+            Op::Cancel { nth } => {
+                if accepted.is_empty() {
+                    continue;
+                }
+                let id = accepted[nth % accepted.len()];
+                let remaining = live_remaining(&book, id);
+                if book.cancel_order(id).is_ok() {
+                    model.get_mut(&id).unwrap().withdrawn += remaining;
+                }
+            }
+        }
+
+        check_invariants(&book, &model)?;
+    }
+
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn invariants_hold_for_price_time(ops in prop::collection::vec(op_strategy(), 1..80)) {
+        run_operations(ops, OrderBookConfig::default())?;
+    }
+
+    #[test]
+    fn invariants_hold_for_pro_rata(ops in prop::collection::vec(op_strategy(), 1..80)) {
+        let config = OrderBookConfig {
+            matching_mode: MatchingMode::ProRata,
+            ..Default::default()
+        };
+        run_operations(ops, config)?;
+    }
+
+    #[test]
+    fn invariants_hold_for_hybrid(
+        ops in prop::collection::vec(op_strategy(), 1..80),
+        fifo_portion in 0u64..150,
+    ) {
+        let config = OrderBookConfig {
+            matching_mode: MatchingMode::Hybrid { fifo_portion },
+            ..Default::default()
+        };
+        run_operations(ops, config)?;
+    }
+
+    #[test]
+    fn invariants_hold_with_self_trade_netting(
+        ops in prop::collection::vec(op_strategy(), 1..80),
+        pro_rata in any::<bool>(),
+    ) {
+        let config = OrderBookConfig {
+            self_trade_policy: SelfTradePolicy::DecrementAndCancel,
+            matching_mode: if pro_rata { MatchingMode::ProRata } else { MatchingMode::PriceTime },
+            ..Default::default()
+        };
+        run_operations(ops, config)?;
+    }
+}
@@ -0,0 +1,239 @@
+//! Two-sided quoting for market makers
+//!
+//! A quote is a bid and an ask placed together by one user. Submitting a new
+//! quote replaces the user's previous one atomically: both legs are validated,
+//! and the user's rate limit checked for room for both, before anything
+//! changes, so a bad leg leaves the old quote resting.
+//!
+//! Quote legs get their order ids from the book, starting above every id the
+//! book has accepted so far (or at the start of `id_range`, if configured).
+
+use crate::{
+    Order, OrderBook, OrderBookError, OrderEvent, OrderId, Price, ProcessOrderResult, Quantity,
+    SelfTradePolicy, Side,
+};
+
+/// Outcome of a quote submission
+#[derive(Debug)]
+pub struct QuoteResult {
+    /// The bid leg: its order id, resting remainder and immediate fills
+    pub bid: ProcessOrderResult,
+    /// The ask leg
+    pub ask: ProcessOrderResult,
+}
+
+impl OrderBook {
+    /// Replace a user's two-sided quote
+    ///
+    /// Cancels the user's previous quote, if any legs of it are still live,
+    /// then places the bid followed by the ask. Either leg may trade on entry.
+    ///
+    /// The replacement is atomic: if either leg is invalid, the user's rate
+    /// limit has no room for both legs, or the bid is not strictly below the
+    /// ask (`WouldSelfCross`), an error is returned and neither the old quote
+    /// nor the rest of the book is touched. Should a leg still be refused once
+    /// the old quote is gone, a placed bid is cancelled (fills it already made
+    /// stand) and `quote` keeps reporting the old legs, so no leg is left
+    /// resting outside a quote.
+    pub fn submit_quote(
+        &mut self,
+        user_id: &str,
+        bid_price: Price,
+        bid_qty: Quantity,
+        ask_price: Price,
+        ask_qty: Quantity,
+    ) -> Result<QuoteResult, OrderBookError> {
+        let bid_id = self.next_order_id;
+        let ask_id = bid_id.saturating_add(1);
+        let mut bid = self.quote_leg(bid_id, user_id, Side::Buy, bid_price, bid_qty);
+        let mut ask = self.quote_leg(ask_id, user_id, Side::Sell, ask_price, ask_qty);
+
+        if let Err(error) = self.validate_quote(&mut bid, &mut ask) {
+            self.listeners.emit(OrderEvent::Rejected {
+                order_id: bid_id,
+                client_order_id: None,
+                code: error.code(),
+            });
+            return Err(error);
+        }
+
+        let replaced = self.quotes.remove(user_id);
+        if let Some((old_bid, old_ask)) = replaced {
+            // Legs that already filled or were cancelled are simply gone
+            let _ = self.cancel_resting(old_bid);
+            let _ = self.cancel_resting(old_ask);
+        }
+
+        let placed = self.process_limit_order(bid).and_then(|bid| {
+            match self.process_limit_order(ask) {
+                Ok(ask) => Ok(QuoteResult { bid, ask }),
+                Err(error) => {
+                    // The bid's fills stand, but it must not rest outside a quote
+                    let _ = self.cancel_resting(bid_id);
+                    Err(error)
+                }
+            }
+        });
+        match placed {
+            Ok(result) => {
+                self.quotes.insert(user_id.to_string(), (bid_id, ask_id));
+                Ok(result)
+            }
+            Err(error) => {
+                if let Some(old) = replaced {
+                    self.quotes.insert(user_id.to_string(), old);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Ids of the user's current quote (bid, ask), if one was placed
+    ///
+    /// Either leg may since have been filled or cancelled.
+    pub fn quote(&self, user_id: &str) -> Option<(OrderId, OrderId)> {
+        self.quotes.get(user_id).copied()
+    }
+
+    fn quote_leg(
+        &self,
+        id: OrderId,
+        user_id: &str,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    ) -> Order {
+        Order::with_timestamp(
+            id,
+            user_id.to_string(),
+            self.market_id.clone(),
+            self.outcome_id.clone(),
+            side,
+            price,
+            quantity,
+            self.clock.now(),
+        )
+    }
+
+    /// Check both legs as they will be submitted once the old quote is gone
+    ///
+    /// Leg prices between ticks are snapped or rejected first, per `TickPolicy`.
+    fn validate_quote(&self, bid: &mut Order, ask: &mut Order) -> Result<(), OrderBookError> {
+        self.check_open()?;
+        self.check_rate_for(&bid.user_id, 2)?;
+        bid.price = self.check_tick(bid.price)?;
+        ask.price = self.check_tick(ask.price)?;
+        self.check_order(bid)?;
+        self.check_order(ask)?;
+        if Side::Buy.reaches(bid.price, ask.price) {
+            return Err(OrderBookError::WouldSelfCross);
+        }
+
+        if self.config.self_trade_policy == SelfTradePolicy::RejectTaker {
+            let replaced: Vec<OrderId> = self
+                .quotes
+                .get(&bid.user_id)
+                .map(|&(old_bid, old_ask)| vec![old_bid, old_ask])
+                .unwrap_or_default();
+            if self.crosses_own_order(bid, &replaced) || self.crosses_own_order(ask, &replaced) {
+                return Err(OrderBookError::WouldSelfCross);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockClock, OrderBookConfig, OrderStatus, RateLimit};
+    use std::sync::Arc;
+
+    fn book() -> OrderBook {
+        OrderBook::new("market1".to_string(), "YES".to_string())
+    }
+
+    #[test]
+    fn test_replacing_quote_updates_both_sides() {
+        let mut book = book();
+        let first = book.submit_quote("mm", 4900, 100, 5100, 100).unwrap();
+        let (old_bid, old_ask) = (first.bid.order.id, first.ask.order.id);
+        assert_eq!(book.best_bid(), Some(4900));
+        assert_eq!(book.best_ask(), Some(5100));
+
+        let second = book.submit_quote("mm", 4950, 50, 5050, 60).unwrap();
+        assert_eq!(book.quote("mm"), Some((second.bid.order.id, second.ask.order.id)));
+        assert_eq!(book.get_order_status(old_bid), Some(OrderStatus::Cancelled));
+        assert_eq!(book.get_order_status(old_ask), Some(OrderStatus::Cancelled));
+        // The old legs' levels had nothing else live, so they are gone
+        let (bids, asks) = book.get_depth(5);
+        assert_eq!(bids, vec![(4950, 50)]);
+        assert_eq!(asks, vec![(5050, 60)]);
+        assert_eq!(book.active_orders(), 2);
+    }
+
+    #[test]
+    fn test_quote_fills_immediately() {
+        let mut book = book();
+        let resting = Order::with_timestamp(
+            1,
+            "seller".to_string(),
+            "market1".to_string(),
+            "YES".to_string(),
+            Side::Sell,
+            5000,
+            30,
+            1,
+        );
+        book.process_limit_order(resting).unwrap();
+
+        // Ids continue above the caller-assigned ones
+        let result = book.submit_quote("mm", 5000, 100, 5200, 100).unwrap();
+        assert_eq!((result.bid.order.id, result.ask.order.id), (2, 3));
+        assert_eq!(result.bid.trades.len(), 1);
+        assert_eq!(result.bid.trades[0].quantity, 30);
+        assert_eq!(book.get_order_remaining(2), Some(70));
+    }
+
+    #[test]
+    fn test_invalid_leg_keeps_old_quote() {
+        let mut book = book();
+        let first = book.submit_quote("mm", 4900, 100, 5100, 100).unwrap();
+
+        assert_eq!(
+            book.submit_quote("mm", 4950, 50, 5050, 0).unwrap_err(),
+            OrderBookError::InvalidQuantity
+        );
+        assert_eq!(
+            book.submit_quote("mm", 5100, 50, 5000, 50).unwrap_err(),
+            OrderBookError::WouldSelfCross
+        );
+
+        assert_eq!(book.quote("mm"), Some((first.bid.order.id, first.ask.order.id)));
+        assert_eq!(book.get_depth(5), (vec![(4900, 100)], vec![(5100, 100)]));
+    }
+
+    #[test]
+    fn test_rate_limited_quote_keeps_old_quote() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let config = OrderBookConfig {
+            rate_limit: Some(RateLimit { max_orders: 3, window: 1_000_000 }),
+            clock: Some(clock.clone()),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        let first = book.submit_quote("mm", 3900, 100, 4100, 100).unwrap();
+
+        // One submission is left in the window, not the two a quote needs
+        let limited = OrderBookError::RateLimited { retry_after_micros: 1_000_000 };
+        assert_eq!(book.submit_quote("mm", 4100, 50, 4300, 50).unwrap_err(), limited);
+        assert_eq!(book.quote("mm"), Some((first.bid.order.id, first.ask.order.id)));
+        assert_eq!(book.get_order_status(first.bid.order.id), Some(OrderStatus::Open));
+        assert_eq!(book.get_depth(5), (vec![(3900, 100)], vec![(4100, 100)]));
+
+        clock.advance(1_000_000);
+        let second = book.submit_quote("mm", 4000, 50, 4200, 50).unwrap();
+        assert_eq!(book.quote("mm"), Some((second.bid.order.id, second.ask.order.id)));
+        assert_eq!(book.get_depth(5), (vec![(4000, 50)], vec![(4200, 50)]));
+    }
+}
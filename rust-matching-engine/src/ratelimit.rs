@@ -0,0 +1,187 @@
+//! Per-user submission rate limits
+//!
+//! With `OrderBookConfig::rate_limit` set, each user may have at most
+//! `max_orders` orders accepted within any `window` microseconds of the
+//! book's clock; further submissions are rejected with
+//! `OrderBookError::RateLimited`, saying how long until the oldest accepted
+//! order leaves the window. Rejected submissions don't count.
+//!
+//! The limiter keeps at most `max_orders` timestamps per user, and forgets
+//! users with nothing left in the window as the map grows, so its memory is
+//! bounded by the users active within one window.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{OrderBook, OrderBookError, Timestamp, UserId};
+
+/// Users the limiter tracks before its first sweep for idle ones
+const MIN_SWEEP_USERS: usize = 64;
+
+/// Submission rate limit settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Orders a user may have accepted within one window (at least 1)
+    pub max_orders: usize,
+    /// Length of the sliding window, in microseconds
+    pub window: Timestamp,
+}
+
+/// Recent acceptance times per user
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    /// Acceptance times within the window, oldest first
+    recent: HashMap<UserId, VecDeque<Timestamp>>,
+    /// Map size that triggers the next sweep for idle users
+    next_sweep: usize,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self { limit, recent: HashMap::new(), next_sweep: MIN_SWEEP_USERS }
+    }
+
+    /// Acceptance times of a user still inside the window at `now`
+    fn in_window(&self, user_id: &str, now: Timestamp) -> impl Iterator<Item = Timestamp> + '_ {
+        let window = self.limit.window;
+        self.recent
+            .get(user_id)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(move |&at| now.saturating_sub(at) < window)
+    }
+
+    /// How long `user_id` must wait at `now` before `orders` more orders are
+    /// accepted (None = it may submit them now)
+    ///
+    /// More than `max_orders` orders never fit; they are told to wait until
+    /// everything now in the window has left it.
+    pub(crate) fn retry_after(
+        &self,
+        user_id: &str,
+        now: Timestamp,
+        orders: usize,
+    ) -> Option<Timestamp> {
+        let recent: Vec<Timestamp> = self.in_window(user_id, now).collect();
+        let excess = (recent.len() + orders).saturating_sub(self.limit.max_orders);
+        if excess == 0 {
+            return None;
+        }
+        // The oldest `excess` acceptances must leave the window first
+        let leaves = recent.get(excess - 1).or(recent.last()).copied().unwrap_or(now);
+        Some(leaves.saturating_add(self.limit.window).saturating_sub(now))
+    }
+
+    /// Count an accepted order
+    pub(crate) fn record(&mut self, user_id: &str, now: Timestamp) {
+        let window = self.limit.window;
+        let recent = self.recent.entry(user_id.to_string()).or_default();
+        while recent.front().is_some_and(|&at| now.saturating_sub(at) >= window) {
+            recent.pop_front();
+        }
+        recent.push_back(now);
+        while recent.len() > self.limit.max_orders {
+            recent.pop_front();
+        }
+
+        if self.recent.len() >= self.next_sweep {
+            self.recent.retain(|_, recent| {
+                recent.back().is_some_and(|&at| now.saturating_sub(at) < window)
+            });
+            self.next_sweep = (2 * self.recent.len()).max(MIN_SWEEP_USERS);
+        }
+    }
+}
+
+impl OrderBook {
+    /// Reject a submission from a user over the rate limit
+    pub(crate) fn check_rate(&self, user_id: &str) -> Result<(), OrderBookError> {
+        self.check_rate_for(user_id, 1)
+    }
+
+    /// Reject `orders` submissions from a user without room for all of them
+    pub(crate) fn check_rate_for(&self, user_id: &str, orders: usize) -> Result<(), OrderBookError> {
+        let Some(limiter) = &self.rate_limiter else {
+            return Ok(());
+        };
+        match limiter.retry_after(user_id, self.clock.now(), orders) {
+            Some(retry_after_micros) => Err(OrderBookError::RateLimited { retry_after_micros }),
+            None => Ok(()),
+        }
+    }
+
+    /// Count an accepted submission against its user's rate limit
+    pub(crate) fn record_submission(&mut self, user_id: &str) {
+        if let Some(limiter) = &mut self.rate_limiter {
+            limiter.record(user_id, self.clock.now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockClock, Order, OrderBookConfig, OrderId, Side};
+    use std::sync::Arc;
+
+    fn order(id: OrderId, user: &str) -> Order {
+        Order::with_timestamp(
+            id,
+            user.to_string(),
+            "market1".to_string(),
+            "YES".to_string(),
+            Side::Buy,
+            5000,
+            10,
+            id,
+        )
+    }
+
+    #[test]
+    fn test_rate_limit_rejects_until_window_passes() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let config = OrderBookConfig {
+            rate_limit: Some(RateLimit { max_orders: 3, window: 1_000 }),
+            clock: Some(clock.clone()),
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+
+        book.process_limit_order(order(1, "alice")).unwrap();
+        clock.advance(100);
+        book.process_limit_order(order(2, "alice")).unwrap();
+        // A rejection for another reason doesn't use up the allowance
+        assert!(book.process_limit_order(order(2, "alice")).is_err());
+        book.process_limit_order(order(3, "alice")).unwrap();
+
+        clock.advance(200);
+        let limited = OrderBookError::RateLimited { retry_after_micros: 700 };
+        assert_eq!(book.validate_order(&order(4, "alice")), Err(limited.clone()));
+        assert_eq!(book.process_limit_order(order(4, "alice")).unwrap_err(), limited);
+        // Other users are unaffected
+        book.process_limit_order(order(5, "bob")).unwrap();
+
+        // Order 1 leaves the window, making room for one more
+        clock.advance(700);
+        book.process_limit_order(order(6, "alice")).unwrap();
+        assert!(book.process_limit_order(order(7, "alice")).is_err());
+        assert_eq!(book.user_orders("alice"), vec![1, 2, 3, 6]);
+    }
+
+    #[test]
+    fn test_rate_limiter_forgets_idle_users() {
+        let mut limiter = RateLimiter::new(RateLimit { max_orders: 2, window: 100 });
+        for user in 0..MIN_SWEEP_USERS {
+            limiter.record(&format!("user{}", user), 0);
+        }
+        // The sweep triggered by the last of them finds them all active
+        assert_eq!(limiter.recent.len(), MIN_SWEEP_USERS);
+
+        for user in 0..MIN_SWEEP_USERS * 2 {
+            limiter.record(&format!("late{}", user), 1_000);
+        }
+        assert_eq!(limiter.recent.len(), MIN_SWEEP_USERS * 2);
+        assert_eq!(limiter.retry_after("user0", 1_000, 1), None);
+    }
+}
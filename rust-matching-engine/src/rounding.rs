@@ -0,0 +1,105 @@
+//! Rounding of fixed-point values
+//!
+//! Prices and quantities are integers, but some derived values are not: a
+//! midpoint between two basis-point prices, a fee in basis points of a
+//! notional, a pro-rata share of a fill. Every such division goes through
+//! [`RoundingMode::div`] so all features round the same way.
+
+/// How to round the result of an integer division
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    /// Round half-way values away from zero (2.5 -> 3)
+    HalfUp,
+    /// Round half-way values to the nearest even integer (2.5 -> 2, 3.5 -> 4)
+    ///
+    /// Unbiased over many operations, hence the default.
+    #[default]
+    HalfEven,
+    /// Always round down (2.9 -> 2)
+    Floor,
+    /// Always round up (2.1 -> 3)
+    Ceil,
+}
+
+impl RoundingMode {
+    /// Divide `numerator` by `denominator`, rounding the result with this mode
+    ///
+    /// # Panics
+    /// Panics if `denominator` is zero.
+    pub fn div(self, numerator: u128, denominator: u128) -> u128 {
+        assert!(denominator != 0, "division by zero");
+
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        if remainder == 0 {
+            return quotient;
+        }
+
+        // Compare 2 * remainder against the denominator without overflowing
+        let half_way = remainder.cmp(&(denominator - remainder));
+        let round_up = match self {
+            RoundingMode::Floor => false,
+            RoundingMode::Ceil => true,
+            RoundingMode::HalfUp => half_way != std::cmp::Ordering::Less,
+            RoundingMode::HalfEven => match half_way {
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Equal => quotient % 2 == 1,
+            },
+        };
+
+        if round_up {
+            quotient + 1
+        } else {
+            quotient
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_way_values() {
+        // 5 / 2 = 2.5, 7 / 2 = 3.5
+        assert_eq!(RoundingMode::HalfUp.div(5, 2), 3);
+        assert_eq!(RoundingMode::HalfUp.div(7, 2), 4);
+        assert_eq!(RoundingMode::HalfEven.div(5, 2), 2);
+        assert_eq!(RoundingMode::HalfEven.div(7, 2), 4);
+        assert_eq!(RoundingMode::Floor.div(5, 2), 2);
+        assert_eq!(RoundingMode::Floor.div(7, 2), 3);
+        assert_eq!(RoundingMode::Ceil.div(5, 2), 3);
+        assert_eq!(RoundingMode::Ceil.div(7, 2), 4);
+    }
+
+    #[test]
+    fn test_non_half_way_values() {
+        // 13 / 4 = 3.25, 15 / 4 = 3.75
+        for mode in [RoundingMode::HalfUp, RoundingMode::HalfEven] {
+            assert_eq!(mode.div(13, 4), 3);
+            assert_eq!(mode.div(15, 4), 4);
+        }
+        assert_eq!(RoundingMode::Floor.div(15, 4), 3);
+        assert_eq!(RoundingMode::Ceil.div(13, 4), 4);
+    }
+
+    #[test]
+    fn test_exact_division_is_unchanged() {
+        for mode in [
+            RoundingMode::HalfUp,
+            RoundingMode::HalfEven,
+            RoundingMode::Floor,
+            RoundingMode::Ceil,
+        ] {
+            assert_eq!(mode.div(6500, 100), 65);
+        }
+    }
+
+    #[test]
+    fn test_midpoint_of_basis_point_prices() {
+        // Midpoint of 6501 and 6504 is 6502.5
+        assert_eq!(RoundingMode::HalfUp.div(6501 + 6504, 2), 6503);
+        assert_eq!(RoundingMode::HalfEven.div(6501 + 6504, 2), 6502);
+    }
+}
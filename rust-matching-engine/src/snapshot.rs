@@ -0,0 +1,836 @@
+//! Point-in-time snapshots of an order book
+//!
+//! A [`BookSnapshot`] holds the book's primary state with every collection in
+//! a fixed order: levels by ascending price, orders within a level in queue
+//! order, and indexed orders by ascending id. Derived state (the per-user
+//! index, live counters) is left out since it can be rebuilt from the rest.
+//! [`BookSnapshot::encode`] writes it in a compact binary form, so identical
+//! book state always produces identical bytes, whatever the iteration order
+//! of the book's hash maps.
+//!
+//! [`OrderBook::from_snapshot`] rebuilds a book from a snapshot and refuses
+//! one that is inconsistent; [`OrderBook::from_snapshot_repaired`] loads it
+//! anyway, fixing what it can and listing each fix in a [`RepairReport`].
+//! Cancelled orders still queued under lazy deletion are compacted away on
+//! load, which is not a repair. Midpoint orders rest in no level, so a
+//! snapshot keeps their hidden queues apart, and loading queues them again.
+//! [`OrderBook::from_side_snapshot`] loads one side alone, for one-sided
+//! scenarios and recovery sharded by side.
+//!
+//! Every load carries on the trade id and order sequence numbering from
+//! where the snapshot left off, so trades and acks already published are
+//! never numbered again.
+
+use crate::{
+    BookOrder, IcebergSlicing, MarketId, Order, OrderBook, OrderBookConfig, OrderBookError,
+    OrderId, OrderMetadata, OrderStatus, OrderType, OutcomeId, Price, PriceLevelQueue, Quantity,
+    Side, TimeInForce, TradeId, UserId,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// All orders queued at one price level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelSnapshot {
+    /// Price of the level
+    pub price: Price,
+    /// Live quantity at the level
+    pub total_quantity: Quantity,
+    /// Queued orders, front first
+    pub orders: Vec<Order>,
+}
+
+/// One entry of the book's order index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderRecord {
+    /// Indexed order
+    pub order_id: OrderId,
+    /// User who placed the order
+    pub user_id: UserId,
+    /// Side of the book the order rests on
+    pub side: Side,
+    /// Price level the order rests at
+    pub price: Price,
+    /// Current status
+    pub status: OrderStatus,
+    /// Remaining quantity, including any iceberg reserve
+    pub remaining_quantity: Quantity,
+    /// The order's `client_order_id`
+    pub client_order_id: Option<String>,
+}
+
+/// The state of an order book at one instant, in a deterministic order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookSnapshot {
+    /// Market the book is for
+    pub market_id: MarketId,
+    /// Outcome the book is for
+    pub outcome_id: OutcomeId,
+    /// Bid levels, ascending price
+    pub bids: Vec<LevelSnapshot>,
+    /// Ask levels, ascending price
+    pub asks: Vec<LevelSnapshot>,
+    /// Midpoint-pegged orders, bids then asks, each in time order
+    pub midpoint: Vec<Order>,
+    /// Order index, ascending order id
+    pub orders: Vec<OrderRecord>,
+    /// Trades executed so far
+    pub total_trades: u64,
+    /// Volume traded so far
+    pub total_volume: Quantity,
+    /// Notional traded so far
+    pub total_notional: u128,
+    /// Id the next trade will get, so a restored book never reuses one
+    pub next_trade_id: TradeId,
+    /// Sequence number of the last accepted order
+    pub sequence: u64,
+}
+
+/// One inconsistency `from_snapshot_repaired` fixed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// A level's recorded total didn't fit its orders; the loaded level
+    /// totals the orders it kept
+    LevelTotal { side: Side, price: Price, recorded: Quantity, loaded: Quantity },
+    /// A filled or empty order was queued at a level, and was dropped
+    TerminalOrder { side: Side, price: Price, order_id: OrderId },
+    /// An order was queued at a level on another side or price, or a pegged
+    /// order at a level or a lit one in a midpoint queue, and was dropped
+    MisplacedOrder { side: Side, price: Price, order_id: OrderId },
+    /// A queued order failed the checks made on submission, and was dropped
+    InvalidOrder { order_id: OrderId, error: OrderBookError },
+}
+
+/// The repairs made loading a snapshot, in the order they were made
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RepairReport {
+    /// Each fix, levels bids first then asks, by ascending price
+    pub repairs: Vec<Repair>,
+}
+
+impl RepairReport {
+    /// Whether the snapshot loaded without repairs
+    pub fn is_clean(&self) -> bool {
+        self.repairs.is_empty()
+    }
+}
+
+impl OrderBook {
+    /// Rebuild a book from a snapshot
+    ///
+    /// Fails with `InconsistentSnapshot` if loading it would need any repair.
+    pub fn from_snapshot(
+        snapshot: &BookSnapshot,
+        config: OrderBookConfig,
+    ) -> Result<OrderBook, OrderBookError> {
+        let (book, report) = Self::from_snapshot_repaired(snapshot, config);
+        if !report.is_clean() {
+            return Err(OrderBookError::InconsistentSnapshot);
+        }
+        Ok(book)
+    }
+
+    /// Rebuild a book from a possibly corrupt snapshot, repairing what it must
+    ///
+    /// For disaster recovery, where a usable book matters more than strict
+    /// validation. Queued orders are loaded level by level, front first, then
+    /// the midpoint queues, and dropped if they are filled or empty, are
+    /// queued in the wrong place, or fail
+    /// the checks made on submission. Level totals are always recomputed from
+    /// the orders kept, and reported if the recorded total doesn't fit them.
+    /// Icebergs are sliced afresh, so their shown quantity only has to be
+    /// plausible. Filled and cancelled index records are kept as history.
+    ///
+    /// # Time Complexity
+    /// O(N) where N is the number of queued and indexed orders
+    pub fn from_snapshot_repaired(
+        snapshot: &BookSnapshot,
+        config: OrderBookConfig,
+    ) -> (OrderBook, RepairReport) {
+        Self::load_snapshot(snapshot, config, None)
+    }
+
+    /// Rebuild one side of a book from a snapshot, leaving the other empty
+    ///
+    /// Loads `side`'s levels and the index history of its orders, checked as
+    /// `from_snapshot` checks them. Trade statistics cover both sides, so
+    /// they are not restored. The book then trades normally, and the empty
+    /// side fills up with new orders as usual.
+    pub fn from_side_snapshot(
+        side: Side,
+        snapshot: &BookSnapshot,
+        config: OrderBookConfig,
+    ) -> Result<OrderBook, OrderBookError> {
+        let (book, report) = Self::load_snapshot(snapshot, config, Some(side));
+        if !report.is_clean() {
+            return Err(OrderBookError::InconsistentSnapshot);
+        }
+        Ok(book)
+    }
+
+    /// Load a snapshot's levels and index, on `only` one side if given
+    fn load_snapshot(
+        snapshot: &BookSnapshot,
+        config: OrderBookConfig,
+        only: Option<Side>,
+    ) -> (OrderBook, RepairReport) {
+        let loads = |side: Side| only.is_none_or(|only| only == side);
+        let mut book = OrderBook::with_config(
+            snapshot.market_id.clone(),
+            snapshot.outcome_id.clone(),
+            config,
+        );
+        let mut report = RepairReport::default();
+        // The index is authoritative for status: lazy deletion only marks it
+        let status: HashMap<OrderId, OrderStatus> =
+            snapshot.orders.iter().map(|record| (record.order_id, record.status)).collect();
+
+        let sides = [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)];
+        for (side, levels) in sides.into_iter().filter(|&(side, _)| loads(side)) {
+            for level in levels {
+                let (mut low, mut high) = (0, 0);
+                for order in &level.orders {
+                    let status = status.get(&order.id).copied().unwrap_or(order.status);
+                    if !book.load_queued(order, status, side, level.price, false, &mut report) {
+                        continue;
+                    }
+                    let remaining = order.remaining_quantity;
+                    let shown = order.display_quantity.map_or(remaining, |d| remaining.min(d));
+                    low += if shown < remaining { 1 } else { remaining };
+                    high += shown;
+                }
+
+                let loaded = book.book(side).get(&level.price).map_or(0, |l| l.total_quantity);
+                if !(low..=high).contains(&level.total_quantity) {
+                    report.repairs.push(Repair::LevelTotal {
+                        side,
+                        price: level.price,
+                        recorded: level.total_quantity,
+                        loaded,
+                    });
+                }
+            }
+        }
+
+        for order in snapshot.midpoint.iter().filter(|order| loads(order.side)) {
+            let status = status.get(&order.id).copied().unwrap_or(order.status);
+            book.load_queued(order, status, order.side, order.price, true, &mut report);
+        }
+
+        for record in &snapshot.orders {
+            if matches!(record.status, OrderStatus::Filled | OrderStatus::Cancelled)
+                && loads(record.side)
+                && !book.order_index.contains_key(&record.order_id)
+            {
+                book.order_index.insert(
+                    record.order_id,
+                    OrderMetadata {
+                        user_id: record.user_id.clone(),
+                        side: record.side,
+                        price: record.price,
+                        status: record.status,
+                        remaining_quantity: 0,
+                        hidden_quantity: 0,
+                        client_order_id: record.client_order_id.clone(),
+                        timestamp: 0,
+                        pegged: false,
+                        filled_quantity: 0,
+                    },
+                );
+                book.archive_orders(&[record.order_id]);
+            }
+        }
+        book.next_trade_id = snapshot.next_trade_id.max(1);
+        book.sequence = snapshot.sequence;
+        if only.is_none() {
+            book.total_trades = snapshot.total_trades;
+            book.total_volume = snapshot.total_volume;
+            book.total_notional = snapshot.total_notional;
+        }
+        (book, report)
+    }
+
+    /// Queue one snapshot order as it was, unless it needs a repair
+    ///
+    /// `side` and `price` are where the snapshot queued it, in a midpoint
+    /// queue if `pegged`. Returns whether the order was queued; cancelled
+    /// orders are skipped without a repair.
+    fn load_queued(
+        &mut self,
+        order: &Order,
+        status: OrderStatus,
+        side: Side,
+        price: Price,
+        pegged: bool,
+        report: &mut RepairReport,
+    ) -> bool {
+        if status == OrderStatus::Cancelled {
+            return false;
+        }
+        let order_id = order.id;
+        let repair = if status == OrderStatus::Filled || order.remaining_quantity == 0 {
+            Some(Repair::TerminalOrder { side, price, order_id })
+        } else if order.side != side
+            || order.price != price
+            || (order.order_type == OrderType::MidpointPeg) != pegged
+        {
+            Some(Repair::MisplacedOrder { side, price, order_id })
+        } else {
+            self.check_order(order).err().map(|error| Repair::InvalidOrder { order_id, error })
+        };
+        if let Some(repair) = repair {
+            report.repairs.push(repair);
+            return false;
+        }
+
+        self.next_order_id = self.next_order_id.max(order_id.saturating_add(1));
+        let mut order = BookOrder::from(order);
+        order.status = status;
+        self.add_to_book(order);
+        true
+    }
+
+    /// Capture the book's current state
+    ///
+    /// # Time Complexity
+    /// O(N log N) where N is the number of indexed orders (sorting the index)
+    pub fn snapshot(&self) -> BookSnapshot {
+        let levels = |book: &BTreeMap<Price, PriceLevelQueue>| {
+            book.iter()
+                .map(|(&price, level)| LevelSnapshot {
+                    price,
+                    total_quantity: level.total_quantity,
+                    orders: level
+                        .orders
+                        .iter()
+                        .map(|order| order.to_order(&self.market_id, &self.outcome_id))
+                        .collect(),
+                })
+                .collect()
+        };
+
+        let mut orders: Vec<OrderRecord> = self
+            .order_index
+            .iter()
+            .map(|(&order_id, m)| OrderRecord::new(order_id, m))
+            .collect();
+        orders.sort_unstable_by_key(|record| record.order_id);
+
+        BookSnapshot {
+            market_id: self.market_id.clone(),
+            outcome_id: self.outcome_id.clone(),
+            bids: levels(&self.bids),
+            asks: levels(&self.asks),
+            midpoint: self
+                .midpoint_orders
+                .iter()
+                .map(|order| order.to_order(&self.market_id, &self.outcome_id))
+                .collect(),
+            orders,
+            total_trades: self.total_trades,
+            total_volume: self.total_volume,
+            total_notional: self.total_notional,
+            next_trade_id: self.next_trade_id,
+            sequence: self.sequence,
+        }
+    }
+}
+
+impl OrderBook {
+    /// The live orders at one price level, front first
+    ///
+    /// Cancelled orders still queued under lazy deletion are left out, so the
+    /// snapshot can be replayed with `import_level`. Returns None if the level
+    /// has no live orders.
+    ///
+    /// # Time Complexity
+    /// O(log P + K) where K is the number of orders queued at the level
+    pub fn level_snapshot(&self, side: Side, price: Price) -> Option<LevelSnapshot> {
+        let level = self.book(side).get(&price)?;
+        let orders: Vec<(Order, Quantity)> = level
+            .orders
+            .iter()
+            .filter_map(|order| {
+                let metadata = self.order_index.get(&order.id)?;
+                if !matches!(metadata.status, OrderStatus::Open | OrderStatus::PartiallyFilled) {
+                    return None;
+                }
+                let mut order = order.to_order(&self.market_id, &self.outcome_id);
+                order.remaining_quantity = metadata.total_remaining();
+                order.status = metadata.status;
+                Some((order, metadata.remaining_quantity))
+            })
+            .collect();
+        if orders.is_empty() {
+            return None;
+        }
+
+        // Icebergs' reserves don't count towards the level
+        let (orders, shown): (Vec<Order>, Vec<Quantity>) = orders.into_iter().unzip();
+        Some(LevelSnapshot {
+            price,
+            total_quantity: shown.iter().sum(),
+            orders,
+        })
+    }
+
+    /// Queue a level's orders on `side` without matching them, front first
+    ///
+    /// The counterpart of `level_snapshot`, for reproducing one level in
+    /// isolation. Orders keep their ids, timestamps, quantities and status,
+    /// and go behind any already live at the price. Each order is validated
+    /// as on submission, must be on `side` at the level's price
+    /// (`IncompatiblePrice` otherwise), and the level must not cross the
+    /// opposite side. Nothing is imported unless every order is accepted.
+    pub fn import_level(
+        &mut self,
+        side: Side,
+        level: &LevelSnapshot,
+    ) -> Result<(), OrderBookError> {
+        self.check_resting_price(side, level.price)?;
+        let mut ids = HashSet::new();
+        for order in &level.orders {
+            if order.side != side || order.price != level.price {
+                return Err(OrderBookError::IncompatiblePrice);
+            }
+            self.check_order(order)?;
+            if !ids.insert(order.id) {
+                return Err(OrderBookError::DuplicateOrderId(order.id));
+            }
+        }
+
+        for order in &level.orders {
+            self.next_order_id = self.next_order_id.max(order.id.saturating_add(1));
+            self.add_to_book(BookOrder::from(order));
+        }
+        self.poll_depth_updates();
+        Ok(())
+    }
+
+    /// Look up an indexed order (live, filled while resting, or cancelled)
+    ///
+    /// # Time Complexity
+    /// O(1)
+    pub fn get_order(&self, order_id: OrderId) -> Option<OrderRecord> {
+        self.order_index.get(&order_id).map(|m| OrderRecord::new(order_id, m))
+    }
+}
+
+impl OrderRecord {
+    fn new(order_id: OrderId, metadata: &OrderMetadata) -> Self {
+        Self {
+            order_id,
+            user_id: metadata.user_id.clone(),
+            side: metadata.side,
+            price: metadata.price,
+            status: metadata.status,
+            remaining_quantity: metadata.total_remaining(),
+            client_order_id: metadata.client_order_id.clone(),
+        }
+    }
+}
+
+impl BookSnapshot {
+    /// Encode the snapshot as bytes
+    ///
+    /// Integers are little-endian `u64` (`u128` for the notional), strings
+    /// and sequences are prefixed with their length, and options with a 0/1
+    /// tag. Every field of every order is written, so equal snapshots always
+    /// encode to equal bytes and differing ones to differing bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_str(&mut out, &self.market_id);
+        put_str(&mut out, &self.outcome_id);
+        for levels in [&self.bids, &self.asks] {
+            put_u64(&mut out, levels.len() as u64);
+            for level in levels {
+                put_u64(&mut out, level.price);
+                put_u64(&mut out, level.total_quantity);
+                put_u64(&mut out, level.orders.len() as u64);
+                for order in &level.orders {
+                    put_order(&mut out, order);
+                }
+            }
+        }
+        put_u64(&mut out, self.midpoint.len() as u64);
+        for order in &self.midpoint {
+            put_order(&mut out, order);
+        }
+        put_u64(&mut out, self.orders.len() as u64);
+        for record in &self.orders {
+            put_u64(&mut out, record.order_id);
+            put_str(&mut out, &record.user_id);
+            out.push(side_tag(record.side));
+            put_u64(&mut out, record.price);
+            out.push(status_tag(record.status));
+            put_u64(&mut out, record.remaining_quantity);
+            put_opt_str(&mut out, record.client_order_id.as_deref());
+        }
+        put_u64(&mut out, self.total_trades);
+        put_u64(&mut out, self.total_volume);
+        out.extend_from_slice(&self.total_notional.to_le_bytes());
+        put_u64(&mut out, self.next_trade_id);
+        put_u64(&mut out, self.sequence);
+        out
+    }
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_u64(out, value.len() as u64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn put_opt(out: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            put_u64(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+fn put_opt_str(out: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            put_str(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+fn put_order(out: &mut Vec<u8>, order: &Order) {
+    put_u64(out, order.id);
+    put_str(out, &order.user_id);
+    put_str(out, &order.market_id);
+    put_str(out, &order.outcome_id);
+    out.push(side_tag(order.side));
+    put_u64(out, order.price);
+    put_u64(out, order.original_quantity);
+    put_u64(out, order.remaining_quantity);
+    put_u64(out, order.timestamp);
+    out.push(status_tag(order.status));
+    put_opt(out, order.last_look_bps);
+    put_opt(out, order.max_levels.map(|levels| levels as u64));
+    put_opt_str(out, order.client_order_id.as_deref());
+    out.push(match order.order_type {
+        OrderType::Limit => 0,
+        OrderType::Market => 1,
+        OrderType::MidpointPeg => 2,
+    });
+    put_opt(out, order.max_slippage_bps);
+    put_opt(out, order.max_rest_quantity);
+    out.push(match order.time_in_force {
+        TimeInForce::GoodTilCancelled => 0,
+        TimeInForce::FillAndKill => 1,
+    });
+    put_opt(out, order.display_quantity);
+    out.push(match order.iceberg_slicing {
+        IcebergSlicing::Remainder => 0,
+        IcebergSlicing::EqualSlices => 1,
+    });
+}
+
+fn side_tag(side: Side) -> u8 {
+    match side {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    }
+}
+
+fn status_tag(status: OrderStatus) -> u8 {
+    match status {
+        OrderStatus::Open => 0,
+        OrderStatus::PartiallyFilled => 1,
+        OrderStatus::Filled => 2,
+        OrderStatus::Cancelled => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+
+    fn build_book() -> OrderBook {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        for id in 1..=20 {
+            let side = if id % 2 == 0 { Side::Buy } else { Side::Sell };
+            let price = if side == Side::Buy { 4900 - id * 10 } else { 5100 + id * 10 };
+            let order = Order::with_timestamp(
+                id,
+                format!("user{}", id % 5),
+                "market1".to_string(),
+                "YES".to_string(),
+                side,
+                price,
+                10 * id,
+                id,
+            );
+            book.process_limit_order(order).unwrap();
+        }
+        book.cancel_order(7).unwrap();
+        book.cancel_order(12).unwrap();
+        book
+    }
+
+    #[test]
+    fn test_snapshot_encoding_is_deterministic() {
+        let book = build_book();
+        assert_eq!(book.snapshot().encode(), book.snapshot().encode());
+
+        // A separately built book has different hash map iteration order
+        let other = build_book();
+        assert_eq!(book.snapshot().encode(), other.snapshot().encode());
+    }
+
+    #[test]
+    fn test_encoding_covers_every_order_field() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let mut iceberg = order(1, Side::Sell, 5100, 250);
+        iceberg.display_quantity = Some(100);
+        book.process_limit_order(iceberg).unwrap();
+
+        let snapshot = book.snapshot();
+        let mut sliced = snapshot.clone();
+        sliced.asks[0].orders[0].iceberg_slicing = IcebergSlicing::EqualSlices;
+        assert_ne!(snapshot.encode(), sliced.encode());
+        let mut shown = snapshot.clone();
+        shown.asks[0].orders[0].display_quantity = Some(50);
+        assert_ne!(snapshot.encode(), shown.encode());
+    }
+
+    #[test]
+    fn test_snapshot_is_sorted() {
+        let snapshot = build_book().snapshot();
+
+        let ids: Vec<OrderId> = snapshot.orders.iter().map(|r| r.order_id).collect();
+        assert_eq!(ids, (1..=20).collect::<Vec<_>>());
+        assert!(snapshot.bids.windows(2).all(|w| w[0].price < w[1].price));
+        assert!(snapshot.asks.windows(2).all(|w| w[0].price < w[1].price));
+        assert_eq!(snapshot.orders[6].status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_level_round_trip_reproduces_matching() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        for (id, quantity) in [(1, 40), (2, 30), (3, 50), (4, 20)] {
+            let order = Order::with_timestamp(
+                id,
+                format!("user{}", id),
+                "market1".to_string(),
+                "YES".to_string(),
+                Side::Sell,
+                5000,
+                quantity,
+                id,
+            );
+            book.process_limit_order(order).unwrap();
+        }
+        book.cancel_order(2).unwrap();
+        let taker = |id, quantity| {
+            Order::with_timestamp(
+                id,
+                "taker".to_string(),
+                "market1".to_string(),
+                "YES".to_string(),
+                Side::Buy,
+                5000,
+                quantity,
+                id,
+            )
+        };
+        book.process_limit_order(taker(5, 10)).unwrap();
+
+        let level = book.level_snapshot(Side::Sell, 5000).unwrap();
+        let ids: Vec<OrderId> = level.orders.iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+        assert_eq!((level.orders[0].remaining_quantity, level.total_quantity), (30, 100));
+        assert_eq!(book.level_snapshot(Side::Buy, 5000), None);
+
+        let mut isolated = OrderBook::new("market1".to_string(), "YES".to_string());
+        isolated.import_level(Side::Sell, &level).unwrap();
+        assert_eq!(isolated.level_snapshot(Side::Sell, 5000), Some(level.clone()));
+        assert_eq!(
+            isolated.import_level(Side::Sell, &level),
+            Err(OrderBookError::DuplicateOrderId(1))
+        );
+
+        let fills = |book: &mut OrderBook| {
+            let result = book.process_limit_order(taker(6, 90)).unwrap();
+            result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect::<Vec<_>>()
+        };
+        assert_eq!(fills(&mut book), vec![(1, 30), (3, 50), (4, 10)]);
+        assert_eq!(fills(&mut isolated), vec![(1, 30), (3, 50), (4, 10)]);
+    }
+
+    #[test]
+    fn test_consistent_snapshot_round_trips() {
+        let book = build_book();
+        let loaded = OrderBook::from_snapshot(&book.snapshot(), Default::default()).unwrap();
+        for side in [Side::Buy, Side::Sell] {
+            let levels = |book: &OrderBook| book.live_levels(side).collect::<Vec<_>>();
+            assert_eq!(levels(&loaded), levels(&book));
+        }
+        assert_eq!(loaded.active_orders(), book.active_orders());
+        assert_eq!(loaded.get_order(7), book.get_order(7));
+        assert_eq!(loaded.user_orders("user1"), book.user_orders("user1"));
+        assert!(loaded.is_healthy());
+    }
+
+    #[test]
+    fn test_restored_book_continues_numbering() {
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        let order = |id, side| {
+            Order::with_timestamp(
+                id,
+                format!("user{}", id),
+                "market1".to_string(),
+                "YES".to_string(),
+                side,
+                5000,
+                10,
+                id,
+            )
+        };
+        for id in 1..=50 {
+            book.process_limit_order(order(2 * id - 1, Side::Sell)).unwrap();
+            let result = book.process_limit_order(order(2 * id, Side::Buy)).unwrap();
+            assert_eq!(result.trades[0].id, id);
+        }
+        book.process_limit_order(order(101, Side::Sell)).unwrap();
+
+        let snapshot = book.snapshot();
+        assert_eq!((snapshot.next_trade_id, snapshot.sequence), (51, 101));
+        for only in [None, Some(Side::Sell)] {
+            let mut loaded = match only {
+                None => OrderBook::from_snapshot(&snapshot, Default::default()).unwrap(),
+                Some(side) => {
+                    OrderBook::from_side_snapshot(side, &snapshot, Default::default()).unwrap()
+                }
+            };
+            let result = loaded.process_limit_order(order(102, Side::Buy)).unwrap();
+            assert_eq!(result.trades[0].id, 51);
+            assert_eq!(result.ack.sequence, 102);
+        }
+    }
+
+    #[test]
+    fn test_repaired_load_fixes_and_reports() {
+        let mut snapshot = build_book().snapshot();
+        // Best ask 5110 (order 1); the level above it is 5130 (order 3)
+        snapshot.asks[0].total_quantity = 999;
+        snapshot.asks[1].orders[0].remaining_quantity = 0;
+        let misplaced = snapshot.asks[0].orders[0].clone();
+        snapshot.asks[1].orders.push(misplaced);
+        let mut foreign = snapshot.asks[2].orders[0].clone();
+        foreign.id = 100;
+        foreign.market_id = "market2".to_string();
+        snapshot.asks[2].orders.push(foreign);
+        snapshot.asks[2].total_quantity += 10;
+        let order_5 = snapshot.asks[2].orders[0].id;
+
+        assert_eq!(
+            OrderBook::from_snapshot(&snapshot, Default::default()).unwrap_err(),
+            OrderBookError::InconsistentSnapshot
+        );
+        let (book, report) = OrderBook::from_snapshot_repaired(&snapshot, Default::default());
+        assert_eq!(
+            report.repairs,
+            vec![
+                Repair::LevelTotal { side: Side::Sell, price: 5110, recorded: 999, loaded: 10 },
+                Repair::TerminalOrder { side: Side::Sell, price: 5130, order_id: 3 },
+                Repair::MisplacedOrder { side: Side::Sell, price: 5130, order_id: 1 },
+                Repair::LevelTotal { side: Side::Sell, price: 5130, recorded: 30, loaded: 0 },
+                Repair::InvalidOrder { order_id: 100, error: OrderBookError::MarketMismatch },
+                Repair::LevelTotal { side: Side::Sell, price: 5150, recorded: 60, loaded: 50 },
+            ]
+        );
+
+        let asks: Vec<_> = book.live_levels(Side::Sell).take(2).collect();
+        assert_eq!(asks, vec![(5110, 10), (5150, 50)]);
+        assert_eq!(book.get_order_status(3), None);
+        assert_eq!(book.get_order_remaining(order_5), Some(50));
+        assert!(book.is_healthy());
+    }
+
+    #[test]
+    fn test_snapshot_restores_midpoint_orders() {
+        let pegged = |id, side, limit, quantity| {
+            let mut order = order(id, side, limit, quantity);
+            order.order_type = OrderType::MidpointPeg;
+            order
+        };
+        let mut book = OrderBook::new("market1".to_string(), "YES".to_string());
+        book.process_limit_order(order(1, Side::Buy, 4900, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Sell, 5100, 100)).unwrap();
+        book.process_limit_order(pegged(3, Side::Buy, 5000, 50)).unwrap();
+        book.process_limit_order(pegged(4, Side::Sell, 5100, 50)).unwrap();
+        book.process_limit_order(order(5, Side::Sell, 4900, 20)).unwrap();
+
+        let mut snapshot = book.snapshot();
+        let ids: Vec<OrderId> = snapshot.midpoint.iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![3, 4]);
+        let mut loaded = OrderBook::from_snapshot(&snapshot, Default::default()).unwrap();
+        assert_eq!(loaded.snapshot(), snapshot);
+        assert_eq!(loaded.midpoint_quantity(Side::Buy), 30);
+
+        let fills = |book: &mut OrderBook| {
+            let result = book.process_limit_order(order(6, Side::Sell, 4900, 40)).unwrap();
+            result.trades.iter().map(|t| (t.maker_order_id, t.price)).collect::<Vec<_>>()
+        };
+        assert_eq!(fills(&mut book), vec![(3, 5000), (1, 4900)]);
+        assert_eq!(fills(&mut loaded), vec![(3, 5000), (1, 4900)]);
+
+        // A lit order has no place in a midpoint queue
+        let mut lit = snapshot.asks[0].orders[0].clone();
+        lit.id = 100;
+        snapshot.midpoint.push(lit);
+        let (loaded, report) = OrderBook::from_snapshot_repaired(&snapshot, Default::default());
+        assert_eq!(
+            report.repairs,
+            vec![Repair::MisplacedOrder { side: Side::Sell, price: 5100, order_id: 100 }]
+        );
+        assert_eq!(loaded.get_order(100), None);
+    }
+
+    #[test]
+    fn test_side_snapshot_loads_only_asks() {
+        let book = build_book();
+        let snapshot = book.snapshot();
+        let mut asks = OrderBook::from_side_snapshot(Side::Sell, &snapshot, Default::default())
+            .unwrap();
+        assert_eq!(
+            asks.live_levels(Side::Sell).collect::<Vec<_>>(),
+            book.live_levels(Side::Sell).collect::<Vec<_>>()
+        );
+        assert_eq!(asks.best_bid(), None);
+        // Bid history stays behind with the bids; ask history comes along
+        assert_eq!(asks.get_order(2), None);
+        assert_eq!(asks.get_order_status(7), Some(OrderStatus::Cancelled));
+        assert_eq!(asks.get_order_status(12), None);
+        assert_eq!(asks.active_orders(), 9);
+
+        // A buy sweeps the asks up to 5150 and rests what is left
+        let buy = Order::with_timestamp(
+            100,
+            "buyer".to_string(),
+            "market1".to_string(),
+            "YES".to_string(),
+            Side::Buy,
+            5150,
+            100,
+            100,
+        );
+        let result = asks.process_limit_order(buy).unwrap();
+        let fills: Vec<_> = result.trades.iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(1, 10), (3, 30), (5, 50)]);
+        assert_eq!(asks.best_bid(), Some(5150));
+        assert_eq!(asks.get_order_remaining(100), Some(10));
+        assert!(asks.is_healthy());
+    }
+}
@@ -0,0 +1,29 @@
+//! Order fixtures shared by the unit tests
+
+use crate::{Order, OrderId, Price, Quantity, Side, Timestamp};
+
+/// An order for the `market1` YES book
+pub(crate) fn create_test_order(
+    id: OrderId,
+    user_id: &str,
+    side: Side,
+    price: Price,
+    quantity: Quantity,
+    timestamp: Timestamp,
+) -> Order {
+    Order::with_timestamp(
+        id,
+        user_id.to_string(),
+        "market1".to_string(),
+        "YES".to_string(),
+        side,
+        price,
+        quantity,
+        timestamp,
+    )
+}
+
+/// An order for the `market1` YES book from `user<id>`, timestamped `id`
+pub(crate) fn order(id: OrderId, side: Side, price: Price, quantity: Quantity) -> Order {
+    create_test_order(id, &format!("user{}", id), side, price, quantity, id)
+}
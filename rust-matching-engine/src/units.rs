@@ -0,0 +1,160 @@
+//! Type-safe price and quantity wrappers
+//!
+//! The core API uses bare `u64` aliases (`crate::Price`, `crate::Quantity`),
+//! which makes it easy to pass a quantity where a price is expected. These
+//! newtypes catch that class of bug at compile time for callers who opt in,
+//! and convert losslessly to and from the raw aliases at the API boundary.
+//!
+//! ```
+//! use matching_engine::units::{Price, Qty};
+//!
+//! let price = Price::from_dollars(0.65);
+//! assert_eq!(price, Price(6500));
+//! assert_eq!(price * Qty(10), 65_000);
+//! ```
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+
+/// Basis points per dollar (1 bp = $0.0001)
+pub const BASIS_POINTS_PER_DOLLAR: u64 = 10_000;
+
+/// A price in basis points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Price(pub u64);
+
+/// A quantity of shares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Qty(pub u64);
+
+impl Price {
+    /// Convert a dollar amount to basis points, rounding to the nearest basis point
+    ///
+    /// Negative amounts, NaN and infinities give zero; amounts too large for
+    /// a `u64` saturate to `u64::MAX`.
+    pub fn from_dollars(dollars: f64) -> Self {
+        if !dollars.is_finite() {
+            return Price(0);
+        }
+        Price((dollars * BASIS_POINTS_PER_DOLLAR as f64).round() as u64)
+    }
+
+    /// Convert basis points back to dollars
+    pub fn to_dollars(self) -> f64 {
+        self.0 as f64 / BASIS_POINTS_PER_DOLLAR as f64
+    }
+
+    /// Raw basis-point value for the core API
+    pub fn bps(self) -> crate::Price {
+        self.0
+    }
+}
+
+impl Qty {
+    /// Raw share count for the core API
+    pub fn shares(self) -> crate::Quantity {
+        self.0
+    }
+}
+
+macro_rules! impl_arithmetic {
+    ($ty:ident) => {
+        impl Add for $ty {
+            type Output = $ty;
+            fn add(self, rhs: $ty) -> $ty {
+                $ty(self.0 + rhs.0)
+            }
+        }
+
+        impl Sub for $ty {
+            type Output = $ty;
+            fn sub(self, rhs: $ty) -> $ty {
+                $ty(self.0 - rhs.0)
+            }
+        }
+
+        impl AddAssign for $ty {
+            fn add_assign(&mut self, rhs: $ty) {
+                self.0 += rhs.0;
+            }
+        }
+
+        impl SubAssign for $ty {
+            fn sub_assign(&mut self, rhs: $ty) {
+                self.0 -= rhs.0;
+            }
+        }
+
+        impl From<u64> for $ty {
+            fn from(value: u64) -> $ty {
+                $ty(value)
+            }
+        }
+
+        impl From<$ty> for u64 {
+            fn from(value: $ty) -> u64 {
+                value.0
+            }
+        }
+    };
+}
+
+impl_arithmetic!(Price);
+impl_arithmetic!(Qty);
+
+/// Notional value (basis points x shares); widened so it cannot overflow
+impl Mul<Qty> for Price {
+    type Output = u128;
+    fn mul(self, rhs: Qty) -> u128 {
+        self.0 as u128 * rhs.0 as u128
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bps", self.0)
+    }
+}
+
+impl fmt::Display for Qty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} shares", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dollar_round_trip() {
+        assert_eq!(Price::from_dollars(0.65), Price(6500));
+        assert_eq!(Price(6500).to_dollars(), 0.65);
+        assert_eq!(Price::from_dollars(0.01), Price(100));
+        assert_eq!(Price::from_dollars(0.99), Price(9900));
+
+        for bps in [1, 100, 5000, 6500, 9999] {
+            assert_eq!(Price::from_dollars(Price(bps).to_dollars()), Price(bps));
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_dollars() {
+        assert_eq!(Price::from_dollars(-0.65), Price(0));
+        assert_eq!(Price::from_dollars(f64::NAN), Price(0));
+        assert_eq!(Price::from_dollars(f64::INFINITY), Price(0));
+        assert_eq!(Price::from_dollars(f64::NEG_INFINITY), Price(0));
+        assert_eq!(Price::from_dollars(1e300), Price(u64::MAX));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut qty = Qty(100) + Qty(50);
+        qty -= Qty(30);
+        assert_eq!(qty, Qty(120));
+        assert_eq!(Price(6600) - Price(6500), Price(100));
+        assert_eq!(Price(u64::MAX) * Qty(2), u64::MAX as u128 * 2);
+        assert_eq!(Price(6500).bps(), 6500);
+        assert_eq!(u64::from(Qty(7)), 7);
+    }
+}
@@ -0,0 +1,163 @@
+//! Immutable read views of an order book
+//!
+//! The book's maps are private and borrowed mutably while matching, so tools
+//! that inspect a live book (reporting, monitoring) get a [`BookView`]: a copy
+//! of the observable state taken at one instant. Views are behind an `Arc`,
+//! so clones are cheap and can be handed to other threads or tasks.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    Depth, MarketId, OrderBook, OrderId, OrderMetadata, OrderStatus, OutcomeId, Price, Quantity,
+    Trade,
+};
+
+/// Read-only snapshot of an order book, see [`OrderBook::frozen`]
+#[derive(Debug, Clone)]
+pub struct BookView {
+    inner: Arc<ViewInner>,
+}
+
+#[derive(Debug)]
+struct ViewInner {
+    market_id: MarketId,
+    outcome_id: OutcomeId,
+    /// Bid levels, best (highest) first
+    bids: Vec<(Price, Quantity)>,
+    /// Ask levels, best (lowest) first
+    asks: Vec<(Price, Quantity)>,
+    orders: HashMap<OrderId, OrderMetadata>,
+    trades: Vec<Trade>,
+    total_trades: u64,
+    total_volume: u64,
+}
+
+impl OrderBook {
+    /// Take a read-only view of the book's current state
+    ///
+    /// Later changes to the book are not reflected in the view.
+    pub fn frozen(&self) -> BookView {
+        let (bids, asks) = self.depth(usize::MAX);
+        BookView {
+            inner: Arc::new(ViewInner {
+                market_id: self.market_id.clone(),
+                outcome_id: self.outcome_id.clone(),
+                bids,
+                asks,
+                orders: self.order_index.clone(),
+                trades: self.trade_history.iter().cloned().collect(),
+                total_trades: self.total_trades,
+                total_volume: self.total_volume,
+            }),
+        }
+    }
+}
+
+impl BookView {
+    /// Market the book belongs to
+    pub fn market_id(&self) -> &str {
+        &self.inner.market_id
+    }
+
+    /// Outcome the book trades
+    pub fn outcome_id(&self) -> &str {
+        &self.inner.outcome_id
+    }
+
+    /// Best bid price at freeze time
+    pub fn best_bid(&self) -> Option<Price> {
+        self.inner.bids.first().map(|&(price, _)| price)
+    }
+
+    /// Best ask price at freeze time
+    pub fn best_ask(&self) -> Option<Price> {
+        self.inner.asks.first().map(|&(price, _)| price)
+    }
+
+    /// Spread between best bid and best ask
+    pub fn spread(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) if ask > bid => Some(ask - bid),
+            _ => None,
+        }
+    }
+
+    /// Up to `levels` price levels per side, best first, as `OrderBook::get_depth`
+    pub fn get_depth(&self, levels: usize) -> Depth {
+        let bids = self.inner.bids.iter().take(levels).copied().collect();
+        let asks = self.inner.asks.iter().take(levels).copied().collect();
+        (bids, asks)
+    }
+
+    /// Status of an order at freeze time
+    pub fn get_order_status(&self, order_id: OrderId) -> Option<OrderStatus> {
+        self.inner.orders.get(&order_id).map(|m| m.status)
+    }
+
+    /// Remaining quantity of an order at freeze time
+    pub fn get_order_remaining(&self, order_id: OrderId) -> Option<Quantity> {
+        self.inner.orders.get(&order_id).map(OrderMetadata::total_remaining)
+    }
+
+    /// Up to the `n` most recent retained trades, oldest first
+    pub fn recent_trades(&self, n: usize) -> &[Trade] {
+        let trades = &self.inner.trades;
+        &trades[trades.len().saturating_sub(n)..]
+    }
+
+    /// Number of trades executed before the freeze
+    pub fn total_trades(&self) -> u64 {
+        self.inner.total_trades
+    }
+
+    /// Volume traded before the freeze
+    pub fn total_volume(&self) -> u64 {
+        self.inner.total_volume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::order;
+    use crate::{OrderBookConfig, Side};
+
+    #[test]
+    fn test_view_reflects_state_at_freeze_time() {
+        let config = OrderBookConfig {
+            trade_history_capacity: 10,
+            ..Default::default()
+        };
+        let mut book = OrderBook::with_config("market1".to_string(), "YES".to_string(), config);
+        book.process_limit_order(order(1, Side::Sell, 5100, 100)).unwrap();
+        book.process_limit_order(order(2, Side::Buy, 5000, 100)).unwrap();
+        book.process_limit_order(order(3, Side::Buy, 5100, 40)).unwrap();
+
+        let view = book.frozen();
+
+        // Mutations after the freeze are not visible
+        book.cancel_order(1).unwrap();
+        book.process_limit_order(order(4, Side::Buy, 4900, 10)).unwrap();
+
+        assert_eq!(view.best_bid(), Some(5000));
+        assert_eq!(view.best_ask(), Some(5100));
+        assert_eq!(view.spread(), Some(100));
+        assert_eq!(view.get_depth(5), (vec![(5000, 100)], vec![(5100, 60)]));
+        assert_eq!(view.get_order_status(1), Some(OrderStatus::PartiallyFilled));
+        assert_eq!(view.get_order_remaining(1), Some(60));
+        assert_eq!(view.get_order_status(4), None);
+        assert_eq!(view.recent_trades(5).len(), 1);
+        assert_eq!(view.total_volume(), 40);
+
+        let clone = view.clone();
+        assert_eq!(clone.market_id(), "market1");
+        assert_eq!(clone.get_depth(1), view.get_depth(1));
+    }
+
+    #[test]
+    fn test_view_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<BookView>();
+    }
+}